@@ -21,6 +21,11 @@ layout(set = 2, binding = 3) uniform texture2D HexMaterial_background_texture;
 layout(set = 2, binding = 4) uniform sampler HexMaterial_background_texture_sampler;
 layout(set = 2, binding = 5) uniform utexture2D HexMaterial_map_state;
 layout(set = 2, binding = 6) uniform sampler HexMaterial_map_state_sampler;
+layout(set = 2, binding = 8) uniform utexture2D HexMaterial_overlay_state;
+layout(set = 2, binding = 9) uniform sampler HexMaterial_overlay_state_sampler;
+layout(set = 2, binding = 7) uniform HexMaterial_map_wrap {
+    vec2 map_wrap;
+};
 // ============================================================================
 
 
@@ -55,6 +60,106 @@ ivec2 hex_grid_coord(vec2 id) {
     return ivec2(id / h + vec2(0.1, 0.1));
 }
 
+// Edge of the hex closest to gv, numbered like the neighbors: 60 degrees apart counter clockwise from +x
+int hex_edge(vec2 gv) {
+    float angle = atan(gv.y, gv.x);
+    return int(mod(round(angle / (3.14159265 / 3.0)), 6.0));
+}
+
+// Cells past a wrapping edge of the map are the cells at the other side
+ivec2 wrap_coord(ivec2 coord) {
+    ivec2 map_size = textureSize(usampler2D(HexMaterial_map_state, HexMaterial_map_state_sampler), 0);
+    // % on negative ints is undefined in GLSL, mod on floats always lands in 0..map_size
+    vec2 wrapped = mod(vec2(coord), vec2(map_size));
+    return ivec2(mix(vec2(coord), wrapped, map_wrap));
+}
+
+
+// Bits of a map_state texel above the terrain type, copied from the MAP_ constants in hex_shader/src/lib.rs
+const uint MAP_TERRAIN_MASK = 0xFFu;
+const uint MAP_FLAG_SELECTED = 1u << 8;
+const uint MAP_FLAG_HOVERED = 1u << 9;
+// Visibility channel for fog of war. hex_shader/src/lib.rs is the source of truth,
+// the game writes the texels with its MAP_VISIBILITY_ constants, change them there first
+const uint MAP_VISIBILITY_SHIFT = 10u;
+const uint MAP_VISIBILITY_MASK = 0x3u;
+const uint MAP_VISIBILITY_REMEMBERED = 1u;
+const uint MAP_VISIBILITY_UNEXPLORED = 2u;
+
+// Terrain in the lowest bits of a map_state texel, see terrain_index in rendering::systems
+const uint TERRAIN_LAND = 0u;
+const uint TERRAIN_WATER = 1u;
+const uint TERRAIN_MOUNTAIN = 2u;
+const uint TERRAIN_FOREST = 3u;
+const uint TERRAIN_PLAINS = 4u;
+const uint TERRAIN_DESERT = 5u;
+const uint TERRAIN_SHALLOWS = 6u;
+const uint TERRAIN_BRIDGE = 7u;
+
+// Overlay categories, copied from the OVERLAY_ constants in hex_shader/src/lib.rs
+const uint OVERLAY_EDGE_MASK = 0x3Fu;
+const uint OVERLAY_REACHABLE = 1u << 8;
+const uint OVERLAY_CAPTURABLE = 1u << 9;
+const uint OVERLAY_THREATENED = 1u << 10;
+const uint OVERLAY_CAPITAL = 1u << 11;
+const uint OVERLAY_PATH = 1u << 12;
+
+// border_dist goes from 0 at the border of the cell to 1 at its center
+vec3 apply_overlay(vec3 col, uint overlay, int edge, float border_dist, vec2 pattern_uv) {
+    bool in_rim = border_dist < 0.24;
+    if((overlay & OVERLAY_REACHABLE) != 0u && in_rim)
+        col = mix(col, vec3(1.0, 1.0, 0.6), 0.6);
+    if((overlay & OVERLAY_CAPTURABLE) != 0u && in_rim)
+        col = mix(col, vec3(1.0, 0.3, 0.2), 0.7);
+    if((overlay & OVERLAY_THREATENED) != 0u) {
+        float stripe = step(0.5, fract((pattern_uv.x + pattern_uv.y) * 8.0));
+        col = mix(col, vec3(0.8, 0.1, 0.1), 0.35 * stripe);
+    }
+    if((overlay & OVERLAY_PATH) != 0u)
+        col *= vec3(1.0, 0.7, 0.4);
+    if((overlay & OVERLAY_CAPITAL) != 0u && border_dist > 0.76)
+        col = mix(col, vec3(1.0, 0.8, 0.1), 0.8);
+    // Province outline
+    if((overlay & OVERLAY_EDGE_MASK & (1u << edge)) != 0u && border_dist < 0.1)
+        col = vec3(0.05);
+    return col;
+}
+
+// Tint of the terrain, border_dist goes from 0 at the border of the cell to 1 at its center
+vec3 terrain_color(uint terrain, float border_dist, vec2 pattern_uv) {
+    if(terrain == TERRAIN_LAND)
+        return vec3(0.0, 1.0, 0.0);
+    if(terrain == TERRAIN_WATER)
+        return vec3(0.0, 0.0, 1.0);
+    // Grey slopes with a snowy peak
+    if(terrain == TERRAIN_MOUNTAIN)
+        return mix(vec3(0.45, 0.4, 0.35), vec3(0.95), smoothstep(0.55, 0.85, border_dist));
+    // Dark green with rows of treetops
+    if(terrain == TERRAIN_FOREST)
+        return vec3(0.05, 0.45, 0.1) * mix(0.7, 1.0, step(0.5, fract((pattern_uv.x - pattern_uv.y) * 12.0)));
+    if(terrain == TERRAIN_PLAINS)
+        return vec3(0.65, 0.9, 0.2);
+    if(terrain == TERRAIN_DESERT)
+        return vec3(1.0, 0.85, 0.45);
+    if(terrain == TERRAIN_SHALLOWS)
+        return vec3(0.3, 0.75, 1.0);
+    // Wooden planks
+    if(terrain == TERRAIN_BRIDGE)
+        return vec3(0.6, 0.4, 0.2) * mix(0.7, 1.0, step(0.2, fract(pattern_uv.x * 16.0)));
+    return vec3(1.0);
+}
+
+// Remembered tiles fade to grey, unexplored ones are dark apart from the cursor
+vec3 apply_visibility(vec3 col, uint visibility, bool highlighted) {
+    if(visibility == MAP_VISIBILITY_REMEMBERED) {
+        float grey = dot(col, vec3(0.299, 0.587, 0.114));
+        col = mix(col, vec3(grey), 0.6) * 0.5;
+    } else if(visibility == MAP_VISIBILITY_UNEXPLORED)
+        col = vec3(mix(0.03, 0.12, float(highlighted)));
+    return col;
+}
+
+
 
 // Fragment shader
 void main() {
@@ -63,26 +168,31 @@ void main() {
     vec2 gv = hex_relative_uv(uv);
     float hex_dist = 0.5 - hex_dist(gv);
     vec2 id = uv - gv;
-    ivec2 coord = hex_grid_coord(id);
+    ivec2 coord = wrap_coord(hex_grid_coord(id));
+
+    uint map_data = texelFetch(usampler2D(HexMaterial_map_state, HexMaterial_map_state_sampler), coord, 0).r;
+    uint terrain = map_data & MAP_TERRAIN_MASK;
 
     vec3 col = color.rgb;
 
-    bool fragment_in_highlight = distance(coord, highlighted_coord) < 0.1;
+    bool fragment_in_highlight = distance(coord, highlighted_coord) < 0.1 || (map_data & MAP_FLAG_HOVERED) != 0u;
     col *= mix(0.4, 1.0, float(fragment_in_highlight));
 
-    bool fragment_in_selected = distance(coord, selected_coord) < 0.1;
+    bool fragment_in_selected = distance(coord, selected_coord) < 0.1 || (map_data & MAP_FLAG_SELECTED) != 0u;
     col *= mix(vec3(1.0), vec3(1.0, 1.0, 0.2), float(fragment_in_selected));
 
     bool fragment_in_border = hex_dist < 0.04;
     col += vec3(float(fragment_in_border));
 
+    uint overlay = texelFetch(usampler2D(HexMaterial_overlay_state, HexMaterial_overlay_state_sampler), coord, 0).r;
+    col = apply_overlay(col, overlay, hex_edge(gv), hex_dist * 2.0, i_Uv);
+
     col *= texture(sampler2D(HexMaterial_background_texture, HexMaterial_background_texture_sampler), i_Uv).xyz;
 
-    uint map_data = texelFetch(usampler2D(HexMaterial_map_state, HexMaterial_map_state_sampler), coord, 0).r;
-    if(map_data == 0)
-        col *= vec3(0.0, 1.0, 0.0);
-    else if(map_data == 1)
-        col *= vec3(0.0, 0.0, 1.0);
+    col *= terrain_color(terrain, hex_dist * 2.0, i_Uv);
+
+    uint visibility = (map_data >> MAP_VISIBILITY_SHIFT) & MAP_VISIBILITY_MASK;
+    col = apply_visibility(col, visibility, fragment_in_highlight || fragment_in_selected);
 
     o_Target = vec4(col.rgb, color.a);
 }
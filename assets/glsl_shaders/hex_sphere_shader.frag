@@ -0,0 +1,150 @@
+#version 450
+
+// Attributes
+// ============================================================================
+// Input
+// x: cell index plus (edge + 0.5) / 8, y: 0 at the cell center to 1 at the cell border
+layout(location = 0) in vec2 i_Uv;
+// Output 
+layout(location = 0) out vec4 o_Target;
+
+// Uniforms
+layout(set = 2, binding = 0) uniform HexMaterial_color {
+    vec4 color;
+};
+layout(set = 2, binding = 1) uniform HexMaterial_highlighted_coord {
+    vec2 highlighted_coord;
+};
+layout(set = 2, binding = 2) uniform HexMaterial_selected_coord {
+    vec2 selected_coord;
+};
+layout(set = 2, binding = 5) uniform utexture2D HexMaterial_map_state;
+layout(set = 2, binding = 6) uniform sampler HexMaterial_map_state_sampler;
+layout(set = 2, binding = 8) uniform utexture2D HexMaterial_overlay_state;
+layout(set = 2, binding = 9) uniform sampler HexMaterial_overlay_state_sampler;
+// ============================================================================
+
+
+// Cells are laid out row by row in the map_state texture
+ivec2 cell_coord(uint index) {
+    ivec2 map_size = textureSize(usampler2D(HexMaterial_map_state, HexMaterial_map_state_sampler), 0);
+    return ivec2(int(index) % map_size.x, int(index) / map_size.x);
+}
+
+
+// Bits of a map_state texel above the terrain type
+const uint MAP_TERRAIN_MASK = 0xFFu;
+const uint MAP_FLAG_SELECTED = 1u << 8;
+const uint MAP_FLAG_HOVERED = 1u << 9;
+// Visibility channel, see the MAP_VISIBILITY_ constants in rendering::systems
+const uint MAP_VISIBILITY_SHIFT = 10u;
+const uint MAP_VISIBILITY_MASK = 0x3u;
+const uint MAP_VISIBILITY_REMEMBERED = 1u;
+const uint MAP_VISIBILITY_UNEXPLORED = 2u;
+
+// Terrain in the lowest bits of a map_state texel, see terrain_index in rendering::systems
+const uint TERRAIN_LAND = 0u;
+const uint TERRAIN_WATER = 1u;
+const uint TERRAIN_MOUNTAIN = 2u;
+const uint TERRAIN_FOREST = 3u;
+const uint TERRAIN_PLAINS = 4u;
+const uint TERRAIN_DESERT = 5u;
+const uint TERRAIN_SHALLOWS = 6u;
+const uint TERRAIN_BRIDGE = 7u;
+
+// Overlay categories, see the OVERLAY_ constants in gameplay::components
+const uint OVERLAY_EDGE_MASK = 0x3Fu;
+const uint OVERLAY_REACHABLE = 1u << 8;
+const uint OVERLAY_CAPTURABLE = 1u << 9;
+const uint OVERLAY_THREATENED = 1u << 10;
+const uint OVERLAY_CAPITAL = 1u << 11;
+const uint OVERLAY_PATH = 1u << 12;
+
+// border_dist goes from 0 at the border of the cell to 1 at its center
+vec3 apply_overlay(vec3 col, uint overlay, int edge, float border_dist, vec2 pattern_uv) {
+    bool in_rim = border_dist < 0.24;
+    if((overlay & OVERLAY_REACHABLE) != 0u && in_rim)
+        col = mix(col, vec3(1.0, 1.0, 0.6), 0.6);
+    if((overlay & OVERLAY_CAPTURABLE) != 0u && in_rim)
+        col = mix(col, vec3(1.0, 0.3, 0.2), 0.7);
+    if((overlay & OVERLAY_THREATENED) != 0u) {
+        float stripe = step(0.5, fract((pattern_uv.x + pattern_uv.y) * 8.0));
+        col = mix(col, vec3(0.8, 0.1, 0.1), 0.35 * stripe);
+    }
+    if((overlay & OVERLAY_PATH) != 0u)
+        col *= vec3(1.0, 0.7, 0.4);
+    if((overlay & OVERLAY_CAPITAL) != 0u && border_dist > 0.76)
+        col = mix(col, vec3(1.0, 0.8, 0.1), 0.8);
+    // Province outline
+    if((overlay & OVERLAY_EDGE_MASK & (1u << edge)) != 0u && border_dist < 0.1)
+        col = vec3(0.05);
+    return col;
+}
+
+// Tint of the terrain, border_dist goes from 0 at the border of the cell to 1 at its center
+vec3 terrain_color(uint terrain, float border_dist, vec2 pattern_uv) {
+    if(terrain == TERRAIN_LAND)
+        return vec3(0.0, 1.0, 0.0);
+    if(terrain == TERRAIN_WATER)
+        return vec3(0.0, 0.0, 1.0);
+    // Grey slopes with a snowy peak
+    if(terrain == TERRAIN_MOUNTAIN)
+        return mix(vec3(0.45, 0.4, 0.35), vec3(0.95), smoothstep(0.55, 0.85, border_dist));
+    // Dark green with rows of treetops
+    if(terrain == TERRAIN_FOREST)
+        return vec3(0.05, 0.45, 0.1) * mix(0.7, 1.0, step(0.5, fract((pattern_uv.x - pattern_uv.y) * 12.0)));
+    if(terrain == TERRAIN_PLAINS)
+        return vec3(0.65, 0.9, 0.2);
+    if(terrain == TERRAIN_DESERT)
+        return vec3(1.0, 0.85, 0.45);
+    if(terrain == TERRAIN_SHALLOWS)
+        return vec3(0.3, 0.75, 1.0);
+    // Wooden planks
+    if(terrain == TERRAIN_BRIDGE)
+        return vec3(0.6, 0.4, 0.2) * mix(0.7, 1.0, step(0.2, fract(pattern_uv.x * 16.0)));
+    return vec3(1.0);
+}
+
+// Remembered tiles fade to grey, unexplored ones are dark apart from the cursor
+vec3 apply_visibility(vec3 col, uint visibility, bool highlighted) {
+    if(visibility == MAP_VISIBILITY_REMEMBERED) {
+        float grey = dot(col, vec3(0.299, 0.587, 0.114));
+        col = mix(col, vec3(grey), 0.6) * 0.5;
+    } else if(visibility == MAP_VISIBILITY_UNEXPLORED)
+        col = vec3(mix(0.03, 0.12, float(highlighted)));
+    return col;
+}
+
+
+
+// Fragment shader
+void main() {
+    uint index = uint(floor(i_Uv.x));
+    int edge = int(fract(i_Uv.x) * 8.0);
+    ivec2 coord = cell_coord(index);
+    float border_dist = 1.0 - i_Uv.y;
+
+    uint map_data = texelFetch(usampler2D(HexMaterial_map_state, HexMaterial_map_state_sampler), coord, 0).r;
+    uint terrain = map_data & MAP_TERRAIN_MASK;
+
+    vec3 col = color.rgb;
+
+    bool fragment_in_highlight = distance(coord, highlighted_coord) < 0.1 || (map_data & MAP_FLAG_HOVERED) != 0u;
+    col *= mix(0.4, 1.0, float(fragment_in_highlight));
+
+    bool fragment_in_selected = distance(coord, selected_coord) < 0.1 || (map_data & MAP_FLAG_SELECTED) != 0u;
+    col *= mix(vec3(1.0), vec3(1.0, 1.0, 0.2), float(fragment_in_selected));
+
+    bool fragment_in_border = border_dist < 0.08;
+    col += vec3(float(fragment_in_border));
+
+    uint overlay = texelFetch(usampler2D(HexMaterial_overlay_state, HexMaterial_overlay_state_sampler), coord, 0).r;
+    col = apply_overlay(col, overlay, edge, border_dist, gl_FragCoord.xy / 64.0);
+
+    col *= terrain_color(terrain, border_dist, gl_FragCoord.xy / 64.0);
+
+    uint visibility = (map_data >> MAP_VISIBILITY_SHIFT) & MAP_VISIBILITY_MASK;
+    col = apply_visibility(col, visibility, fragment_in_highlight || fragment_in_selected);
+
+    o_Target = vec4(col.rgb, color.a);
+}
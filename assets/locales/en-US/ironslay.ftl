@@ -0,0 +1,150 @@
+## Menus
+
+game-title = IronSlay
+menu-new-game = New Game
+menu-settings = Settings
+menu-quit = Quit
+menu-start = Start
+menu-back = Back
+menu-resume = Resume
+menu-main-menu = Main Menu
+menu-change = Change
+menu-toggle = Toggle
+menu-rebind = Rebind
+on = On
+off = Off
+
+new-game-title = New Game
+setup-planet = Planet: { $planet }
+setup-size = Size: { $width } x { $height }
+setup-wrap = Wrap: { $wrap }
+planet-hexagon-cap = Hexagon cap
+planet-sphere = Sphere ({ $cells ->
+    [one] { $cells } cell
+   *[other] { $cells } cells
+})
+wrap-none = None
+wrap-cylinder = Cylinder
+wrap-torus = Torus
+setup-fog-of-war = Fog of war: { $value }
+setup-trees = Trees: { $value }
+setup-treasury-cap = Treasury cap: { $value }
+setup-starting-positions = Starting positions: { $value }
+setup-terrain-income = Terrain income: { $value }
+setup-sudden-death = Sudden death rounds: { $value }
+positions-map = Map
+positions-random = Random
+positions-clustered = Clustered
+
+paused-title = Paused
+
+settings-title = Settings
+setting-master-volume = Master volume: { $value }
+setting-music-volume = Music volume: { $value }
+setting-effects-volume = Effects volume: { $value }
+setting-camera-sensitivity = Camera sensitivity: { $value }
+setting-fullscreen = Fullscreen: { $value }
+setting-vsync = VSync: { $value }
+setting-language = Language: { $value }
+binding-row = { $name }: { $bindings }
+binding-waiting = press a key
+
+game-over-title = Game Over
+game-over-winner = Team { $team } won after { $turns ->
+    [one] { $turns } turn
+   *[other] { $turns } turns
+}
+game-over-no-winner = Nobody won
+
+end-turn-title = End Turn?
+end-turn-unmoved = { $count ->
+    [one] { $count } unit can still move
+   *[other] { $count } units can still move
+}
+end-turn-confirm = End Turn
+end-turn-keep-playing = Keep Playing
+
+## HUD
+
+hud-turn = Turn { $turn }
+hud-economy = Treasury { $treasury }  Income +{ $income }  Upkeep -{ $upkeep }  Net { $net }
+hud-tile = { $terrain }  Team { $team }  { $occupant }  Defense { $defense }
+next-turn = Next Turn
+purchase-button = { $name } { $cost }
+purchase-drop = Drop the { $name } on a highlighted tile
+purchase-cost = { $name }: { $cost } gold.
+unit-stats = Strength { $strength }, upkeep { $upkeep }.
+unit-merge = on a { $other } for a { $merged }
+unit-merges = Drop it { $merges }.
+castle-defense = Defends its tile and the tiles around it with strength { $strength }.
+not-enough-gold = Not enough gold, the province has { $treasury }.
+needs-capital = Select a province with a capital first.
+
+units-peasant = { $count ->
+    [one] { $count } peasant
+   *[other] { $count } peasants
+}
+units-spearman = { $count ->
+    [one] { $count } spearman
+   *[other] { $count } spearmen
+}
+units-knight = { $count ->
+    [one] { $count } knight
+   *[other] { $count } knights
+}
+units-baron = { $count ->
+    [one] { $count } baron
+   *[other] { $count } barons
+}
+
+## Names
+
+terrain-land = Land
+terrain-water = Water
+terrain-mountain = Mountain
+terrain-forest = Forest
+terrain-plains = Plains
+terrain-desert = Desert
+terrain-shallows = Shallows
+terrain-bridge = Bridge
+tier-peasant = Peasant
+tier-spearman = Spearman
+tier-knight = Knight
+tier-baron = Baron
+occupant-empty = Empty
+occupant-castle = Castle
+occupant-capital = Capital
+occupant-pine = Pine
+occupant-palm = Palm
+occupant-grave = Grave
+
+## Input
+
+action-select = Select
+action-multi-select = Add to selection
+action-cancel = Cancel
+action-end-turn = End turn
+action-undo = Undo
+action-rotate-camera = Rotate camera
+action-zoom = Zoom
+action-cycle-units = Next unit
+action-cycle-units-back = Previous unit
+action-pause = Pause
+action-cursor-up = Cursor up
+action-cursor-down = Cursor down
+action-cursor-left = Cursor left
+action-cursor-right = Cursor right
+action-camera-left = Turn camera left
+action-camera-right = Turn camera right
+action-camera-up = Tilt camera up
+action-camera-down = Tilt camera down
+binding-pan-forward = Pan forward
+binding-pan-back = Pan back
+binding-pan-left = Pan left
+binding-pan-right = Pan right
+binding-rotate-left = Rotate left
+binding-rotate-right = Rotate right
+binding-focus-capital = Focus capital
+input-mouse = Mouse { $button }
+input-pad = Pad { $button }
+input-mouse-wheel = Mouse wheel
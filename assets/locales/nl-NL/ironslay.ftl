@@ -0,0 +1,153 @@
+## Menus
+
+game-title = IronSlay
+menu-new-game = Nieuw spel
+menu-settings = Instellingen
+menu-quit = Afsluiten
+menu-start = Start
+menu-back = Terug
+menu-resume = Verder
+menu-main-menu = Hoofdmenu
+menu-change = Wijzig
+menu-toggle = Wissel
+menu-rebind = Wijzig
+on = Aan
+off = Uit
+
+new-game-title = Nieuw spel
+setup-planet = Planeet: { $planet }
+setup-size = Grootte: { $width } x { $height }
+setup-wrap = Randen: { $wrap }
+planet-hexagon-cap = Zeshoekige kap
+planet-sphere = Bol ({ $cells ->
+    [one] { $cells } vak
+   *[other] { $cells } vakken
+})
+wrap-none = Geen
+wrap-cylinder = Cilinder
+wrap-torus = Torus
+setup-fog-of-war = Oorlogsmist: { $value }
+setup-trees = Bomen: { $value }
+setup-treasury-cap = Maximale schatkist: { $value }
+setup-starting-positions = Startposities: { $value }
+setup-terrain-income = Inkomen per terrein: { $value }
+setup-sudden-death = Sudden death rondes: { $value }
+positions-map = Kaart
+positions-random = Willekeurig
+positions-clustered = Gegroepeerd
+
+paused-title = Gepauzeerd
+
+settings-title = Instellingen
+setting-master-volume = Hoofdvolume: { $value }
+setting-music-volume = Muziekvolume: { $value }
+setting-effects-volume = Effectenvolume: { $value }
+setting-camera-sensitivity = Cameragevoeligheid: { $value }
+setting-fullscreen = Volledig scherm: { $value }
+setting-vsync = VSync: { $value }
+setting-language = Taal: { $value }
+binding-row = { $name }: { $bindings }
+binding-waiting = druk op een toets
+
+game-over-title = Einde spel
+game-over-winner = Team { $team } won na { $turns ->
+    [one] { $turns } beurt
+   *[other] { $turns } beurten
+}
+game-over-no-winner = Niemand won
+
+end-turn-title = Beurt beëindigen?
+end-turn-unmoved = { $count ->
+    [one] { $count } eenheid kan nog bewegen
+   *[other] { $count } eenheden kunnen nog bewegen
+}
+end-turn-confirm = Beëindig beurt
+end-turn-keep-playing = Verder spelen
+
+## HUD
+
+hud-turn = Beurt { $turn }
+hud-economy = Schatkist { $treasury }  Inkomen +{ $income }  Onderhoud -{ $upkeep }  Netto { $net }
+hud-tile = { $terrain }  Team { $team }  { $occupant }  Verdediging { $defense }
+next-turn = Volgende beurt
+purchase-button = { $name } { $cost }
+purchase-drop = Zet de { $name } op een gemarkeerd vak
+purchase-cost = { $name }: { $cost ->
+    [one] { $cost } goudstuk
+   *[other] { $cost } goudstukken
+}.
+unit-stats = Kracht { $strength }, onderhoud { $upkeep }.
+unit-merge = op een { $other } voor een { $merged }
+unit-merges = Zet hem { $merges }.
+castle-defense = Verdedigt zijn vak en de vakken eromheen met kracht { $strength }.
+not-enough-gold = Niet genoeg goud, de provincie heeft { $treasury }.
+needs-capital = Kies eerst een provincie met een hoofdstad.
+
+units-peasant = { $count ->
+    [one] { $count } boer
+   *[other] { $count } boeren
+}
+units-spearman = { $count ->
+    [one] { $count } speerdrager
+   *[other] { $count } speerdragers
+}
+units-knight = { $count ->
+    [one] { $count } ridder
+   *[other] { $count } ridders
+}
+units-baron = { $count ->
+    [one] { $count } baron
+   *[other] { $count } baronnen
+}
+
+## Names
+
+terrain-land = Land
+terrain-water = Water
+terrain-mountain = Berg
+terrain-forest = Bos
+terrain-plains = Vlakte
+terrain-desert = Woestijn
+terrain-shallows = Ondiepte
+terrain-bridge = Brug
+tier-peasant = Boer
+tier-spearman = Speerdrager
+tier-knight = Ridder
+tier-baron = Baron
+occupant-empty = Leeg
+occupant-castle = Kasteel
+occupant-capital = Hoofdstad
+occupant-pine = Den
+occupant-palm = Palm
+occupant-grave = Graf
+
+## Input
+
+action-select = Selecteren
+action-multi-select = Aan selectie toevoegen
+action-cancel = Annuleren
+action-end-turn = Beurt beëindigen
+action-undo = Ongedaan maken
+action-rotate-camera = Camera draaien
+action-zoom = Zoomen
+action-cycle-units = Volgende eenheid
+action-cycle-units-back = Vorige eenheid
+action-pause = Pauze
+action-cursor-up = Cursor omhoog
+action-cursor-down = Cursor omlaag
+action-cursor-left = Cursor links
+action-cursor-right = Cursor rechts
+action-camera-left = Camera links draaien
+action-camera-right = Camera rechts draaien
+action-camera-up = Camera omhoog kantelen
+action-camera-down = Camera omlaag kantelen
+binding-pan-forward = Naar voren schuiven
+binding-pan-back = Naar achteren schuiven
+binding-pan-left = Naar links schuiven
+binding-pan-right = Naar rechts schuiven
+binding-rotate-left = Links draaien
+binding-rotate-right = Rechts draaien
+binding-focus-capital = Naar hoofdstad
+input-mouse = Muis { $button }
+input-pad = Pad { $button }
+input-mouse-wheel = Muiswiel
@@ -1,18 +1,45 @@
-//use spirv_builder::{MemoryModel, SpirvBuilder};
 use std::error::Error;
+#[cfg(feature = "rust-gpu-shader")]
+use std::process::{Command, Stdio};
 
-// fn build_shader(path_to_create: &str) -> Result<(), Box<dyn Error>> {
-//     SpirvBuilder::new(path_to_create)
-//         .spirv_version(1, 3)
-//         .memory_model(MemoryModel::GLSL450)
-//         .release(true)
-//         .build()?;
-    
-//     Ok(())
-// }
+// Exposes the path of the compiled shader through the "hex_shader.spv" env var.
+// rust-gpu needs its own toolchain, so hex_shader_builder compiles the shader in a separate cargo run
+#[cfg(feature = "rust-gpu-shader")]
+fn build_shader(builder_path: &str) -> Result<(), Box<dyn Error>> {
+    for path in [
+        "hex_shader/src",
+        "hex_shader/Cargo.toml",
+        "hex_shader_builder/src",
+        "hex_shader_builder/build.rs",
+        "hex_shader_builder/Cargo.toml",
+    ]
+    .iter()
+    {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+
+    // Started from its own folder the builder picks up its rust-toolchain file, unless the
+    // toolchain of this build is passed on
+    let output = Command::new("cargo")
+        .args(&["run", "--release", "--quiet"])
+        .current_dir(builder_path)
+        .env_remove("RUSTUP_TOOLCHAIN")
+        .env_remove("RUSTC")
+        .env_remove("CARGO_TARGET_DIR")
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", builder_path, output.status).into());
+    }
+    // The builder prints the cargo:rustc-env line for the compiled module
+    print!("{}", String::from_utf8(output.stdout)?);
+
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    //build_shader("hex_shader")?;
+    #[cfg(feature = "rust-gpu-shader")]
+    build_shader("hex_shader_builder")?;
 
     Ok(())
 }
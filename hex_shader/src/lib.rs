@@ -9,40 +9,98 @@
 // #![deny(warnings)]
 #![allow(unused_imports)]
 
-#[cfg(not(target_arch = "spirv"))]
-#[macro_use]
-pub extern crate spirv_std_macros;
-
 use core::f32;
 
-use spirv_std::glam::{vec2, vec3, vec4};
+// Only the SPIR-V build needs spirv-std, the game and the tests get the math from plain glam
+#[cfg(target_arch = "spirv")]
+use spirv_std::glam;
+#[cfg(target_arch = "spirv")]
 use spirv_std::num_traits::Float;
-use spirv_std::storage_class::{Input, Output, Uniform};
-use spirv_std::{Image2d, Sampler};
+#[cfg(target_arch = "spirv")]
+use spirv_std::storage_class::{Input, Output, Uniform, UniformConstant};
+#[cfg(target_arch = "spirv")]
+use spirv_std::{Image2d, Image2dU, Sampler};
+
+use glam::{vec2, vec3, vec4, IVec2, UVec4};
 
-pub use spirv_std::glam::{Vec2, Vec3, Vec4};
+pub use glam::{Vec2, Vec3, Vec4};
 
+// Uniform blocks are named after the HexMaterial fields, keep them in sync with rendering::components::HexMaterial
+#[cfg(target_arch = "spirv")]
 #[spirv(block)]
 #[repr(C)]
-pub struct MyMaterial_color {
+pub struct HexMaterial_color {
     color: Vec4,
 }
+#[cfg(target_arch = "spirv")]
 #[spirv(block)]
 #[repr(C)]
-pub struct MyMaterial_highlighted_id {
-    highlighted_id: Vec2,
+pub struct HexMaterial_highlighted_coord {
+    highlighted_coord: Vec2,
     // Shader compiler shares struct definitions with the same internal types, so we have to add dummy fields to let the types differ...
     _dummy: f32,
 }
 
+#[cfg(target_arch = "spirv")]
 #[spirv(block)]
 #[repr(C)]
-pub struct MyMaterial_selected_id {
-    selected_id: Vec2,
+pub struct HexMaterial_selected_coord {
+    selected_coord: Vec2,
     // Shader compiler shares struct definitions with the same internal types, so we have to add dummy fields to let the types differ...
     _dummy: Vec2,
 }
 
+#[cfg(target_arch = "spirv")]
+#[spirv(block)]
+#[repr(C)]
+pub struct HexMaterial_map_wrap {
+    map_wrap: Vec2,
+    // Shader compiler shares struct definitions with the same internal types, so we have to add dummy fields to let the types differ...
+    _dummy: Vec3,
+}
+
+#[cfg(target_arch = "spirv")]
+#[spirv(block)]
+#[repr(C)]
+pub struct HexMaterial_map_size {
+    map_size: Vec2,
+    // Shader compiler shares struct definitions with the same internal types, so we have to add dummy fields to let the types differ...
+    _dummy: Vec4,
+}
+
+// The layout of the map_state and overlay_state texels. The game writes them with these constants,
+// the GLSL fallback in assets/glsl_shaders/hex_shader.frag repeats them by hand.
+// Bits of a map_state texel above the terrain type
+pub const MAP_TERRAIN_MASK: u32 = 0xFF;
+pub const MAP_FLAG_SELECTED: u32 = 1 << 8;
+pub const MAP_FLAG_HOVERED: u32 = 1 << 9;
+// Visibility channel, how much the team on turn knows of the tile. Visible tiles leave it zero
+pub const MAP_VISIBILITY_SHIFT: u32 = 10;
+pub const MAP_VISIBILITY_MASK: u32 = 0x3;
+pub const MAP_VISIBILITY_REMEMBERED: u32 = 1;
+pub const MAP_VISIBILITY_UNEXPLORED: u32 = 2;
+
+// Terrain in the lowest bits of a map_state texel, see terrain_index in rendering::systems
+pub const TERRAIN_LAND: u32 = 0;
+pub const TERRAIN_WATER: u32 = 1;
+pub const TERRAIN_MOUNTAIN: u32 = 2;
+pub const TERRAIN_FOREST: u32 = 3;
+pub const TERRAIN_PLAINS: u32 = 4;
+pub const TERRAIN_DESERT: u32 = 5;
+pub const TERRAIN_SHALLOWS: u32 = 6;
+pub const TERRAIN_BRIDGE: u32 = 7;
+
+// Highlight categories of the overlay layer.
+// The lowest bits mark which edges of the tile lie on the outline of its province
+pub const OVERLAY_EDGE_MASK: u32 = 0x3F;
+pub const OVERLAY_REACHABLE: u32 = 1 << 8;
+pub const OVERLAY_CAPTURABLE: u32 = 1 << 9;
+pub const OVERLAY_THREATENED: u32 = 1 << 10;
+pub const OVERLAY_CAPITAL: u32 = 1 << 11;
+pub const OVERLAY_PATH: u32 = 1 << 12;
+
+// f32::clamp can panic, which the rust-gpu build doesn't support
+#[allow(clippy::manual_clamp)]
 pub fn saturate(x: f32) -> f32 {
     x.max(0.0).min(1.0)
 }
@@ -67,8 +125,7 @@ pub fn lerp(x: f32, min: f32, max: f32) -> f32 {
 }
 
 pub fn lerp_clamped(x: f32, min: f32, max: f32) -> f32 {
-    let x_clamped = x.clamp(min, max);
-    min + (max - min) * x_clamped
+    lerp(saturate(x), min, max)
 }
 
 pub fn vec2_mod(a: Vec2, b: Vec2) -> Vec2 {
@@ -81,7 +138,7 @@ pub fn hex_dist(mut p: Vec2) -> f32 {
     let mut c = p.dot(vec2(1.0, 1.73).normalize());
     c = c.max(p.x);
 
-    return c;
+    c
 }
 
 pub fn hex_relative_uv(uv: Vec2) -> Vec2 {
@@ -98,24 +155,129 @@ pub fn hex_relative_uv(uv: Vec2) -> Vec2 {
     }
 }
 
+pub fn hex_grid_coord(id: Vec2) -> Vec2 {
+    let r = vec2(1.0, 1.73);
+    let h = r * 0.5;
+    // Meh floating point precision
+    let coord = id / h + vec2(0.1, 0.1);
+    vec2(coord.x.floor(), coord.y.floor())
+}
+
+pub fn hex_grid_coord_to_id(coord: Vec2) -> Vec2 {
+    let r = vec2(1.0, 1.73);
+    let h = r * 0.5;
+    coord * h
+}
+
+// Edge of the hex closest to gv, numbered like the neighbors: 60 degrees apart counter clockwise from +x
+pub fn hex_edge(gv: Vec2) -> u32 {
+    let angle = gv.y.atan2(gv.x);
+    let edge = (angle / (f32::consts::PI / 3.0)).round();
+    (edge - 6.0 * (edge / 6.0).floor()) as u32
+}
+
+// Cells past a wrapping edge of the map are the cells at the other side
+pub fn wrap_coord(coord: Vec2, map_size: Vec2, map_wrap: Vec2) -> Vec2 {
+    let wrapped = vec2_mod(vec2_mod(coord, map_size) + map_size, map_size);
+    coord + (wrapped - coord) * map_wrap
+}
+
+fn mix3(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    a + (b - a) * t
+}
+
+// border_dist goes from 0 at the border of the cell to 1 at its center
+pub fn apply_overlay(
+    mut col: Vec3,
+    overlay: u32,
+    edge: u32,
+    border_dist: f32,
+    pattern_uv: Vec2,
+) -> Vec3 {
+    let in_rim = border_dist < 0.24;
+    if overlay & OVERLAY_REACHABLE != 0 && in_rim {
+        col = mix3(col, vec3(1.0, 1.0, 0.6), 0.6);
+    }
+    if overlay & OVERLAY_CAPTURABLE != 0 && in_rim {
+        col = mix3(col, vec3(1.0, 0.3, 0.2), 0.7);
+    }
+    if overlay & OVERLAY_THREATENED != 0 {
+        let stripes = (pattern_uv.x + pattern_uv.y) * 8.0;
+        let stripe = step(0.5, stripes - stripes.floor());
+        col = mix3(col, vec3(0.8, 0.1, 0.1), 0.35 * stripe);
+    }
+    if overlay & OVERLAY_PATH != 0 {
+        col *= vec3(1.0, 0.7, 0.4);
+    }
+    if overlay & OVERLAY_CAPITAL != 0 && border_dist > 0.76 {
+        col = mix3(col, vec3(1.0, 0.8, 0.1), 0.8);
+    }
+    // Province outline
+    if overlay & OVERLAY_EDGE_MASK & (1 << edge) != 0 && border_dist < 0.1 {
+        col = Vec3::splat(0.05);
+    }
+    col
+}
+
+// Remembered tiles fade to grey, unexplored ones are dark apart from the cursor
+pub fn apply_visibility(col: Vec3, visibility: u32, highlighted: bool) -> Vec3 {
+    if visibility == MAP_VISIBILITY_REMEMBERED {
+        let grey = col.dot(vec3(0.299, 0.587, 0.114));
+        mix3(col, Vec3::splat(grey), 0.6) * 0.5
+    } else if visibility == MAP_VISIBILITY_UNEXPLORED {
+        Vec3::splat(lerp(highlighted as i32 as f32, 0.03, 0.12))
+    } else {
+        col
+    }
+}
+
+#[cfg(target_arch = "spirv")]
 fn selection_color() -> Vec3 {
-    Vec3::new(1.0, 1.0, 0.0)
+    Vec3::new(1.0, 1.0, 0.2)
 }
 
+// Tint of the terrain, border_dist goes from 0 at the border of the cell to 1 at its center
+pub fn terrain_color(terrain: u32, border_dist: f32, pattern_uv: Vec2) -> Vec3 {
+    match terrain {
+        TERRAIN_LAND => vec3(0.0, 1.0, 0.0),
+        TERRAIN_WATER => vec3(0.0, 0.0, 1.0),
+        // Grey slopes with a snowy peak
+        TERRAIN_MOUNTAIN => mix3(
+            vec3(0.45, 0.4, 0.35),
+            Vec3::splat(0.95),
+            smoothstep(0.55, 0.85, border_dist),
+        ),
+        // Dark green with rows of treetops
+        TERRAIN_FOREST => {
+            let rows = (pattern_uv.x - pattern_uv.y) * 12.0;
+            vec3(0.05, 0.45, 0.1) * lerp(step(0.5, rows - rows.floor()), 0.7, 1.0)
+        }
+        TERRAIN_PLAINS => vec3(0.65, 0.9, 0.2),
+        TERRAIN_DESERT => vec3(1.0, 0.85, 0.45),
+        TERRAIN_SHALLOWS => vec3(0.3, 0.75, 1.0),
+        // Wooden planks
+        TERRAIN_BRIDGE => {
+            let planks = pattern_uv.x * 16.0;
+            vec3(0.6, 0.4, 0.2) * lerp(step(0.2, planks - planks.floor()), 0.7, 1.0)
+        }
+        _ => Vec3::splat(1.0),
+    }
+}
+
+#[cfg(target_arch = "spirv")]
 #[spirv(fragment)]
 pub fn main(
     uv_input: Input<Vec2>,
 
-    #[spirv(descriptor_set = 2, binding = 0)] color_uniform: Uniform<MyMaterial_color>,
-    #[spirv(descriptor_set = 2, binding = 1)] highlight_uniform: Uniform<MyMaterial_highlighted_id>,
-    #[spirv(descriptor_set = 2, binding = 2)] selection_uniform: Uniform<MyMaterial_selected_id>,
-
-    /*
-       #[spirv(descriptor_set = 2, binding = 3)] my_material_background_texture: Uniform<Image2d>,
-       #[spirv(descriptor_set = 2, binding = 4)] my_material_background_texture_sampler: Uniform<
-           Sampler,
-       >,
-    */
+    #[spirv(descriptor_set = 2, binding = 0)] color_uniform: Uniform<HexMaterial_color>,
+    #[spirv(descriptor_set = 2, binding = 1)] highlight_uniform: Uniform<HexMaterial_highlighted_coord>,
+    #[spirv(descriptor_set = 2, binding = 2)] selection_uniform: Uniform<HexMaterial_selected_coord>,
+    #[spirv(descriptor_set = 2, binding = 3)] background_texture: UniformConstant<Image2d>,
+    #[spirv(descriptor_set = 2, binding = 4)] background_texture_sampler: UniformConstant<Sampler>,
+    #[spirv(descriptor_set = 2, binding = 5)] map_state: UniformConstant<Image2dU>,
+    #[spirv(descriptor_set = 2, binding = 7)] wrap_uniform: Uniform<HexMaterial_map_wrap>,
+    #[spirv(descriptor_set = 2, binding = 8)] overlay_state: UniformConstant<Image2dU>,
+    #[spirv(descriptor_set = 2, binding = 10)] size_uniform: Uniform<HexMaterial_map_size>,
     mut colour_output: Output<Vec4>,
 ) {
     let mut uv: Vec2 = *uv_input;
@@ -124,24 +286,174 @@ pub fn main(
     let gv = hex_relative_uv(uv);
     let hex_dist = 0.5 - hex_dist(gv);
     let id = uv - gv;
+    let coord = wrap_coord(
+        hex_grid_coord(id),
+        size_uniform.map_size,
+        wrap_uniform.map_wrap,
+    );
+    let texel = IVec2::new(coord.x as i32, coord.y as i32);
 
-    let mut col = Vec3::one();
+    let map_data: UVec4 = map_state.fetch(texel);
+    let map_data = map_data.x;
+    let terrain = map_data & MAP_TERRAIN_MASK;
 
-    let target_id_in_fragment =
-        Vec2::from(highlight_uniform.highlighted_id).distance_squared(id) < 0.1;
-    col *= Vec3::splat(lerp(target_id_in_fragment as i32 as f32, 0.4, 1.0));
+    let color: Vec4 = color_uniform.color;
+    let mut col = color.truncate();
 
-    let selected_id_in_fragment =
-        Vec2::from(selection_uniform.selected_id).distance_squared(id) < 0.1;
-    col *= Vec3::one().lerp(selection_color(), selected_id_in_fragment as i32 as f32);
+    let fragment_in_highlight = Vec2::from(highlight_uniform.highlighted_coord).distance(coord)
+        < 0.1
+        || map_data & MAP_FLAG_HOVERED != 0;
+    col *= Vec3::splat(lerp(fragment_in_highlight as i32 as f32, 0.4, 1.0));
+
+    let fragment_in_selected = Vec2::from(selection_uniform.selected_coord).distance(coord) < 0.1
+        || map_data & MAP_FLAG_SELECTED != 0;
+    col *= Vec3::one().lerp(selection_color(), fragment_in_selected as i32 as f32);
 
     let fragment_in_border = hex_dist < 0.04;
     col += Vec3::splat(fragment_in_border as i32 as f32);
 
-    /*
-    let diffuse_texture_color =
-        my_material_background_texture.sample(*my_material_background_texture_sampler, *uv_input);
-    *colour_output = diffuse_texture_color;
-    */
-    *colour_output = col.extend(1.0) * color_uniform.color;
+    let overlay: UVec4 = overlay_state.fetch(texel);
+    col = apply_overlay(col, overlay.x, hex_edge(gv), hex_dist * 2.0, *uv_input);
+
+    let background: Vec4 = background_texture.sample(*background_texture_sampler, *uv_input);
+    col *= background.truncate();
+
+    col *= terrain_color(terrain, hex_dist * 2.0, *uv_input);
+
+    let visibility = (map_data >> MAP_VISIBILITY_SHIFT) & MAP_VISIBILITY_MASK;
+    col = apply_visibility(
+        col,
+        visibility,
+        fragment_in_highlight || fragment_in_selected,
+    );
+
+    *colour_output = col.extend(color.w);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    fn assert_close_vec2(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn saturate_clamps_to_unit_range() {
+        assert_close(saturate(-1.0), 0.0);
+        assert_close(saturate(0.25), 0.25);
+        assert_close(saturate(3.0), 1.0);
+    }
+
+    #[test]
+    fn smoothstep_eases_between_edges() {
+        assert_close(smoothstep(1.0, 3.0, 0.0), 0.0);
+        assert_close(smoothstep(1.0, 3.0, 2.0), 0.5);
+        assert_close(smoothstep(1.0, 3.0, 4.0), 1.0);
+        assert_close(smoothstep(0.0, 1.0, 0.25), 0.15625);
+    }
+
+    #[test]
+    fn step_is_one_from_the_edge_on() {
+        assert_close(step(0.5, 0.4), 0.0);
+        assert_close(step(0.5, 0.5), 1.0);
+        assert_close(step(0.5, 0.6), 1.0);
+    }
+
+    #[test]
+    fn lerp_extrapolates() {
+        assert_close(lerp(0.0, 2.0, 4.0), 2.0);
+        assert_close(lerp(0.5, 2.0, 4.0), 3.0);
+        assert_close(lerp(2.0, 2.0, 4.0), 6.0);
+    }
+
+    #[test]
+    fn lerp_clamped_stays_between_min_and_max() {
+        assert_close(lerp_clamped(0.5, 2.0, 4.0), 3.0);
+        assert_close(lerp_clamped(-1.0, 2.0, 4.0), 2.0);
+        assert_close(lerp_clamped(2.0, 2.0, 4.0), 4.0);
+        assert_close(lerp_clamped(0.25, 0.4, 1.0), 0.55);
+    }
+
+    #[test]
+    fn vec2_mod_is_per_component() {
+        assert_close_vec2(vec2_mod(vec2(5.5, 3.0), vec2(2.0, 1.73)), vec2(1.5, 1.27));
+        assert_close_vec2(vec2_mod(vec2(1.0, 0.5), vec2(2.0, 1.73)), vec2(1.0, 0.5));
+    }
+
+    #[test]
+    fn wrap_coord_brings_cells_past_the_edge_back_on_the_map() {
+        let size = vec2(10.0, 8.0);
+        let left_of_map = vec2(-2.0, 3.0);
+        let below_map = vec2(4.0, -1.0);
+        assert_close_vec2(
+            wrap_coord(left_of_map, size, vec2(1.0, 0.0)),
+            vec2(8.0, 3.0),
+        );
+        assert_close_vec2(wrap_coord(below_map, size, vec2(1.0, 1.0)), vec2(4.0, 7.0));
+        assert_close_vec2(
+            wrap_coord(vec2(12.0, 9.0), size, vec2(1.0, 1.0)),
+            vec2(2.0, 1.0),
+        );
+        // Without wrapping the coordinate stays off the map
+        assert_close_vec2(wrap_coord(left_of_map, size, Vec2::ZERO), left_of_map);
+    }
+
+    #[test]
+    fn hex_dist_is_zero_at_the_center_and_half_at_the_border() {
+        assert_close(hex_dist(Vec2::ZERO), 0.0);
+        assert_close(hex_dist(vec2(0.5, 0.0)), 0.5);
+        assert_close(hex_dist(vec2(-0.5, 0.0)), 0.5);
+    }
+
+    #[test]
+    fn hex_relative_uv_is_zero_at_cell_centers() {
+        assert_close_vec2(hex_relative_uv(Vec2::ZERO), Vec2::ZERO);
+        assert_close_vec2(hex_relative_uv(vec2(0.5, 0.865)), Vec2::ZERO);
+        assert_close_vec2(hex_relative_uv(vec2(2.0, 1.73)), Vec2::ZERO);
+        assert_close_vec2(hex_relative_uv(vec2(1.6, 0.865)), vec2(0.1, 0.0));
+    }
+
+    #[test]
+    fn apply_visibility_fades_remembered_and_darkens_unexplored() {
+        let col = vec3(0.2, 0.8, 0.4);
+        assert_close_vec2(apply_visibility(col, 0, false).truncate(), col.truncate());
+        let remembered = apply_visibility(col, MAP_VISIBILITY_REMEMBERED, false);
+        assert!(remembered.y < col.y);
+        let unexplored = apply_visibility(col, MAP_VISIBILITY_UNEXPLORED, false);
+        assert_close(unexplored.x, 0.03);
+        let hovered = apply_visibility(col, MAP_VISIBILITY_UNEXPLORED, true);
+        assert_close(hovered.x, 0.12);
+    }
+
+    #[test]
+    fn every_terrain_has_its_own_color() {
+        let colors: Vec<Vec3> = (TERRAIN_LAND..=TERRAIN_BRIDGE)
+            .map(|terrain| terrain_color(terrain, 0.5, vec2(0.3, 0.1)))
+            .collect();
+        for (index, color) in colors.iter().enumerate() {
+            for other in colors.iter().skip(index + 1) {
+                assert!(
+                    (*color - *other).length() > 0.1,
+                    "{:?} == {:?}",
+                    color,
+                    other
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hex_grid_coord_round_trips() {
+        for &(x, y) in &[(0.0, 0.0), (1.0, 1.0), (4.0, 2.0), (3.0, 5.0)] {
+            let coord = vec2(x, y);
+            let id = hex_grid_coord_to_id(coord);
+            assert_close_vec2(hex_relative_uv(id), Vec2::ZERO);
+            assert_close_vec2(hex_grid_coord(id), coord);
+        }
+    }
 }
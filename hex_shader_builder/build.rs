@@ -0,0 +1,15 @@
+use spirv_builder::{MemoryModel, SpirvBuilder};
+use std::error::Error;
+
+// spirv-builder exposes the path of the compiled shader through the "hex_shader.spv" env var
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed=../hex_shader/src");
+    println!("cargo:rerun-if-changed=../hex_shader/Cargo.toml");
+    SpirvBuilder::new("../hex_shader")
+        .spirv_version(1, 3)
+        .memory_model(MemoryModel::GLSL450)
+        .release(true)
+        .build()?;
+
+    Ok(())
+}
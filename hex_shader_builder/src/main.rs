@@ -0,0 +1,4 @@
+// Hands the compiled shader to the build script of the game, which passes this line on to cargo
+fn main() {
+    println!("cargo:rustc-env=hex_shader.spv={}", env!("hex_shader.spv"));
+}
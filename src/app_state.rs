@@ -0,0 +1,34 @@
+// Screens the app moves between, the turns of a running game are tracked separately by GameState
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AppScreen {
+    MainMenu,
+    NewGameSetup,
+    InGame,
+    // Pushed on top of InGame, the game keeps existing underneath
+    Paused,
+    // Pushed on top of the main menu or the pause screen
+    Settings,
+    GameOver,
+}
+
+// Outcome of the last game, for the game over screen
+#[derive(Default, Debug)]
+pub struct GameResult {
+    pub winner: Option<i32>,
+    pub turns: i32,
+}
+
+// Marks the entities of a running game, they are despawned when it ends
+pub struct GameWorld;
+
+// For systems that only make sense while a game is being played, in any stage
+pub fn run_if_in_game(screen: Res<State<AppScreen>>) -> ShouldRun {
+    if *screen.current() == AppScreen::InGame {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
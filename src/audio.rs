@@ -0,0 +1,265 @@
+// Sound effects for what happens in the game, and music playlists that crossfade from track to track.
+// Which files play is defined in a ron file in the assets, volumes come from the settings
+use crate::app_state::{AppScreen, GameResult};
+use crate::gameplay::components::{
+    ProvinceBankrupt, Selection, SelectionChanged, TurnStarted, UnitMoved, UnitPurchased,
+};
+use crate::settings::Settings;
+use bevy::prelude::*;
+use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin, AudioSource};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+const SOUNDS_FILE: &str = "assets/audio/sounds.ron";
+
+const EFFECTS_CHANNEL: &str = "effects";
+// Music alternates between two channels, so one track can fade out while the next fades in
+const MUSIC_CHANNELS: [&str; 2] = ["music_a", "music_b"];
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum SoundEvent {
+    Select,
+    Move,
+    Capture,
+    Purchase,
+    Bankruptcy,
+    TurnStart,
+    Victory,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum Playlist {
+    Menu,
+    Game,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Track {
+    // Relative to the assets folder
+    pub path: String,
+    // The next track starts fading in this long after the track started, minus the crossfade
+    pub seconds: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SoundConfig {
+    // Files relative to the assets folder, an event with several of them plays them in turn
+    pub sounds: HashMap<SoundEvent, Vec<String>>,
+    pub playlists: HashMap<Playlist, Vec<Track>>,
+    pub crossfade_seconds: f32,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        SoundConfig {
+            sounds: HashMap::new(),
+            playlists: HashMap::new(),
+            crossfade_seconds: 3.0,
+        }
+    }
+}
+
+impl SoundConfig {
+    // Without the file the game stays silent
+    pub fn load() -> Self {
+        fs::read_to_string(SOUNDS_FILE)
+            .map_err(|error| warn!("Could not read {}: {}", SOUNDS_FILE, error))
+            .ok()
+            .and_then(|text| match ron::de::from_str::<SoundConfig>(&text) {
+                Ok(config) => Some(config),
+                Err(error) => {
+                    warn!("Ignoring {}: {}", SOUNDS_FILE, error);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+}
+
+// Loaded sound effects, and which of the variations of every event plays next
+#[derive(Default)]
+struct SoundEffects {
+    sounds: HashMap<SoundEvent, Vec<Handle<AudioSource>>>,
+    next: HashMap<SoundEvent, usize>,
+}
+
+impl SoundEffects {
+    fn next(&mut self, event: SoundEvent) -> Option<Handle<AudioSource>> {
+        let sounds = self.sounds.get(&event).filter(|sounds| !sounds.is_empty())?;
+        let index = self.next.entry(event).or_insert(0);
+        let sound = sounds[*index % sounds.len()].clone();
+        *index = (*index + 1) % sounds.len();
+        Some(sound)
+    }
+}
+
+#[derive(Default)]
+struct MusicPlayer {
+    playlist: Option<Playlist>,
+    track: usize,
+    // Seconds since the current track started
+    elapsed: f32,
+    // Index into MUSIC_CHANNELS of the channel the current track plays on
+    channel: usize,
+    // Seconds into the crossfade from the other channel, None once it's done
+    fade: Option<f32>,
+}
+
+fn music_channel(index: usize) -> AudioChannel {
+    AudioChannel::new(MUSIC_CHANNELS[index].to_string())
+}
+
+fn load_sounds(
+    config: Res<SoundConfig>,
+    asset_server: Res<AssetServer>,
+    mut effects: ResMut<SoundEffects>,
+) {
+    effects.sounds = config
+        .sounds
+        .iter()
+        .map(|(event, paths)| {
+            let handles = paths.iter().map(|path| asset_server.load(path.as_str())).collect();
+            (*event, handles)
+        })
+        .collect();
+}
+
+// Turns gameplay events into sound events
+fn event_sounds(
+    selection: Res<Selection>,
+    result: Option<Res<GameResult>>,
+    mut selection_events: EventReader<SelectionChanged>,
+    mut moved_events: EventReader<UnitMoved>,
+    mut purchased_events: EventReader<UnitPurchased>,
+    mut bankrupt_events: EventReader<ProvinceBankrupt>,
+    mut turn_events: EventReader<TurnStarted>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    if selection_events.iter().count() > 0 && !selection.coords.is_empty() {
+        sound_events.send(SoundEvent::Select);
+    }
+    for moved in moved_events.iter() {
+        sound_events.send(match moved.outcome.captured_from {
+            Some(_) => SoundEvent::Capture,
+            None => SoundEvent::Move,
+        });
+    }
+    for _ in purchased_events.iter() {
+        sound_events.send(SoundEvent::Purchase);
+    }
+    if bankrupt_events.iter().count() > 0 {
+        sound_events.send(SoundEvent::Bankruptcy);
+    }
+    if turn_events.iter().count() > 0 {
+        sound_events.send(SoundEvent::TurnStart);
+    }
+    if result.map_or(false, |result| result.is_changed()) {
+        sound_events.send(SoundEvent::Victory);
+    }
+}
+
+fn play_sounds(
+    audio: Res<Audio>,
+    mut effects: ResMut<SoundEffects>,
+    mut sound_events: EventReader<SoundEvent>,
+) {
+    let channel = AudioChannel::new(EFFECTS_CHANNEL.to_string());
+    for event in sound_events.iter() {
+        if let Some(sound) = effects.next(*event) {
+            audio.play_in_channel(sound, &channel);
+        }
+    }
+}
+
+fn effects_volume(settings: Res<Settings>, audio: Res<Audio>) {
+    if settings.is_changed() {
+        let volume = settings.audio.master_volume * settings.audio.effects_volume;
+        audio.set_volume_in_channel(volume, &AudioChannel::new(EFFECTS_CHANNEL.to_string()));
+    }
+}
+
+// Plays the playlist of the current screen, and fades over to the next track when one ends or the playlist changes
+fn music_system(
+    time: Res<Time>,
+    screen: Res<State<AppScreen>>,
+    settings: Res<Settings>,
+    config: Res<SoundConfig>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut player: ResMut<MusicPlayer>,
+) {
+    let in_game = *screen.current() == AppScreen::InGame
+        || *screen.current() == AppScreen::GameOver
+        || screen.inactives().contains(&AppScreen::InGame);
+    let playlist = if in_game {
+        Playlist::Game
+    } else {
+        Playlist::Menu
+    };
+    let tracks = config
+        .playlists
+        .get(&playlist)
+        .map_or(&[][..], |tracks| tracks.as_slice());
+    let crossfade = config.crossfade_seconds.max(0.01);
+
+    player.elapsed += time.delta_seconds();
+    let next_track = if player.playlist != Some(playlist) {
+        player.playlist = Some(playlist);
+        Some(0)
+    } else {
+        tracks
+            .get(player.track)
+            .filter(|track| player.elapsed >= track.seconds - crossfade)
+            .map(|_| (player.track + 1) % tracks.len())
+    };
+    if let Some(index) = next_track {
+        player.channel = 1 - player.channel;
+        player.track = index;
+        player.elapsed = 0.0;
+        player.fade = Some(0.0);
+        let channel = music_channel(player.channel);
+        audio.stop_channel(&channel);
+        if let Some(track) = tracks.get(index) {
+            audio.play_in_channel(asset_server.load(track.path.as_str()), &channel);
+        }
+    }
+
+    let volume = settings.audio.master_volume * settings.audio.music_volume;
+    let fade_in = match player.fade {
+        Some(fade) => {
+            let fade = fade + time.delta_seconds();
+            if fade >= crossfade {
+                audio.stop_channel(&music_channel(1 - player.channel));
+                player.fade = None;
+                1.0
+            } else {
+                player.fade = Some(fade);
+                fade / crossfade
+            }
+        }
+        None => 1.0,
+    };
+    audio.set_volume_in_channel(volume * fade_in, &music_channel(player.channel));
+    if player.fade.is_some() {
+        audio.set_volume_in_channel(volume * (1.0 - fade_in), &music_channel(1 - player.channel));
+    }
+}
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(AudioPlugin)
+            .add_event::<SoundEvent>()
+            .insert_resource(SoundConfig::load())
+            .init_resource::<SoundEffects>()
+            .init_resource::<MusicPlayer>()
+            .add_startup_system(load_sounds.system())
+            .add_system(event_sounds.system().label("event_sounds"))
+            .add_system(play_sounds.system().after("event_sounds"))
+            .add_system(effects_volume.system())
+            .add_system(music_system.system());
+    }
+}
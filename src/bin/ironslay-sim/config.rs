@@ -0,0 +1,126 @@
+// What the simulator plays, read from a ron file
+use ironslay::gameplay::ai::AiKind;
+use ironslay::gameplay::components::{PlanetShape, Scenario};
+use ironslay::gameplay::ruleset::{Variants, DEFAULT_RULESET};
+use ironslay::gameplay::topology::WrapMode;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum MapLayout {
+    // The layout of the sandbox game, it always has two teams
+    Sandbox,
+    // A fresh random map every game, with about `land` of the tiles land
+    Generated { land: f64 },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MapConfig {
+    pub name: String,
+    pub shape: PlanetShape,
+    // Only used by flat maps, like in the Scenario
+    #[serde(default)]
+    pub width: i32,
+    #[serde(default)]
+    pub height: i32,
+    #[serde(default)]
+    pub wrap: WrapMode,
+    pub layout: MapLayout,
+    // Ruleset asset the games on this map are played with
+    #[serde(default = "default_rules")]
+    pub rules: String,
+    #[serde(default)]
+    pub variants: Variants,
+}
+
+fn default_rules() -> String {
+    DEFAULT_RULESET.to_string()
+}
+
+impl MapConfig {
+    pub fn scenario(&self) -> Scenario {
+        Scenario {
+            name: self.name.clone(),
+            planet_shape: self.shape,
+            width: self.width,
+            height: self.height,
+            wrap: self.wrap,
+            rules: self.rules.clone(),
+            variants: self.variants.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SimConfig {
+    pub games: usize,
+    // Games that take longer end in a draw
+    pub max_turns: i32,
+    // Game n is played with seed + n, so every game can be replayed on its own
+    pub seed: u64,
+    // The players, the first one plays team 1 in the first game.
+    // Seats rotate every time the games have gone round all maps, so every player sits in every seat on every map
+    pub players: Vec<AiKind>,
+    // Games are spread over the maps in turn
+    pub maps: Vec<MapConfig>,
+    // Zero uses every core
+    pub threads: usize,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            games: 100,
+            max_turns: 300,
+            seed: 0,
+            players: vec![AiKind::Greedy, AiKind::Defensive],
+            maps: vec![MapConfig {
+                name: "Generated".to_string(),
+                shape: PlanetShape::HexagonCap,
+                width: 16,
+                height: 16,
+                wrap: WrapMode::None,
+                layout: MapLayout::Generated { land: 0.7 },
+                rules: default_rules(),
+                variants: Variants::default(),
+            }],
+            threads: 0,
+        }
+    }
+}
+
+impl SimConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        let config: SimConfig =
+            ron::de::from_str(&text).map_err(|error| format!("{}: {}", path.display(), error))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.players.len() < 2 {
+            return Err("A game needs at least two players".to_string());
+        }
+        if self.maps.is_empty() {
+            return Err("No maps to play on".to_string());
+        }
+        for map in self.maps.iter() {
+            if map.shape == PlanetShape::HexagonCap {
+                if map.width <= 0 || map.height <= 0 {
+                    return Err(format!("Map {} has no size", map.name));
+                }
+                // FlatHexTopology can't line up the hexes of an odd wrapping edge
+                if (map.wrap.wraps_x() && map.width % 2 != 0)
+                    || (map.wrap.wraps_y() && map.height % 2 != 0)
+                {
+                    return Err(format!("Map {} needs an even size to wrap", map.name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
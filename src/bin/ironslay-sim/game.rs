@@ -0,0 +1,193 @@
+// Plays a single game between computer players on the rules' board, without any of the ECS
+use crate::config::{MapLayout, SimConfig};
+use ironslay::gameplay::ai;
+use ironslay::gameplay::components::{HexGrid, Occupant, TurnOrder};
+use ironslay::gameplay::mapgen;
+use ironslay::gameplay::rules::{self, Board, TeamMemory};
+use ironslay::gameplay::ruleset::RuleSet;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+use std::collections::HashSet;
+
+// Where a team stands at the end of its turn
+#[derive(Clone, Debug, Serialize)]
+pub struct TurnStats {
+    pub game: usize,
+    pub turn: i32,
+    pub team: i32,
+    pub player: usize,
+    pub tiles: usize,
+    pub provinces: usize,
+    pub units: usize,
+    pub treasury: i32,
+    pub income: i32,
+    pub upkeep: i32,
+    pub actions: usize,
+    pub bankruptcies: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub game: usize,
+    pub map: String,
+    pub seed: u64,
+    // The player of every team, team 1 first
+    pub seats: Vec<usize>,
+    // The player that won, None for a draw
+    pub winner: Option<usize>,
+    pub turns: i32,
+    pub turn_stats: Vec<TurnStats>,
+}
+
+// `rulesets` holds the rules of every map, in the order of the config
+pub fn simulate(config: &SimConfig, rulesets: &[RuleSet], game: usize) -> GameRecord {
+    let map = &config.maps[game % config.maps.len()];
+    let ruleset = &rulesets[game % config.maps.len()];
+    let seed = config.seed.wrapping_add(game as u64);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let players = config.players.len();
+
+    let grid = HexGrid::for_scenario(&map.scenario());
+    let mut board = Board::new(&grid, ruleset);
+    match map.layout {
+        MapLayout::Sandbox => mapgen::fill_sandbox(&mut board, &grid, &mut rng),
+        MapLayout::Generated { land } => {
+            mapgen::generate(&mut board, players as i32, land, &mut rng);
+        }
+    }
+
+    let seats = seats(game, config.maps.len(), players);
+    let turn_order = TurnOrder {
+        teams: (1..=players as i32).collect(),
+    };
+    let mut record = GameRecord {
+        game,
+        map: map.name.clone(),
+        seed,
+        seats: seats.clone(),
+        winner: None,
+        turns: 0,
+        turn_stats: Vec::new(),
+    };
+    // What every team has seen of the board, indexed by team - 1
+    let mut memories = vec![TeamMemory::default(); players];
+    while record.turns < config.max_turns {
+        let turn = record.turns;
+        record.turns += 1;
+        let team = turn_order.team_for_turn(turn);
+        let player = seats[(team - 1) as usize];
+        // Teams without land are out of the game
+        if !board
+            .tiles
+            .iter()
+            .flatten()
+            .any(|tile| rules::is_land(ruleset, tile) && tile.team == team)
+        {
+            continue;
+        }
+
+        let report = rules::start_turn(&mut board, team, &mut rng);
+        let actions = ai::play_turn(
+            &mut board,
+            &mut memories[(team - 1) as usize],
+            team,
+            config.players[player],
+            &mut rng,
+        );
+        let mut stats = team_stats(&board, team);
+        stats.game = game;
+        stats.turn = turn;
+        stats.player = player;
+        stats.actions = actions.len();
+        stats.bankruptcies = report.bankrupt.len();
+        record.turn_stats.push(stats);
+
+        if let Some(game_over) = rules::game_over(&board, record.turns / players as i32) {
+            record.winner = game_over
+                .winner
+                .and_then(|winner| seats.get((winner - 1) as usize).copied());
+            break;
+        }
+    }
+    record
+}
+
+// The player of every team in `game`. The games cycle through the maps, and the seats rotate once per cycle,
+// so a map doesn't always see the same player move first
+pub fn seats(game: usize, maps: usize, players: usize) -> Vec<usize> {
+    (0..players)
+        .map(|seat| (seat + game / maps) % players)
+        .collect()
+}
+
+fn team_stats(board: &Board, team: i32) -> TurnStats {
+    let mut stats = TurnStats {
+        game: 0,
+        turn: 0,
+        team,
+        player: 0,
+        tiles: 0,
+        provinces: 0,
+        units: 0,
+        treasury: 0,
+        income: 0,
+        upkeep: 0,
+        actions: 0,
+        bankruptcies: 0,
+    };
+    let mut visited = HashSet::new();
+    for coord in board.coords() {
+        let tile = board.tile(coord).unwrap();
+        if !rules::is_land(board.rules, tile) || tile.team != team {
+            continue;
+        }
+        stats.tiles += 1;
+        if matches!(tile.occupant, Some(Occupant::Unit(_))) {
+            stats.units += 1;
+        }
+        if visited.contains(&coord) {
+            continue;
+        }
+        let province = rules::province(board, coord);
+        visited.extend(province.iter().copied());
+        // Lone tiles without a capital don't count as a province
+        if let Some(treasury) = rules::province_treasury(board, &province) {
+            stats.provinces += 1;
+            stats.treasury += treasury;
+            stats.income += rules::province_income(board, &province);
+            stats.upkeep += rules::province_upkeep(board, &province);
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_map_sees_every_player_in_every_seat() {
+        for &(maps, players) in &[(6, 2), (1, 2), (4, 3), (3, 3)] {
+            let games = maps * players;
+            for map in 0..maps {
+                for seat in 0..players {
+                    let mut seen: Vec<usize> = (map..games)
+                        .step_by(maps)
+                        .map(|game| seats(game, maps, players)[seat])
+                        .collect();
+                    seen.sort_unstable();
+                    assert_eq!(
+                        seen,
+                        (0..players).collect::<Vec<usize>>(),
+                        "{} maps, {} players, map {} seat {}",
+                        maps,
+                        players,
+                        map,
+                        seat
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// Plays many games between computer players without a window, and reports how every player did.
+// For tuning the AI and checking balance changes with numbers instead of a feeling.
+//
+//   cargo run --release --features sim --bin ironslay-sim -- [CONFIG] [--games N] [--threads N] [--seed N] [--out DIR]
+//
+// CONFIG is a ron file like sim/default.ron, the flags override what it says
+mod config;
+mod game;
+mod report;
+
+use config::SimConfig;
+use ironslay::gameplay::ruleset::RuleSet;
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
+
+const DEFAULT_CONFIG: &str = "sim/default.ron";
+const DEFAULT_OUT: &str = "sim_results";
+
+const USAGE: &str = "Usage: ironslay-sim [CONFIG] [FLAGS]
+
+  CONFIG          ron file with the players and maps, sim/default.ron by default
+  --games N       number of games to play
+  --threads N     worker threads, 0 uses every core
+  --seed N        game n is played with seed + n
+  --out DIR       folder the csv and json reports are written to, sim_results by default
+  -h, --help      show this message";
+
+struct Args {
+    config: PathBuf,
+    out: PathBuf,
+    games: Option<usize>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+}
+
+// None when the usage was asked for
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        config: PathBuf::from(DEFAULT_CONFIG),
+        out: PathBuf::from(DEFAULT_OUT),
+        games: None,
+        threads: None,
+        seed: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--out" => parsed.out = PathBuf::from(value()?),
+            "--games" => parsed.games = Some(parse_number(&arg, value()?)?),
+            "--threads" => parsed.threads = Some(parse_number(&arg, value()?)?),
+            "--seed" => parsed.seed = Some(parse_number(&arg, value()?)?),
+            "--help" | "-h" => return Ok(None),
+            flag if flag.starts_with('-') => {
+                return Err(format!("Unknown flag {}\n\n{}", flag, USAGE))
+            }
+            _ => parsed.config = PathBuf::from(arg),
+        }
+    }
+    Ok(Some(parsed))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, not {}", flag, value))
+}
+
+fn run() -> Result<(), String> {
+    let args = match parse_args(std::env::args().skip(1))? {
+        Some(args) => args,
+        None => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+    };
+    let mut config = SimConfig::load(&args.config)?;
+    config.games = args.games.unwrap_or(config.games);
+    config.threads = args.threads.unwrap_or(config.threads);
+    config.seed = args.seed.unwrap_or(config.seed);
+
+    // Every map is played with its own rules and variants, they are read once for all of its games
+    let rulesets = config
+        .maps
+        .iter()
+        .map(|map| {
+            RuleSet::load(&map.rules)
+                .and_then(|rules| rules.with_variants(&map.variants))
+                .map_err(|errors| format!("Map {}: {}", map.name, errors.join(", ")))
+        })
+        .collect::<Result<Vec<RuleSet>, String>>()?;
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(config.threads)
+        .build_global()
+        .map_err(|error| error.to_string())?;
+
+    let started = Instant::now();
+    let records: Vec<game::GameRecord> = (0..config.games)
+        .into_par_iter()
+        .map(|index| game::simulate(&config, &rulesets, index))
+        .collect();
+    let summary = report::summarize(&config, &records);
+    report::write(&args.out, &config, &summary, &records)?;
+
+    println!(
+        "{} games in {:.1}s, {} draws, {:.1} turns on average",
+        summary.games,
+        started.elapsed().as_secs_f32(),
+        summary.draws,
+        summary.average_turns
+    );
+    for player in summary.players.iter() {
+        println!(
+            "  player {} ({:?}): {} wins, {:.1}%",
+            player.player,
+            player.ai,
+            player.wins,
+            player.win_rate * 100.0
+        );
+    }
+    for map in summary.maps.iter() {
+        println!(
+            "  map {}: {} games, {} draws, {:.1} turns on average",
+            map.map, map.games, map.draws, map.average_turns
+        );
+    }
+    println!("Results written to {}", args.out.display());
+    Ok(())
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("ironslay-sim: {}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,154 @@
+// Sums up the played games, and writes them out as csv for spreadsheets and json for scripts
+use crate::config::SimConfig;
+use crate::game::GameRecord;
+use ironslay::gameplay::ai::AiKind;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::path::Path;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlayerSummary {
+    pub player: usize,
+    pub ai: AiKind,
+    pub games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MapSummary {
+    pub map: String,
+    pub games: usize,
+    pub draws: usize,
+    pub average_turns: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    pub games: usize,
+    pub draws: usize,
+    pub average_turns: f64,
+    pub players: Vec<PlayerSummary>,
+    pub maps: Vec<MapSummary>,
+}
+
+// One line of games.csv
+#[derive(Serialize)]
+struct GameRow<'a> {
+    game: usize,
+    map: &'a str,
+    seed: u64,
+    // Players of team 1, 2 and so on, separated by spaces
+    seats: String,
+    winner: Option<usize>,
+    winner_ai: Option<AiKind>,
+    turns: i32,
+}
+
+fn average_turns<'a>(records: impl Iterator<Item = &'a GameRecord>) -> f64 {
+    let (count, turns) = records.fold((0, 0), |(count, turns), record| {
+        (count + 1, turns + record.turns as i64)
+    });
+    if count == 0 {
+        0.0
+    } else {
+        turns as f64 / count as f64
+    }
+}
+
+pub fn summarize(config: &SimConfig, records: &[GameRecord]) -> Summary {
+    let players = config
+        .players
+        .iter()
+        .enumerate()
+        .map(|(player, ai)| {
+            let wins = records
+                .iter()
+                .filter(|record| record.winner == Some(player))
+                .count();
+            PlayerSummary {
+                player,
+                ai: *ai,
+                games: records.len(),
+                wins,
+                win_rate: wins as f64 / records.len().max(1) as f64,
+            }
+        })
+        .collect();
+
+    let mut map_names: Vec<&str> = Vec::new();
+    for map in config.maps.iter() {
+        if !map_names.contains(&map.name.as_str()) {
+            map_names.push(&map.name);
+        }
+    }
+    let maps = map_names
+        .into_iter()
+        .map(|name| {
+            let on_map = move || records.iter().filter(move |record| record.map == name);
+            MapSummary {
+                map: name.to_string(),
+                games: on_map().count(),
+                draws: on_map().filter(|record| record.winner.is_none()).count(),
+                average_turns: average_turns(on_map()),
+            }
+        })
+        .collect();
+
+    Summary {
+        games: records.len(),
+        draws: records
+            .iter()
+            .filter(|record| record.winner.is_none())
+            .count(),
+        average_turns: average_turns(records.iter()),
+        players,
+        maps,
+    }
+}
+
+// Writes games.csv, turns.csv and summary.json into `out`
+pub fn write(
+    out: &Path,
+    config: &SimConfig,
+    summary: &Summary,
+    records: &[GameRecord],
+) -> Result<(), String> {
+    let describe =
+        |path: &Path, error: &dyn std::fmt::Display| format!("{}: {}", path.display(), error);
+    fs::create_dir_all(out).map_err(|e| describe(out, &e))?;
+
+    let path = out.join("games.csv");
+    let mut games = csv::Writer::from_path(&path).map_err(|e| describe(&path, &e))?;
+    for record in records.iter() {
+        games
+            .serialize(GameRow {
+                game: record.game,
+                map: &record.map,
+                seed: record.seed,
+                seats: record
+                    .seats
+                    .iter()
+                    .map(|player| player.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                winner: record.winner,
+                winner_ai: record.winner.map(|player| config.players[player]),
+                turns: record.turns,
+            })
+            .map_err(|e| describe(&path, &e))?;
+    }
+    games.flush().map_err(|e| describe(&path, &e))?;
+
+    let path = out.join("turns.csv");
+    let mut turns = csv::Writer::from_path(&path).map_err(|e| describe(&path, &e))?;
+    for stats in records.iter().flat_map(|record| record.turn_stats.iter()) {
+        turns.serialize(stats).map_err(|e| describe(&path, &e))?;
+    }
+    turns.flush().map_err(|e| describe(&path, &e))?;
+
+    let path = out.join("summary.json");
+    let file = File::create(&path).map_err(|e| describe(&path, &e))?;
+    serde_json::to_writer_pretty(file, summary).map_err(|e| describe(&path, &e))?;
+    Ok(())
+}
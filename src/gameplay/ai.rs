@@ -0,0 +1,320 @@
+// Computer players, they choose their moves and purchases by looking at the rules' board only.
+// Under fog of war they only know what their team has seen of it
+use super::components::{Occupant, Purchase, UNIT_TIERS};
+use super::rules::{self, Board, TeamMemory};
+use bevy::math::IVec2;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+pub enum AiKind {
+    // Takes any legal action, a baseline to measure the others against
+    Random,
+    // Goes for the most valuable capture, and buys units as long as the province can feed them
+    Greedy,
+    // Like Greedy, but keeps a part of its income spare and builds castles where it is threatened
+    Defensive,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum AiAction {
+    Move {
+        from: IVec2,
+        to: IVec2,
+    },
+    Purchase {
+        province: IVec2,
+        item: Purchase,
+        target: IVec2,
+    },
+}
+
+// Units can walk around inside their province as often as they like, this bounds a single turn
+const MAX_ACTIONS: usize = 200;
+// Chance the Random player ends its turn before every action
+const RANDOM_STOP_CHANCE: f64 = 0.2;
+
+const CAPTURE_BONUS: i32 = 10;
+// Taking a tree or grave off our land gives back its income
+const CLEARING_VALUE: i32 = 6;
+// Spending on a stronger unit than needed has to pay off
+const STRENGTH_PENALTY: i32 = 3;
+const CASTLE_VALUE: i32 = 5;
+// A unit the income can't feed is still bought when the treasury pays its upkeep this many turns
+const FEED_TURNS: i32 = 10;
+
+// Plays the whole turn of `team` on the board, returns the actions it took in order.
+// It plans on what `memory` knows of the board, which it updates along the way
+pub fn play_turn(
+    board: &mut Board,
+    memory: &mut TeamMemory,
+    team: i32,
+    kind: AiKind,
+    rng: &mut impl Rng,
+) -> Vec<AiAction> {
+    let mut actions = Vec::new();
+    // A unit repositions once per turn, so two of them can't keep trading places
+    let mut repositioned = HashSet::new();
+    // Actions the board turned down, what was out of sight made them illegal
+    let mut refused = HashSet::new();
+    while actions.len() + refused.len() < MAX_ACTIONS {
+        memory.observe(board, team);
+        let known = memory.known_board(board);
+        let candidates: Vec<(AiAction, i32)> = candidates(&known, team, kind, &repositioned)
+            .into_iter()
+            .filter(|(action, _)| !refused.contains(action))
+            .collect();
+        let action = match kind {
+            AiKind::Random => {
+                if rng.gen_bool(RANDOM_STOP_CHANCE) {
+                    break;
+                }
+                candidates.choose(rng).map(|(action, _)| *action)
+            }
+            AiKind::Greedy | AiKind::Defensive => candidates
+                .iter()
+                .filter(|(_, score)| *score > 0)
+                .max_by_key(|(_, score)| *score)
+                .map(|(action, _)| *action),
+        };
+        let action = match action {
+            Some(action) => action,
+            None => break,
+        };
+        if !apply(board, action) {
+            refused.insert(action);
+            continue;
+        }
+        if let AiAction::Move { to, .. } = action {
+            repositioned.insert(to);
+        }
+        actions.push(action);
+    }
+    actions
+}
+
+fn apply(board: &mut Board, action: AiAction) -> bool {
+    match action {
+        AiAction::Move { from, to } => rules::apply_move(board, from, to).is_some(),
+        AiAction::Purchase {
+            province,
+            item,
+            target,
+        } => rules::apply_purchase(board, province, item, target).is_some(),
+    }
+}
+
+// Every action the player considers, with how much it likes it
+fn candidates(
+    board: &Board,
+    team: i32,
+    kind: AiKind,
+    repositioned: &HashSet<IVec2>,
+) -> Vec<(AiAction, i32)> {
+    let mut candidates = Vec::new();
+    let own_coords: Vec<IVec2> = board
+        .coords()
+        .into_iter()
+        .filter(|coord| board.tile(*coord).map_or(false, |tile| tile.team == team))
+        .collect();
+
+    for from in own_coords.iter().copied() {
+        if !matches!(
+            board.tile(from).and_then(|tile| tile.occupant),
+            Some(Occupant::Unit(_))
+        ) {
+            continue;
+        }
+        for to in rules::legal_moves(board, from) {
+            let captures = board.tile(to).map_or(false, |tile| tile.team != team);
+            if !captures && repositioned.contains(&from) {
+                continue;
+            }
+            let score = match target_value(board, team, to) {
+                Some(value) if captures => value,
+                Some(value) => value - frontier(board, team, from),
+                None => 0,
+            };
+            candidates.push((AiAction::Move { from, to }, score));
+        }
+    }
+
+    let threatened: HashSet<IVec2> = match kind {
+        AiKind::Defensive => rules::threatened_tiles(board, team).into_iter().collect(),
+        _ => HashSet::new(),
+    };
+    let mut visited = HashSet::new();
+    for coord in own_coords {
+        if visited.contains(&coord) {
+            continue;
+        }
+        let tiles = rules::province(board, coord);
+        visited.extend(tiles.iter().copied());
+        let treasury = match rules::province_treasury(board, &tiles) {
+            Some(treasury) => treasury,
+            None => continue,
+        };
+        let income = rules::province_income(board, &tiles);
+        let surplus = income - rules::province_upkeep(board, &tiles);
+        let reserve = match kind {
+            AiKind::Defensive => income / 4,
+            _ => 0,
+        };
+
+        for tier in UNIT_TIERS.iter().copied() {
+            let item = Purchase::Unit(tier);
+            // Buying a unit the province can't feed only leads to bankruptcy
+            let balance = surplus - board.rules.unit(tier).upkeep - reserve;
+            let fed = balance >= 0
+                || treasury - board.rules.purchase_cost(item) + FEED_TURNS * balance >= 0;
+            if !fed && kind != AiKind::Random {
+                continue;
+            }
+            for target in rules::placement_targets(board, coord, item) {
+                let score = target_value(board, team, target).map_or(0, |value| {
+                    value - STRENGTH_PENALTY * board.rules.unit(tier).strength
+                });
+                candidates.push((
+                    AiAction::Purchase {
+                        province: coord,
+                        item,
+                        target,
+                    },
+                    score,
+                ));
+            }
+        }
+
+        if kind == AiKind::Greedy
+            || treasury - board.rules.purchase_cost(Purchase::Castle) < reserve
+        {
+            continue;
+        }
+        for target in rules::placement_targets(board, coord, Purchase::Castle) {
+            let score = if threatened.contains(&target) {
+                CASTLE_VALUE + frontier(board, team, target)
+            } else {
+                0
+            };
+            candidates.push((
+                AiAction::Purchase {
+                    province: coord,
+                    item: Purchase::Castle,
+                    target,
+                },
+                score,
+            ));
+        }
+    }
+    candidates
+}
+
+// How much we gain from a unit of `team` ending up on `target`, None when it would merge with another unit
+fn target_value(board: &Board, team: i32, target: IVec2) -> Option<i32> {
+    let tile = board.tile(target)?;
+    if tile.team != team {
+        let occupant_value = match tile.occupant {
+            Some(Occupant::Capital) => 30,
+            Some(Occupant::Castle) => 10,
+            Some(Occupant::Unit(tier)) => 8 * board.rules.unit(tier).strength,
+            Some(Occupant::Tree(_)) | Some(Occupant::Grave) => 1,
+            None => 4,
+        };
+        // Land that links up with our own is worth more
+        let own_neighbors = board
+            .topology
+            .neighbors(target)
+            .into_iter()
+            .filter_map(|neighbor| board.tile(neighbor))
+            .filter(|neighbor| rules::is_land(board.rules, neighbor) && neighbor.team == team)
+            .count() as i32;
+        return Some(CAPTURE_BONUS + occupant_value + 2 * own_neighbors);
+    }
+    match tile.occupant {
+        Some(Occupant::Tree(_)) | Some(Occupant::Grave) => Some(CLEARING_VALUE),
+        None => Some(frontier(board, team, target)),
+        _ => None,
+    }
+}
+
+// Amount of enemy land around `coord`, units are most useful where this is high
+fn frontier(board: &Board, team: i32, coord: IVec2) -> i32 {
+    board
+        .topology
+        .neighbors(coord)
+        .into_iter()
+        .filter_map(|neighbor| board.tile(neighbor))
+        .filter(|neighbor| rules::is_land(board.rules, neighbor) && neighbor.team != team)
+        .count() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::mapgen;
+    use crate::gameplay::ruleset::load_default_ruleset;
+    use crate::gameplay::topology::{FlatHexTopology, WrapMode};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Replays the actions on the board as it was before the turn, each one has to be legal for `team` when it is taken
+    fn assert_legal(before: &Board, after: &Board, team: i32, actions: &[AiAction]) {
+        let mut board = Board {
+            topology: before.topology,
+            rules: before.rules,
+            tiles: before.tiles.clone(),
+        };
+        for action in actions.iter().copied() {
+            let (own, legal) = match action {
+                AiAction::Move { from, to } => {
+                    (from, rules::legal_moves(&board, from).contains(&to))
+                }
+                AiAction::Purchase {
+                    province,
+                    item,
+                    target,
+                } => (
+                    province,
+                    rules::placement_targets(&board, province, item).contains(&target),
+                ),
+            };
+            assert_eq!(board.tile(own).unwrap().team, team, "{:?}", action);
+            assert!(legal, "{:?}", action);
+            assert!(apply(&mut board, action));
+        }
+        assert_eq!(board.tiles, after.tiles);
+    }
+
+    #[test]
+    fn every_kind_only_takes_legal_actions() {
+        let topology = FlatHexTopology::new(16, 16, WrapMode::None);
+        let rules = load_default_ruleset();
+        for kind in [AiKind::Random, AiKind::Greedy, AiKind::Defensive]
+            .iter()
+            .copied()
+        {
+            let mut rng = StdRng::seed_from_u64(11);
+            let mut board = Board::new(&topology, &rules);
+            mapgen::generate(&mut board, 2, 0.8, &mut rng);
+            let mut memories = vec![TeamMemory::default(); 2];
+            let mut taken = 0;
+            for _round in 0..10 {
+                for team in 1..=2 {
+                    rules::start_turn(&mut board, team, &mut rng);
+                    let before = Board {
+                        topology: &topology,
+                        rules: &rules,
+                        tiles: board.tiles.clone(),
+                    };
+                    let memory = &mut memories[(team - 1) as usize];
+                    let actions = play_turn(&mut board, memory, team, kind, &mut rng);
+                    assert_legal(&before, &board, team, &actions);
+                    taken += actions.len();
+                }
+            }
+            assert!(taken > 0, "{:?} never acted", kind);
+        }
+    }
+}
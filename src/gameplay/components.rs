@@ -1,23 +1,93 @@
+use super::rules::{MoveOutcome, PurchaseOutcome, TeamMemory, TileState};
+use super::ruleset::{Variants, DEFAULT_RULESET};
+use super::topology::{FlatHexTopology, GoldbergSphere, HexTopology, WrapMode};
 use bevy::ecs::entity::Entity;
 use bevy::math::{IVec2, Vec2};
+use bevy::utils::HashMap;
+use serde::Deserialize;
 
 // components
 #[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
 pub struct GridPosition {
     pub position: IVec2,
 }
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+// What a terrain does in the game comes from the ruleset
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 pub enum TerrainType {
     Land,
     Water,
+    Mountain,
+    Forest,
+    Plains,
+    Desert,
+    Shallows,
+    Bridge,
 }
+
+pub const TERRAIN_TYPES: [TerrainType; 8] = [
+    TerrainType::Land,
+    TerrainType::Water,
+    TerrainType::Mountain,
+    TerrainType::Forest,
+    TerrainType::Plains,
+    TerrainType::Desert,
+    TerrainType::Shallows,
+    TerrainType::Bridge,
+];
 #[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
 pub struct Team {
     pub number: i32,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum UnitTier {
+    Peasant,
+    Spearman,
+    Knight,
+    Baron,
+}
+
+// From weakest to strongest
+pub const UNIT_TIERS: [UnitTier; 4] = [
+    UnitTier::Peasant,
+    UnitTier::Spearman,
+    UnitTier::Knight,
+    UnitTier::Baron,
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TreeKind {
+    Pine,
+    Palm,
+}
+
+// Whatever stands on a tile, a tile holds at most one of these
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Occupant {
+    Unit(UnitTier),
+    Castle,
+    Capital,
+    Tree(TreeKind),
+    Grave,
+}
+
+impl Occupant {
+    pub fn is_unit(&self) -> bool {
+        matches!(self, Occupant::Unit(_))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+pub enum PlanetShape {
+    // Flat grid mapped onto the HexagonCap model through its uvs
+    HexagonCap,
+    // Goldberg polyhedron covering the whole planet
+    Sphere { subdivisions: u32 },
+}
+
 pub struct Planet {
     pub number: i32,
+    pub shape: PlanetShape,
 }
 
 pub struct MovementRange {
@@ -28,10 +98,44 @@ pub struct Power {
     pub power: i32,
 }
 
+pub enum GridShape {
+    Flat(FlatHexTopology),
+    Sphere(GoldbergSphere),
+}
+
+// width and height are the dimensions of the map_state texture, which for flat grids is the grid itself
 pub struct HexGrid {
     pub width: i32,
     pub height: i32,
     pub cells: Vec<Entity>,
+    pub shape: GridShape,
+}
+
+// Describes the map a game is played on
+pub struct Scenario {
+    pub name: String,
+    pub planet_shape: PlanetShape,
+    // Only used by flat grids, spheres derive their size from the subdivisions
+    pub width: i32,
+    pub height: i32,
+    pub wrap: WrapMode,
+    // Ruleset asset the game is played with
+    pub rules: String,
+    pub variants: Variants,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            name: "Sandbox".to_string(),
+            planet_shape: PlanetShape::HexagonCap,
+            width: 8,
+            height: 8,
+            wrap: WrapMode::None,
+            rules: DEFAULT_RULESET.to_string(),
+            variants: Variants::default(),
+        }
+    }
 }
 
 pub struct Resource {
@@ -43,21 +147,185 @@ pub struct GameState {
     pub turn: i32,
 }
 
-#[derive(Default, Debug)]
+// Coordinates of the selected tiles, the last one is the primary selection
+#[derive(Default, Debug, PartialEq)]
 pub struct Selection {
-    pub coords: IVec2,
+    pub coords: Vec<IVec2>,
 }
 
-impl std::cmp::PartialEq for Selection {
-    fn ne(&self, other: &Self) -> bool {
-        self.coords.x == other.coords.x && self.coords.y == other.coords.y
+impl Selection {
+    pub fn primary(&self) -> Option<IVec2> {
+        self.coords.last().copied()
     }
 
-    fn eq(&self, other: &Self) -> bool {
-        self.coords.x == other.coords.x && self.coords.y == other.coords.y
+    pub fn contains(&self, coord: IVec2) -> bool {
+        self.coords.contains(&coord)
     }
 }
 
+// How the hex cursor was moved last
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CursorMode {
+    Mouse,
+    // Stepped between neighbors with a gamepad or the keyboard
+    Directional,
+}
+
+impl Default for CursorMode {
+    fn default() -> Self {
+        CursorMode::Mouse
+    }
+}
+
+// The tile the player points at, it is the hovered tile and the one Select acts on
+#[derive(Default, Debug)]
+pub struct HexCursor {
+    pub coord: Option<IVec2>,
+    pub mode: CursorMode,
+}
+
+// What a province can spend its treasury on
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Purchase {
+    Unit(UnitTier),
+    Castle,
+}
+
+// The purchase being dragged from the purchase panel onto the map
+#[derive(Default, Debug)]
+pub struct PendingPurchase {
+    pub item: Option<Purchase>,
+}
+
+// Legal targets of the selected unit, and the route it would walk to the hovered tile
+#[derive(Default, Debug)]
+pub struct MovePreview {
+    pub targets: Vec<IVec2>,
+    pub path: Vec<IVec2>,
+}
+
+// Tiles of the board before every move and purchase of the current turn, the latest last
+#[derive(Default)]
+pub struct UndoHistory {
+    pub boards: Vec<Vec<Option<TileState>>>,
+}
+
+// Set while the game waits for the player to confirm ending the turn with units that can still move
+#[derive(Default, Debug)]
+pub struct EndTurnPrompt {
+    pub unmoved_units: Option<usize>,
+}
+
+// Overlay bits of every cell, indexed like the HexGrid cells
+#[derive(Default)]
+pub struct MapOverlay {
+    pub cells: Vec<u32>,
+}
+
+// How much the team on turn knows of a tile
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TileVisibility {
+    Visible,
+    // Seen before, it shows as it was back then
+    Remembered,
+    Unexplored,
+}
+
+// What every team has seen of the board, and what the team on turn sees of it now
+#[derive(Default)]
+pub struct TeamVision {
+    pub memories: HashMap<i32, TeamMemory>,
+    // The team the cells are worked out for
+    pub team: i32,
+    // Indexed like the HexGrid cells
+    pub cells: Vec<TileVisibility>,
+}
+
+impl TeamVision {
+    pub fn visibility(&self, index: usize) -> TileVisibility {
+        self.cells
+            .get(index)
+            .copied()
+            .unwrap_or(TileVisibility::Unexplored)
+    }
+
+    pub fn sees(&self, index: usize) -> bool {
+        self.visibility(index) == TileVisibility::Visible
+    }
+
+    // The tile as the team last saw it
+    pub fn known_tile(&self, index: usize) -> Option<&TileState> {
+        self.memories.get(&self.team)?.tiles.get(index)?.as_ref()
+    }
+}
+
+// The teams that take turns, in order
+pub struct TurnOrder {
+    pub teams: Vec<i32>,
+}
+
+impl Default for TurnOrder {
+    fn default() -> Self {
+        Self { teams: vec![1, 2] }
+    }
+}
+
+impl TurnOrder {
+    pub fn team_for_turn(&self, turn: i32) -> i32 {
+        self.teams[turn as usize % self.teams.len()]
+    }
+}
+
+// events
+
+// The tile under the cursor changed
+pub struct HexHovered {
+    pub coord: Option<IVec2>,
+}
+
+pub struct HexClicked {
+    pub coord: IVec2,
+    // Add to or remove from the selection instead of replacing it
+    pub additive: bool,
+}
+
+pub struct SelectionChanged;
+
+pub struct MoveRequested {
+    pub from: IVec2,
+    pub to: IVec2,
+}
+
+pub struct UnitMoved {
+    pub outcome: MoveOutcome,
+}
+
+pub struct PurchaseRequested {
+    // Any tile of the province that pays
+    pub province: IVec2,
+    pub item: Purchase,
+    pub target: IVec2,
+}
+
+pub struct UnitPurchased {
+    pub outcome: PurchaseOutcome,
+}
+
+pub struct TurnStarted {
+    pub team: i32,
+}
+
+// A province could not pay the upkeep of its units, and they starved
+pub struct ProvinceBankrupt {
+    pub capital: IVec2,
+}
+
+// The current player is done, from the end turn button or action.
+// Unless confirmed, it asks first when units can still move
+pub struct EndTurnRequested {
+    pub confirmed: bool,
+}
+
 // tags
 
 pub struct SelectedTag;
@@ -78,24 +346,102 @@ pub type HexRaycastSource = bevy_mod_raycast::RayCastSource<HexRaycastLayer>;
 
 impl HexGrid {
     pub fn new(width: i32, height: i32) -> Self {
+        Self::new_wrapping(width, height, WrapMode::None)
+    }
+
+    pub fn new_wrapping(width: i32, height: i32, wrap: WrapMode) -> Self {
+        Self::from_shape(
+            width,
+            height,
+            GridShape::Flat(FlatHexTopology::new(width, height, wrap)),
+        )
+    }
+
+    pub fn new_sphere(subdivisions: u32) -> Self {
+        let sphere = GoldbergSphere::new(subdivisions);
+        Self::from_shape(
+            sphere.texture_width,
+            sphere.texture_height(),
+            GridShape::Sphere(sphere),
+        )
+    }
+
+    pub fn for_scenario(scenario: &Scenario) -> Self {
+        match scenario.planet_shape {
+            PlanetShape::HexagonCap => {
+                Self::new_wrapping(scenario.width, scenario.height, scenario.wrap)
+            }
+            PlanetShape::Sphere { subdivisions } => Self::new_sphere(subdivisions),
+        }
+    }
+
+    pub fn wrap_mode(&self) -> WrapMode {
+        match &self.shape {
+            GridShape::Flat(flat) => flat.wrap,
+            GridShape::Sphere(_) => WrapMode::None,
+        }
+    }
+
+    fn from_shape(width: i32, height: i32, shape: GridShape) -> Self {
         let mut cells = Vec::new();
         cells.resize((width * height) as usize, Entity::new(0));
         Self {
             width,
             height,
             cells,
+            shape,
         }
     }
 
-    pub fn coord_to_index(&self, coord: IVec2) -> usize {
-        (coord.y * self.width + coord.x) as usize
+    pub fn topology(&self) -> &dyn HexTopology {
+        match &self.shape {
+            GridShape::Flat(flat) => flat,
+            GridShape::Sphere(sphere) => sphere,
+        }
+    }
+
+    pub fn sphere(&self) -> Option<&GoldbergSphere> {
+        match &self.shape {
+            GridShape::Sphere(sphere) => Some(sphere),
+            _ => None,
+        }
     }
 
     pub fn coord_to_index_f32(&self, coord: Vec2) -> usize {
         (coord.y * self.width as f32 + coord.x) as usize
     }
+}
+
+impl HexTopology for HexGrid {
+    fn storage_len(&self) -> usize {
+        self.topology().storage_len()
+    }
+
+    fn contains(&self, coord: IVec2) -> bool {
+        self.topology().contains(coord)
+    }
+
+    fn coord_to_index(&self, coord: IVec2) -> usize {
+        self.topology().coord_to_index(coord)
+    }
+
+    fn index_to_coord(&self, index: usize) -> IVec2 {
+        self.topology().index_to_coord(index)
+    }
+
+    fn neighbors(&self, coord: IVec2) -> Vec<IVec2> {
+        self.topology().neighbors(coord)
+    }
+
+    fn edges(&self, coord: IVec2) -> Vec<Option<IVec2>> {
+        self.topology().edges(coord)
+    }
+
+    fn wrap(&self, coord: IVec2) -> IVec2 {
+        self.topology().wrap(coord)
+    }
 
-    pub fn index_to_coord(&self, index: usize) -> IVec2 {
-        IVec2::new(index as i32 % self.height, index as i32 / self.height)
+    fn distance(&self, a: IVec2, b: IVec2) -> i32 {
+        self.topology().distance(a, b)
     }
 }
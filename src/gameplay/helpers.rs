@@ -1,4 +1,8 @@
 use super::components::*;
+use super::rules::{Board, TileState};
+use super::ruleset::RuleSet;
+use super::topology::HexTopology;
+use crate::rendering;
 use bevy::prelude::*;
 
 pub fn update_grid_ids(
@@ -32,3 +36,137 @@ pub fn debug_print_grid(hex_grid: Res<HexGrid>) {
 
     println!("----DONE---------------------------------");
 }
+
+pub fn tile_state(
+    terrain: &TerrainType,
+    team: &Team,
+    occupant: Option<&Occupant>,
+    moved: Option<&MovedTag>,
+    treasury: Option<&Resource>,
+) -> TileState {
+    TileState {
+        terrain: *terrain,
+        team: team.number,
+        occupant: occupant.copied(),
+        moved: moved.is_some(),
+        treasury: treasury.map_or(0, |treasury| treasury.amount),
+    }
+}
+
+// Snapshot of all tiles in the world, for the rules to work on
+pub fn build_board<'a>(
+    grid: &'a HexGrid,
+    rules: &'a RuleSet,
+    tiles: &Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+) -> Board<'a> {
+    Board::from_tiles(
+        grid,
+        rules,
+        tiles.iter().map(|(position, terrain, team, occupant, moved, treasury)| {
+            (
+                position.position,
+                tile_state(terrain, team, occupant, moved, treasury),
+            )
+        }),
+    )
+}
+
+// Writes the rules' view of a tile back onto its entity
+pub fn write_tile(commands: &mut Commands, entity: Entity, tile: &TileState) {
+    let mut entity_commands = commands.entity(entity);
+    entity_commands.insert(Team { number: tile.team });
+    match tile.occupant {
+        Some(occupant) => entity_commands.insert(occupant),
+        None => entity_commands.remove::<Occupant>(),
+    };
+    if tile.moved {
+        entity_commands.insert(MovedTag);
+    } else {
+        entity_commands.remove::<MovedTag>();
+    }
+    if tile.occupant == Some(Occupant::Capital) {
+        entity_commands.insert(Resource {
+            amount: tile.treasury,
+        });
+    } else {
+        entity_commands.remove::<Resource>();
+    }
+}
+
+// Writes every tile of `after` that differs from `before`, both indexed like the board tiles
+pub fn write_changed_tiles(
+    commands: &mut Commands,
+    grid: &HexGrid,
+    before: &[Option<TileState>],
+    after: &[Option<TileState>],
+) {
+    for (index, (old, new)) in before.iter().zip(after.iter()).enumerate() {
+        if let Some(tile) = new {
+            if old.as_ref() != Some(tile) {
+                if let Some(entity) = tile_entity(grid, grid.index_to_coord(index)) {
+                    write_tile(commands, entity, tile);
+                }
+            }
+        }
+    }
+}
+
+pub fn tile_entity(grid: &HexGrid, coord: IVec2) -> Option<Entity> {
+    if grid.contains(coord) {
+        Some(grid.cells[grid.coord_to_index(coord)])
+    } else {
+        None
+    }
+}
+
+// World position of the center of the hex at `coord` on the surface of the planet
+pub fn hex_world_position(
+    grid: &HexGrid,
+    planets: &Query<(&Handle<Mesh>, &GlobalTransform, &Planet)>,
+    meshes: &Assets<Mesh>,
+    coord: IVec2,
+) -> Option<Vec3> {
+    // TODO: Assumption that there is only 1 planet
+    let (mesh, transform, planet) = planets.iter().next()?;
+    let surface = match (planet.shape, grid.sphere()) {
+        (PlanetShape::Sphere { .. }, Some(sphere)) => {
+            rendering::helpers::sphere_surface_transform(sphere, transform, coord, 1.0)
+        }
+        _ => rendering::helpers::hex_surface_transform(meshes.get(mesh)?, transform, coord, 1.0),
+    };
+    surface.map(|surface| surface.translation)
+}
+
+// The neighbor whose offset from `from` points the most along `direction`, None if they all point away from it.
+// Only the part of the offsets across the ground counts, so the up and down of a curved planet doesn't matter.
+pub fn neighbor_in_direction(
+    from: Vec3,
+    neighbors: &[(IVec2, Vec3)],
+    direction: Vec3,
+) -> Option<IVec2> {
+    let direction = Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+    neighbors
+        .iter()
+        .filter_map(|(coord, position)| {
+            let offset = *position - from;
+            let offset = Vec3::new(offset.x, 0.0, offset.z).normalize_or_zero();
+            let alignment = offset.dot(direction);
+            if alignment > 0.0 {
+                Some((*coord, alignment))
+            } else {
+                None
+            }
+        })
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(coord, _)| coord)
+}
@@ -0,0 +1,378 @@
+// Fills a board with the tiles a game starts with, the fixed sandbox layout or a randomly generated map
+use super::components::{HexGrid, Occupant, TerrainType, TreeKind, UnitTier};
+use super::rules::{self, Board, TileState};
+use super::ruleset::{RuleSet, StartingPositions};
+use super::topology::{flood_fill, HexTopology};
+use bevy::math::IVec2;
+use rand::Rng;
+use std::collections::HashSet;
+
+// Chance that a land tile of a generated map starts with a tree
+const TREE_CHANCE: f64 = 0.1;
+
+// Terrain generated maps scatter over the land, and along the coast
+const LAND_FEATURES: [TerrainType; 4] = [
+    TerrainType::Mountain,
+    TerrainType::Forest,
+    TerrainType::Plains,
+    TerrainType::Desert,
+];
+const COAST_FEATURES: [TerrainType; 2] = [TerrainType::Shallows, TerrainType::Bridge];
+
+// The sandbox planet, two teams splitting the land with a few occupants.
+// Unless a starting positions variant deals the land out again
+pub fn fill_sandbox(board: &mut Board, grid: &HexGrid, rng: &mut impl Rng) {
+    for coord in grid.coords() {
+        board.set_tile(coord, sandbox_tile(grid, board.rules, coord));
+    }
+    if let Some(positions) = board.rules.variants.starting_positions {
+        deal_teams(board, 2, positions, rng);
+        found_capitals(board);
+    }
+}
+
+fn sandbox_tile(grid: &HexGrid, rules: &RuleSet, coord: IVec2) -> TileState {
+    let terrain = sandbox_terrain(grid, coord);
+    let occupant = sandbox_occupant(coord);
+    TileState {
+        terrain,
+        team: sandbox_team(grid, rules, coord, terrain),
+        occupant,
+        moved: false,
+        treasury: if occupant == Some(Occupant::Capital) {
+            rules.starting_treasury
+        } else {
+            0
+        },
+    }
+}
+
+fn sandbox_terrain(grid: &HexGrid, coord: IVec2) -> TerrainType {
+    let is_water = match grid.sphere() {
+        Some(sphere) => {
+            let center = sphere.centers[sphere.coord_to_index(coord)];
+            (center.x * 3.0).sin() + (center.z * 2.0).cos() < -0.6
+        }
+        None => coord.x < 3 || coord.y < 3,
+    };
+    // A few tiles of every other terrain on the flat map, to try them out
+    let feature = match (grid.sphere(), coord.x, coord.y) {
+        (Some(_), _, _) => None,
+        (None, 2, 4) | (None, 2, 6) => Some(TerrainType::Shallows),
+        (None, 4, 2) => Some(TerrainType::Bridge),
+        (None, 5, 7) | (None, 4, 8) => Some(TerrainType::Forest),
+        (None, 8, 4) | (None, 9, 5) => Some(TerrainType::Plains),
+        (None, 10, 4) => Some(TerrainType::Desert),
+        (None, 8, 8) => Some(TerrainType::Mountain),
+        _ => None,
+    };
+    match feature {
+        Some(terrain) => terrain,
+        None if is_water => TerrainType::Water,
+        None => TerrainType::Land,
+    }
+}
+
+// Water, and whatever else units can't walk on, belongs to nobody
+fn sandbox_team(grid: &HexGrid, rules: &RuleSet, coord: IVec2, terrain: TerrainType) -> i32 {
+    if !rules.passable(terrain) {
+        return 0;
+    }
+    let west = match grid.sphere() {
+        Some(sphere) => sphere.centers[sphere.coord_to_index(coord)].x < 0.0,
+        None => coord.x < 6,
+    };
+    if west {
+        1
+    } else {
+        2
+    }
+}
+
+fn sandbox_occupant(coord: IVec2) -> Option<Occupant> {
+    match (coord.x, coord.y) {
+        (4, 4) => Some(Occupant::Capital),
+        (3, 5) => Some(Occupant::Unit(UnitTier::Peasant)),
+        (5, 5) => Some(Occupant::Castle),
+        (7, 5) => Some(Occupant::Unit(UnitTier::Spearman)),
+        (3, 7) => Some(Occupant::Tree(TreeKind::Pine)),
+        (6, 6) => Some(Occupant::Grave),
+        (7, 7) => Some(Occupant::Capital),
+        _ => None,
+    }
+}
+
+// Fills the board with a single landmass covering about `land` of the map, with the terrain of the ruleset scattered over it.
+// Its tiles are dealt out between teams 1 to `teams`, at random unless a variant says otherwise.
+// Every province gets a capital
+pub fn generate(board: &mut Board, teams: i32, land: f64, rng: &mut impl Rng) {
+    let topology = board.topology;
+    let coords = topology.coords();
+
+    let mut is_land: HashSet<IVec2> = coords
+        .iter()
+        .copied()
+        .filter(|_| rng.gen_bool(land.clamp(0.0, 1.0)))
+        .collect();
+    // Smooth the noise into coastlines, a tile follows the majority of its neighbors
+    is_land = coords
+        .iter()
+        .copied()
+        .filter(|coord| {
+            let neighbors = topology.neighbors(*coord);
+            let land_neighbors = neighbors.iter().filter(|n| is_land.contains(n)).count();
+            match (land_neighbors * 2).cmp(&neighbors.len()) {
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Equal => is_land.contains(coord),
+                std::cmp::Ordering::Less => false,
+            }
+        })
+        .collect();
+    // Islands can never be reached, only the largest landmass is kept
+    let mut largest = Vec::new();
+    let mut visited = HashSet::new();
+    for coord in coords.iter() {
+        if !is_land.contains(coord) || visited.contains(coord) {
+            continue;
+        }
+        let landmass = flood_fill(topology, *coord, |c| is_land.contains(&c));
+        visited.extend(landmass.iter().copied());
+        if landmass.len() > largest.len() {
+            largest = landmass;
+        }
+    }
+    let is_land: HashSet<IVec2> = largest.into_iter().collect();
+
+    for coord in coords {
+        let terrain = if is_land.contains(&coord) {
+            TerrainType::Land
+        } else {
+            TerrainType::Water
+        };
+        board.set_tile(
+            coord,
+            TileState {
+                terrain,
+                team: 0,
+                occupant: None,
+                moved: false,
+                treasury: 0,
+            },
+        );
+    }
+    scatter_terrain(board, rng);
+
+    let tree_chance = if board.rules.variants.no_trees {
+        0.0
+    } else {
+        TREE_CHANCE
+    };
+    for coord in board.coords() {
+        let tile = board.tile(coord).unwrap();
+        if rules::is_land(board.rules, tile)
+            && board.rules.terrain(tile.terrain).trees
+            && rng.gen_bool(tree_chance)
+        {
+            board.tile_mut(coord).unwrap().occupant = Some(Occupant::Tree(TreeKind::Pine));
+        }
+    }
+
+    let positions = board
+        .rules
+        .variants
+        .starting_positions
+        .unwrap_or(StartingPositions::Random);
+    deal_teams(board, teams, positions, rng);
+    found_capitals(board);
+}
+
+// Gives land tiles other terrain, and water along the coast shallows and bridges, as often as the ruleset says.
+// Terrain that units can't cross is left out where it would cut the land in two
+fn scatter_terrain(board: &mut Board, rng: &mut impl Rng) {
+    let coastal_water: HashSet<IVec2> = board
+        .coords()
+        .into_iter()
+        .filter(|coord| {
+            board.tile(*coord).unwrap().terrain == TerrainType::Water
+                && board
+                    .topology
+                    .neighbors(*coord)
+                    .into_iter()
+                    .filter_map(|neighbor| board.tile(neighbor))
+                    .any(|neighbor| rules::is_land(board.rules, neighbor))
+        })
+        .collect();
+    for coord in board.coords() {
+        let features: &[TerrainType] = match board.tile(coord).unwrap().terrain {
+            TerrainType::Land => &LAND_FEATURES,
+            TerrainType::Water if coastal_water.contains(&coord) => &COAST_FEATURES,
+            _ => continue,
+        };
+        // The frequencies take turns on a single roll, so they add up to the share of changed tiles
+        let roll: f64 = rng.gen();
+        let mut threshold = 0.0;
+        let feature = features.iter().copied().find(|feature| {
+            threshold += board.rules.terrain(*feature).frequency;
+            roll < threshold
+        });
+        let feature = match feature {
+            Some(feature) => feature,
+            None => continue,
+        };
+        let previous = board.tile(coord).unwrap().terrain;
+        board.tile_mut(coord).unwrap().terrain = feature;
+        if !board.rules.passable(feature) && !land_connected(board) {
+            board.tile_mut(coord).unwrap().terrain = previous;
+        }
+    }
+}
+
+// Whether units can walk from any land tile to any other
+fn land_connected(board: &Board) -> bool {
+    let land: Vec<IVec2> = board
+        .coords()
+        .into_iter()
+        .filter(|coord| rules::is_land(board.rules, board.tile(*coord).unwrap()))
+        .collect();
+    match land.first() {
+        Some(start) => {
+            let reached = flood_fill(board.topology, *start, |coord| {
+                board
+                    .tile(coord)
+                    .map_or(false, |tile| rules::is_land(board.rules, tile))
+            });
+            reached.len() == land.len()
+        }
+        None => true,
+    }
+}
+
+// Hands every land tile of the board to one of the teams 1 to `teams`
+fn deal_teams(board: &mut Board, teams: i32, positions: StartingPositions, rng: &mut impl Rng) {
+    let teams = teams.max(1);
+    let land: Vec<IVec2> = board
+        .coords()
+        .into_iter()
+        .filter(|coord| rules::is_land(board.rules, board.tile(*coord).unwrap()))
+        .collect();
+    match positions {
+        StartingPositions::Random => {
+            for coord in land {
+                board.tile_mut(coord).unwrap().team = rng.gen_range(1..=teams);
+            }
+        }
+        // Every team grows out from its own random tile, one step at a time, until the land is taken
+        StartingPositions::Clustered => {
+            let mut unclaimed: HashSet<IVec2> = land.iter().copied().collect();
+            let mut fronts: Vec<Vec<IVec2>> = Vec::new();
+            for team in 1..=teams {
+                // Picked from `land` rather than the set, so a seed always deals the same map
+                let seeds: Vec<IVec2> = land
+                    .iter()
+                    .copied()
+                    .filter(|coord| unclaimed.contains(coord))
+                    .collect();
+                if seeds.is_empty() {
+                    break;
+                }
+                let seed = seeds[rng.gen_range(0..seeds.len())];
+                unclaimed.remove(&seed);
+                board.tile_mut(seed).unwrap().team = team;
+                fronts.push(vec![seed]);
+            }
+            while fronts.iter().any(|front| !front.is_empty()) {
+                for (index, front) in fronts.iter_mut().enumerate() {
+                    let mut next = Vec::new();
+                    for coord in front.drain(..) {
+                        for neighbor in board.topology.neighbors(coord) {
+                            if unclaimed.remove(&neighbor) {
+                                board.tile_mut(neighbor).unwrap().team = index as i32 + 1;
+                                next.push(neighbor);
+                            }
+                        }
+                    }
+                    *front = next;
+                }
+            }
+            // Land no team could reach goes to anyone
+            for coord in land.into_iter().filter(|coord| unclaimed.contains(coord)) {
+                board.tile_mut(coord).unwrap().team = rng.gen_range(1..=teams);
+            }
+        }
+    }
+}
+
+// Capitals for every province, holding the starting treasury
+fn found_capitals(board: &mut Board) {
+    rules::update_capitals(board);
+    let starting_treasury = board.rules.starting_treasury;
+    for tile in board.tiles.iter_mut().flatten() {
+        if tile.occupant == Some(Occupant::Capital) {
+            tile.treasury = starting_treasury;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::ruleset::{load_default_ruleset, Variants};
+    use crate::gameplay::topology::{FlatHexTopology, WrapMode};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Number of provinces every team starts with on an all land map
+    fn provinces_per_team(positions: StartingPositions) -> Vec<usize> {
+        let topology = FlatHexTopology::new(16, 16, WrapMode::None);
+        let rules = load_default_ruleset()
+            .with_variants(&Variants {
+                starting_positions: Some(positions),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut board = Board::new(&topology, &rules);
+        generate(&mut board, 3, 1.0, &mut StdRng::seed_from_u64(7));
+
+        let mut counts = vec![0; 3];
+        let mut visited = HashSet::new();
+        for coord in board.coords() {
+            if !visited.contains(&coord) && rules::is_land(&rules, board.tile(coord).unwrap()) {
+                let province = rules::province(&board, coord);
+                visited.extend(province.iter().copied());
+                counts[(board.tile(coord).unwrap().team - 1) as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn clustered_teams_start_in_one_piece() {
+        assert_eq!(
+            provinces_per_team(StartingPositions::Clustered),
+            vec![1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn impassable_terrain_never_cuts_the_land() {
+        let topology = FlatHexTopology::new(16, 16, WrapMode::None);
+        let mut rules = load_default_ruleset();
+        let mountain = rules.terrain.get_mut(&TerrainType::Mountain).unwrap();
+        mountain.frequency = 0.5;
+        let mut board = Board::new(&topology, &rules);
+        generate(&mut board, 2, 0.8, &mut StdRng::seed_from_u64(3));
+        assert!(board
+            .tiles
+            .iter()
+            .flatten()
+            .any(|tile| tile.terrain == TerrainType::Mountain));
+        assert!(land_connected(&board));
+    }
+
+    #[test]
+    fn random_teams_start_scattered() {
+        assert!(provinces_per_team(StartingPositions::Random)
+            .iter()
+            .all(|count| *count > 1));
+    }
+}
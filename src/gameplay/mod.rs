@@ -1,32 +1,125 @@
 use bevy::prelude::*;
 
+pub mod ai;
 pub mod components;
 pub mod helpers;
+pub mod mapgen;
+pub mod rules;
+pub mod ruleset;
 pub mod systems;
+pub mod topology;
 
+use crate::app_state::{run_if_in_game, AppScreen};
 use bevy_mod_raycast::RaycastSystem;
 
 pub struct GamePlayPlugins;
 
 impl Plugin for GamePlayPlugins {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_to_stage(
-            CoreStage::PostUpdate,
-            systems::update_raycast_with_cursor
-                .system()
-                .before(RaycastSystem::BuildRays),
-        )
-        .add_system(systems::update_mouse_hovering_and_selected.system())
-        .add_system(
-            systems::deselection_system
-                .system()
-                .label("deselection_system"),
-        )
-        .add_system(
-            systems::selection_system
-                .system()
-                .after("deselection_system"),
-        )
-        .add_state(components::GameState::default());
+        app.add_event::<components::HexHovered>()
+            .add_event::<components::HexClicked>()
+            .add_event::<components::SelectionChanged>()
+            .add_event::<components::MoveRequested>()
+            .add_event::<components::UnitMoved>()
+            .add_event::<components::PurchaseRequested>()
+            .add_event::<components::UnitPurchased>()
+            .add_event::<components::EndTurnRequested>()
+            .add_event::<components::TurnStarted>()
+            .add_event::<components::ProvinceBankrupt>()
+            .add_asset::<ruleset::RuleSet>()
+            .init_asset_loader::<ruleset::RuleSetLoader>()
+            .insert_resource(ruleset::load_default_ruleset())
+            .init_resource::<ruleset::ActiveRuleSet>()
+            .init_resource::<components::MovePreview>()
+            .init_resource::<components::MapOverlay>()
+            .init_resource::<components::TeamVision>()
+            .init_resource::<components::TurnOrder>()
+            .init_resource::<components::PendingPurchase>()
+            .init_resource::<components::UndoHistory>()
+            .init_resource::<components::HexCursor>()
+            .init_resource::<components::EndTurnPrompt>()
+            .add_system_set(
+                SystemSet::on_enter(AppScreen::InGame).with_system(
+                    ruleset::load_scenario_ruleset
+                        .system()
+                        .label("load_ruleset"),
+                ),
+            )
+            .add_system(ruleset::reload_ruleset.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                systems::update_raycast_with_cursor
+                    .system()
+                    .before(RaycastSystem::BuildRays),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_in_game.system())
+                    .with_system(helpers::update_grid_ids.system())
+                    .with_system(
+                        systems::directional_cursor_system
+                            .system()
+                            .before("hex_picking"),
+                    )
+                    .with_system(systems::hex_picking_system.system().label("hex_picking"))
+                    .with_system(systems::hover_system.system().after("hex_picking"))
+                    .with_system(
+                        systems::selection_system
+                            .system()
+                            .label("selection_system")
+                            .after("hex_picking"),
+                    )
+                    .with_system(
+                        systems::update_selected_tags
+                            .system()
+                            .after("selection_system"),
+                    )
+                    .with_system(
+                        systems::update_move_preview
+                            .system()
+                            .after("selection_system")
+                            .before("execute_moves"),
+                    )
+                    .with_system(
+                        systems::execute_moves
+                            .system()
+                            .label("execute_moves")
+                            .after("selection_system"),
+                    )
+                    .with_system(
+                        systems::purchase_drop_system
+                            .system()
+                            .label("purchase_drop")
+                            .after("hex_picking"),
+                    )
+                    .with_system(
+                        systems::execute_purchases
+                            .system()
+                            .label("execute_purchases")
+                            .after("purchase_drop"),
+                    )
+                    .with_system(systems::update_team_vision.system().label("team_vision"))
+                    .with_system(
+                        systems::update_map_overlay
+                            .system()
+                            .after("execute_moves")
+                            .after("team_vision"),
+                    )
+                    .with_system(
+                        systems::double_click_focus_system
+                            .system()
+                            .after("hex_picking"),
+                    )
+                    .with_system(systems::focus_capital_system.system())
+                    .with_system(systems::camera_bookmark_system.system())
+                    .with_system(systems::player_camera_system.system())
+                    .with_system(systems::start_turn_system.system())
+                    .with_system(systems::end_turn_system.system())
+                    .with_system(systems::cancel_system.system())
+                    .with_system(systems::undo_system.system())
+                    .with_system(systems::cycle_units_system.system())
+                    .with_system(systems::check_game_over.system()),
+            )
+            .add_state(components::GameState::default());
     }
 }
@@ -0,0 +1,1228 @@
+use super::components::{Occupant, Purchase, TerrainType, TreeKind, UnitTier};
+use super::ruleset::{Bankruptcy, RuleSet};
+use super::topology::{flood_fill, HexTopology};
+use bevy::math::IVec2;
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Snapshot of a single tile, the rules work on these instead of on the ECS so they can run anywhere
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileState {
+    pub terrain: TerrainType,
+    pub team: i32,
+    pub occupant: Option<Occupant>,
+    pub moved: bool,
+    // Money of the province, kept on its capital
+    pub treasury: i32,
+}
+
+pub struct Board<'a> {
+    pub topology: &'a dyn HexTopology,
+    // Every number the rules need comes from here
+    pub rules: &'a RuleSet,
+    // Indexed by HexTopology::coord_to_index
+    pub tiles: Vec<Option<TileState>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveOutcome {
+    pub from: IVec2,
+    pub to: IVec2,
+    // Occupant that stood on the target tile before the move
+    pub replaced: Option<Occupant>,
+    // Team that owned the target tile before the move, if it was not ours
+    pub captured_from: Option<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PurchaseOutcome {
+    pub item: Purchase,
+    // Capital that paid for it
+    pub capital: IVec2,
+    pub target: IVec2,
+    pub cost: i32,
+    pub replaced: Option<Occupant>,
+    pub captured_from: Option<i32>,
+}
+
+// What happened while a turn started
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TurnReport {
+    // Capitals of the provinces that could not pay their upkeep
+    pub bankrupt: Vec<IVec2>,
+}
+
+impl<'a> Board<'a> {
+    pub fn new(topology: &'a dyn HexTopology, rules: &'a RuleSet) -> Self {
+        Self {
+            topology,
+            rules,
+            tiles: vec![None; topology.storage_len()],
+        }
+    }
+
+    pub fn from_tiles(
+        topology: &'a dyn HexTopology,
+        rules: &'a RuleSet,
+        tiles: impl Iterator<Item = (IVec2, TileState)>,
+    ) -> Self {
+        let mut board = Self::new(topology, rules);
+        for (coord, tile) in tiles {
+            board.set_tile(coord, tile);
+        }
+        board
+    }
+
+    pub fn tile(&self, coord: IVec2) -> Option<&TileState> {
+        if !self.topology.contains(coord) {
+            return None;
+        }
+        self.tiles[self.topology.coord_to_index(coord)].as_ref()
+    }
+
+    pub fn tile_mut(&mut self, coord: IVec2) -> Option<&mut TileState> {
+        if !self.topology.contains(coord) {
+            return None;
+        }
+        let index = self.topology.coord_to_index(coord);
+        self.tiles[index].as_mut()
+    }
+
+    pub fn set_tile(&mut self, coord: IVec2, tile: TileState) {
+        if self.topology.contains(coord) {
+            let index = self.topology.coord_to_index(coord);
+            self.tiles[index] = Some(tile);
+        }
+    }
+
+    pub fn coords(&self) -> Vec<IVec2> {
+        self.topology
+            .coords()
+            .into_iter()
+            .filter(|coord| self.tile(*coord).is_some())
+            .collect()
+    }
+}
+
+// Tiles of passable terrain belong to provinces, the rest belongs to nobody
+pub fn is_land(rules: &RuleSet, tile: &TileState) -> bool {
+    rules.passable(tile.terrain)
+}
+
+// Connected land of the same team as the tile at `coord`
+pub fn province(board: &Board, coord: IVec2) -> Vec<IVec2> {
+    let team = match board.tile(coord) {
+        Some(tile) if is_land(board.rules, tile) => tile.team,
+        _ => return Vec::new(),
+    };
+    flood_fill(board.topology, coord, |c| {
+        board.tile(c).map_or(false, |tile| {
+            is_land(board.rules, tile) && tile.team == team
+        })
+    })
+}
+
+pub fn province_capital(board: &Board, province: &[IVec2]) -> Option<IVec2> {
+    province
+        .iter()
+        .copied()
+        .find(|coord| board.tile(*coord).and_then(|tile| tile.occupant) == Some(Occupant::Capital))
+}
+
+pub fn province_treasury(board: &Board, province: &[IVec2]) -> Option<i32> {
+    province_capital(board, province).and_then(|capital| board.tile(capital).map(|tile| tile.treasury))
+}
+
+// Every tile of the province earns its income each turn, tiles with a tree earn the tree income instead
+pub fn province_income(board: &Board, province: &[IVec2]) -> i32 {
+    province
+        .iter()
+        .filter_map(|coord| board.tile(*coord))
+        .map(|tile| tile_income(board.rules, tile))
+        .sum()
+}
+
+fn tile_income(rules: &RuleSet, tile: &TileState) -> i32 {
+    match tile.occupant {
+        Some(Occupant::Tree(_)) => rules.income.per_tree,
+        _ if rules.variants.terrain_income => {
+            rules.income.terrain.get(&tile.terrain).copied().unwrap_or(0)
+        }
+        _ => (rules.income.per_tile + rules.terrain(tile.terrain).income).max(0),
+    }
+}
+
+pub fn province_upkeep(board: &Board, province: &[IVec2]) -> i32 {
+    province
+        .iter()
+        .filter_map(|coord| board.tile(*coord))
+        .filter_map(|tile| match tile.occupant {
+            Some(Occupant::Unit(tier)) => Some(board.rules.unit(tier).upkeep),
+            _ => None,
+        })
+        .sum()
+}
+
+// Strength an attacker has to exceed to take the tile, its terrain adds to the strongest protector
+pub fn defense(board: &Board, coord: IVec2) -> i32 {
+    let tile = match board.tile(coord) {
+        Some(tile) => tile,
+        None => return 0,
+    };
+    let own = tile
+        .occupant
+        .map_or(0, |occupant| board.rules.occupant_strength(occupant));
+    board
+        .topology
+        .neighbors(coord)
+        .into_iter()
+        .filter_map(|neighbor| board.tile(neighbor))
+        .filter(|neighbor| is_land(board.rules, neighbor) && neighbor.team == tile.team)
+        .filter_map(|neighbor| neighbor.occupant)
+        .map(|occupant| board.rules.occupant_strength(occupant))
+        .fold(own, i32::max)
+        + board.rules.terrain(tile.terrain).defense
+}
+
+// All tiles the unit at `from` is allowed to move to this turn
+pub fn legal_moves(board: &Board, from: IVec2) -> Vec<IVec2> {
+    match board.tile(from) {
+        Some(tile) if !tile.moved => {
+            let mut targets = reachable_tiles(board, from);
+            targets.extend(capture_targets(board, from));
+            targets
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Whether a unit of `tier` may step onto a tile of its own province holding `occupant`
+fn accepts_unit(rules: &RuleSet, occupant: Option<Occupant>, tier: UnitTier) -> bool {
+    match occupant {
+        None | Some(Occupant::Tree(_)) | Some(Occupant::Grave) => true,
+        Some(Occupant::Unit(other)) => rules.merged_tier(tier, other).is_some(),
+        Some(Occupant::Castle) | Some(Occupant::Capital) => false,
+    }
+}
+
+// Tiles inside the province of the unit at `from` that are free, or hold a unit to merge with
+pub fn reachable_tiles(board: &Board, from: IVec2) -> Vec<IVec2> {
+    let tier = match board.tile(from).and_then(|tile| tile.occupant) {
+        Some(Occupant::Unit(tier)) => tier,
+        _ => return Vec::new(),
+    };
+
+    province(board, from)
+        .into_iter()
+        .filter(|coord| *coord != from)
+        .filter(|coord| {
+            accepts_unit(
+                board.rules,
+                board.tile(*coord).and_then(|tile| tile.occupant),
+                tier,
+            )
+        })
+        .collect()
+}
+
+// Bordering land of other teams that is weaker than the unit at `from`, whether or not it already moved
+pub fn capture_targets(board: &Board, from: IVec2) -> Vec<IVec2> {
+    match board.tile(from) {
+        Some(TileState {
+            occupant: Some(Occupant::Unit(tier)),
+            team,
+            ..
+        }) => border_captures(
+            board,
+            &province(board, from),
+            *team,
+            board.rules.unit(*tier).strength,
+        ),
+        _ => Vec::new(),
+    }
+}
+
+// Land of other teams along the border of `own_province` that `strength` is enough to take
+fn border_captures(board: &Board, own_province: &[IVec2], team: i32, strength: i32) -> Vec<IVec2> {
+    let own_set: HashSet<IVec2> = own_province.iter().copied().collect();
+    let mut border = HashSet::new();
+    let mut targets = Vec::new();
+    for coord in own_province.iter() {
+        for neighbor in board.topology.neighbors(*coord) {
+            if own_set.contains(&neighbor) || !border.insert(neighbor) {
+                continue;
+            }
+            if let Some(tile) = board.tile(neighbor) {
+                if is_land(board.rules, tile)
+                    && tile.team != team
+                    && defense(board, neighbor) < strength
+                {
+                    targets.push(neighbor);
+                }
+            }
+        }
+    }
+    targets
+}
+
+// Tiles of `team` that some enemy unit could take on its next move
+pub fn threatened_tiles(board: &Board, team: i32) -> Vec<IVec2> {
+    let mut threatened = HashSet::new();
+    for coord in board.coords() {
+        let tile = board.tile(coord).unwrap();
+        if tile.team == team || !matches!(tile.occupant, Some(Occupant::Unit(_))) {
+            continue;
+        }
+        threatened.extend(
+            capture_targets(board, coord)
+                .into_iter()
+                .filter(|target| board.tile(*target).map_or(false, |tile| tile.team == team)),
+        );
+    }
+    threatened.into_iter().collect()
+}
+
+// Route a unit walks to its target, through its own province
+pub fn move_path(board: &Board, from: IVec2, to: IVec2) -> Option<Vec<IVec2>> {
+    let team = board.tile(from)?.team;
+    let walkable = |coord: IVec2| {
+        board.tile(coord).map_or(false, |tile| {
+            is_land(board.rules, tile) && tile.team == team
+        })
+    };
+
+    let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+    let mut queue = VecDeque::new();
+    came_from.insert(from, from);
+    queue.push_back(from);
+    while let Some(coord) = queue.pop_front() {
+        if coord == to {
+            let mut path = vec![to];
+            let mut current = to;
+            while current != from {
+                current = came_from[&current];
+                path.push(current);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        // The target may lie just outside the province, but we never walk through enemy land
+        if coord != from && !walkable(coord) {
+            continue;
+        }
+        for neighbor in board.topology.neighbors(coord) {
+            if !came_from.contains_key(&neighbor) && (walkable(neighbor) || neighbor == to) {
+                came_from.insert(neighbor, coord);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    None
+}
+
+// Moves the unit at `from` to `to`, returns None if the move is not legal
+pub fn apply_move(board: &mut Board, from: IVec2, to: IVec2) -> Option<MoveOutcome> {
+    if !legal_moves(board, from).contains(&to) {
+        return None;
+    }
+
+    let mover = board.tile(from)?.clone();
+    let tier = match mover.occupant {
+        Some(Occupant::Unit(tier)) => tier,
+        _ => return None,
+    };
+    let target = board.tile(to)?.clone();
+    let captured_from = if target.team != mover.team {
+        Some(target.team)
+    } else {
+        None
+    };
+
+    let (occupant, moved) = match target.occupant {
+        Some(Occupant::Unit(other)) if captured_from.is_none() => (
+            Occupant::Unit(board.rules.merged_tier(tier, other)?),
+            target.moved,
+        ),
+        // Clearing trees and graves, or conquering land, takes the rest of the turn
+        Some(_) => (Occupant::Unit(tier), true),
+        None => (Occupant::Unit(tier), captured_from.is_some()),
+    };
+    // And so does rough terrain
+    let moved = moved || board.rules.terrain(target.terrain).ends_move;
+
+    board.tile_mut(from)?.occupant = None;
+    let target_tile = board.tile_mut(to)?;
+    target_tile.team = mover.team;
+    target_tile.occupant = Some(occupant);
+    target_tile.moved = moved;
+    // A captured capital takes the money of its province with it
+    target_tile.treasury = 0;
+
+    Some(MoveOutcome {
+        from,
+        to,
+        replaced: target.occupant,
+        captured_from,
+    })
+}
+
+// Tiles where the province at `coord` can place `item`, empty if it can't afford it
+pub fn placement_targets(board: &Board, coord: IVec2, item: Purchase) -> Vec<IVec2> {
+    let team = match board.tile(coord) {
+        Some(tile) if is_land(board.rules, tile) => tile.team,
+        _ => return Vec::new(),
+    };
+    let own_province = province(board, coord);
+    match province_treasury(board, &own_province) {
+        Some(treasury) if treasury >= board.rules.purchase_cost(item) => {}
+        _ => return Vec::new(),
+    }
+
+    let inside = own_province.iter().copied().filter(|target| {
+        let occupant = board.tile(*target).and_then(|tile| tile.occupant);
+        match item {
+            Purchase::Unit(tier) => accepts_unit(board.rules, occupant, tier),
+            Purchase::Castle => occupant.is_none(),
+        }
+    });
+    match item {
+        Purchase::Unit(tier) => inside
+            .chain(border_captures(
+                board,
+                &own_province,
+                team,
+                board.rules.unit(tier).strength,
+            ))
+            .collect(),
+        Purchase::Castle => inside.collect(),
+    }
+}
+
+// Buys `item` with the money of the province at `coord` and places it on `target`, returns None if that's not allowed
+pub fn apply_purchase(
+    board: &mut Board,
+    coord: IVec2,
+    item: Purchase,
+    target: IVec2,
+) -> Option<PurchaseOutcome> {
+    if !placement_targets(board, coord, item).contains(&target) {
+        return None;
+    }
+    let team = board.tile(coord)?.team;
+    let capital = province_capital(board, &province(board, coord))?;
+    let cost = board.rules.purchase_cost(item);
+    let previous = board.tile(target)?.clone();
+    let captured_from = if previous.team != team {
+        Some(previous.team)
+    } else {
+        None
+    };
+
+    let (occupant, moved) = match (item, previous.occupant) {
+        (Purchase::Unit(tier), Some(Occupant::Unit(other))) if captured_from.is_none() => (
+            Occupant::Unit(board.rules.merged_tier(tier, other)?),
+            previous.moved,
+        ),
+        // Like a move, clearing or conquering a tile uses up the turn of the new unit
+        (Purchase::Unit(tier), Some(_)) => (Occupant::Unit(tier), true),
+        (Purchase::Unit(tier), None) => (Occupant::Unit(tier), captured_from.is_some()),
+        (Purchase::Castle, _) => (Occupant::Castle, false),
+    };
+
+    board.tile_mut(capital)?.treasury -= cost;
+    let target_tile = board.tile_mut(target)?;
+    target_tile.team = team;
+    target_tile.occupant = Some(occupant);
+    target_tile.moved = moved;
+    target_tile.treasury = 0;
+
+    Some(PurchaseOutcome {
+        item,
+        capital,
+        target,
+        cost,
+        replaced: previous.occupant,
+        captured_from,
+    })
+}
+
+// How a finished game ended
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GameOver {
+    // None when sudden death ends in a tie
+    pub winner: Option<i32>,
+}
+
+// Whether the game is over once `rounds` rounds have been played, every team moving once per round
+pub fn game_over(board: &Board, rounds: i32) -> Option<GameOver> {
+    if let Some(winner) = winner(board) {
+        return Some(GameOver {
+            winner: Some(winner),
+        });
+    }
+    match board.rules.variants.sudden_death {
+        Some(limit) if rounds >= limit => Some(GameOver {
+            winner: largest_team(board),
+        }),
+        _ => None,
+    }
+}
+
+// The team with the most land, None on a tie
+fn largest_team(board: &Board) -> Option<i32> {
+    let mut land: HashMap<i32, usize> = HashMap::new();
+    for tile in board.tiles.iter().flatten() {
+        if is_land(board.rules, tile) {
+            *land.entry(tile.team).or_insert(0) += 1;
+        }
+    }
+    let most = land.values().copied().max()?;
+    let mut leaders = land.into_iter().filter(|(_, tiles)| *tiles == most);
+    match (leaders.next(), leaders.next()) {
+        (Some((team, _)), None) => Some(team),
+        _ => None,
+    }
+}
+
+// The last team that still has a province, None while at least two teams are in the game.
+// A province that lost its capital gets a new one when the next turn starts, so it still counts
+pub fn winner(board: &Board) -> Option<i32> {
+    let mut teams = HashSet::new();
+    let mut visited = HashSet::new();
+    for coord in board.coords() {
+        if visited.contains(&coord) {
+            continue;
+        }
+        let tiles = province(board, coord);
+        visited.extend(tiles.iter().copied());
+        if tiles.len() >= 2 || province_capital(board, &tiles).is_some() {
+            teams.insert(board.tile(coord).unwrap().team);
+        }
+    }
+    if teams.len() == 1 {
+        teams.into_iter().next()
+    } else {
+        None
+    }
+}
+
+// Every province of two or more tiles gets exactly one capital, a lone tile can't keep one
+pub fn update_capitals(board: &mut Board) {
+    let mut visited = HashSet::new();
+    for coord in board.coords() {
+        if visited.contains(&coord) {
+            continue;
+        }
+        let tiles = province(board, coord);
+        visited.extend(tiles.iter().copied());
+        let capitals: Vec<IVec2> = tiles
+            .iter()
+            .copied()
+            .filter(|c| board.tile(*c).and_then(|tile| tile.occupant) == Some(Occupant::Capital))
+            .collect();
+
+        if tiles.len() < 2 {
+            for capital in capitals {
+                clear_capital(board, capital);
+            }
+            continue;
+        }
+        match capitals.split_first() {
+            // Provinces that grew together pool their money in one capital
+            Some((kept, others)) => {
+                let pooled: i32 = others
+                    .iter()
+                    .map(|other| clear_capital(board, *other))
+                    .sum();
+                let treasury = board.tile(*kept).unwrap().treasury + pooled;
+                board.tile_mut(*kept).unwrap().treasury = capped_treasury(board.rules, treasury);
+            }
+            // Empty tiles first, otherwise one where a tree or grave is cleared for it
+            None => {
+                let occupant = |c: &IVec2| board.tile(*c).and_then(|tile| tile.occupant);
+                let spot = tiles.iter().find(|c| occupant(c).is_none()).or_else(|| {
+                    tiles.iter().find(|c| {
+                        matches!(occupant(c), Some(Occupant::Tree(_)) | Some(Occupant::Grave))
+                    })
+                });
+                if let Some(spot) = spot.copied() {
+                    let tile = board.tile_mut(spot).unwrap();
+                    tile.occupant = Some(Occupant::Capital);
+                    tile.treasury = 0;
+                }
+            }
+        }
+    }
+}
+
+// What a province may keep of `treasury`
+fn capped_treasury(rules: &RuleSet, treasury: i32) -> i32 {
+    match rules.variants.treasury_cap {
+        Some(cap) => treasury.min(cap),
+        None => treasury,
+    }
+}
+
+// Removes the capital at `coord`, returns the money it held
+fn clear_capital(board: &mut Board, coord: IVec2) -> i32 {
+    let tile = board.tile_mut(coord).unwrap();
+    let treasury = tile.treasury;
+    tile.occupant = None;
+    tile.treasury = 0;
+    treasury
+}
+
+// Tiles `team` can see: everything, or with fog of war the tiles within the vision radius of its land
+pub fn visible_tiles(board: &Board, team: i32) -> HashSet<IVec2> {
+    let coords = board.coords();
+    let radius = match board.rules.variants.fog_of_war {
+        Some(fog) => fog.vision_radius,
+        None => return coords.into_iter().collect(),
+    };
+    // Units always stand on their team's land, so seeing around the land sees around them too
+    let mut frontier: Vec<IVec2> = coords
+        .into_iter()
+        .filter(|coord| {
+            board.tile(*coord).map_or(false, |tile| {
+                is_land(board.rules, tile) && tile.team == team
+            })
+        })
+        .collect();
+    let mut visible: HashSet<IVec2> = frontier.iter().copied().collect();
+    for _ in 0..radius {
+        let mut next = Vec::new();
+        for coord in frontier {
+            for neighbor in board.topology.neighbors(coord) {
+                if board.tile(neighbor).is_some() && visible.insert(neighbor) {
+                    next.push(neighbor);
+                }
+            }
+        }
+        frontier = next;
+    }
+    visible
+}
+
+// What a team knows of the board: every tile it has seen, as it was when it saw it last
+#[derive(Clone, Debug, Default)]
+pub struct TeamMemory {
+    // Indexed like Board::tiles, None for tiles it never saw
+    pub tiles: Vec<Option<TileState>>,
+}
+
+impl TeamMemory {
+    // Looks at the board through the eyes of `team`, returns the tiles it sees right now
+    pub fn observe(&mut self, board: &Board, team: i32) -> HashSet<IVec2> {
+        let visible = visible_tiles(board, team);
+        self.tiles.resize(board.tiles.len(), None);
+        for coord in visible.iter() {
+            let index = board.topology.coord_to_index(*coord);
+            self.tiles[index] = board.tiles[index].clone();
+        }
+        visible
+    }
+
+    // The board as far as the team knows it, tiles out of sight are as it remembers them
+    pub fn known_board<'a>(&self, board: &Board<'a>) -> Board<'a> {
+        Board {
+            topology: board.topology,
+            rules: board.rules,
+            tiles: (0..board.tiles.len())
+                .map(|index| self.tiles.get(index).cloned().flatten())
+                .collect(),
+        }
+    }
+}
+
+fn is_coastal(board: &Board, coord: IVec2) -> bool {
+    board
+        .topology
+        .neighbors(coord)
+        .into_iter()
+        .filter_map(|neighbor| board.tile(neighbor))
+        .any(|neighbor| matches!(neighbor.terrain, TerrainType::Water | TerrainType::Shallows))
+}
+
+// Graves of `team` grow into trees, palms along the coast and pines inland.
+// Without trees, or on terrain trees don't grow on, they just crumble
+fn grow_graves(board: &mut Board, team: i32) {
+    for coord in board.coords() {
+        let tile = board.tile(coord).unwrap();
+        if tile.team != team || tile.occupant != Some(Occupant::Grave) {
+            continue;
+        }
+        let treeless = board.rules.variants.no_trees || !board.rules.terrain(tile.terrain).trees;
+        let occupant = if treeless {
+            None
+        } else if is_coastal(board, coord) {
+            Some(Occupant::Tree(TreeKind::Palm))
+        } else {
+            Some(Occupant::Tree(TreeKind::Pine))
+        };
+        board.tile_mut(coord).unwrap().occupant = occupant;
+    }
+}
+
+// Trees spread onto empty land of `team` that has enough of them around, palms only along the coast
+fn spread_trees(board: &mut Board, team: i32, rng: &mut impl Rng) {
+    if board.rules.variants.no_trees {
+        return;
+    }
+    let mut grown = Vec::new();
+    for coord in board.coords() {
+        let tile = board.tile(coord).unwrap();
+        if !board.rules.terrain(tile.terrain).trees
+            || !is_land(board.rules, tile)
+            || tile.team != team
+            || tile.occupant.is_some()
+        {
+            continue;
+        }
+        let trees = |kind: TreeKind| {
+            board
+                .topology
+                .neighbors(coord)
+                .into_iter()
+                .filter(|neighbor| {
+                    board.tile(*neighbor).and_then(|tile| tile.occupant)
+                        == Some(Occupant::Tree(kind))
+                })
+                .count()
+        };
+        let spread = &board.rules.trees;
+        if trees(TreeKind::Pine) >= spread.pines_needed && rng.gen_bool(spread.pine_spread_chance) {
+            grown.push((coord, TreeKind::Pine));
+        } else if trees(TreeKind::Palm) >= spread.palms_needed
+            && is_coastal(board, coord)
+            && rng.gen_bool(spread.palm_spread_chance)
+        {
+            grown.push((coord, TreeKind::Palm));
+        }
+    }
+    for (coord, kind) in grown {
+        board.tile_mut(coord).unwrap().occupant = Some(Occupant::Tree(kind));
+    }
+}
+
+// Starts the turn of `team`: capitals are put in order, graves grow into trees and trees spread,
+// then every province of the team earns its income and pays its upkeep.
+// A province that can't pay goes bankrupt and its units starve, as the ruleset's bankruptcy rule says
+pub fn start_turn(board: &mut Board, team: i32, rng: &mut impl Rng) -> TurnReport {
+    update_capitals(board);
+    grow_graves(board, team);
+    spread_trees(board, team, rng);
+
+    let mut report = TurnReport::default();
+    let mut visited = HashSet::new();
+    for coord in board.coords() {
+        if visited.contains(&coord) || board.tile(coord).unwrap().team != team {
+            continue;
+        }
+        let tiles = province(board, coord);
+        visited.extend(tiles.iter().copied());
+        let capital = match province_capital(board, &tiles) {
+            Some(capital) => capital,
+            None => continue,
+        };
+        let mut balance = board.tile(capital).unwrap().treasury + province_income(board, &tiles)
+            - province_upkeep(board, &tiles);
+        if balance < 0 {
+            balance = starve_units(board, &tiles, balance);
+            report.bankrupt.push(capital);
+        }
+        board.tile_mut(capital).unwrap().treasury = capped_treasury(board.rules, balance.max(0));
+    }
+
+    for tile in board.tiles.iter_mut().flatten() {
+        if tile.team == team {
+            tile.moved = false;
+        }
+    }
+    report
+}
+
+// Turns units of a bankrupt province into graves, returns the balance left once their upkeep is gone
+fn starve_units(board: &mut Board, province: &[IVec2], balance: i32) -> i32 {
+    let rules = board.rules;
+    let mut units: Vec<(IVec2, UnitTier)> = province
+        .iter()
+        .filter_map(
+            |coord| match board.tile(*coord).and_then(|tile| tile.occupant) {
+                Some(Occupant::Unit(tier)) => Some((*coord, tier)),
+                _ => None,
+            },
+        )
+        .collect();
+    match rules.bankruptcy {
+        // The province loses its money along with its army
+        Bankruptcy::AllUnitsStarve => {
+            for (coord, _) in units {
+                board.tile_mut(coord).unwrap().occupant = Some(Occupant::Grave);
+            }
+            0
+        }
+        // The most expensive units go first
+        Bankruptcy::StarveUntilSolvent => {
+            units.sort_by_key(|(_, tier)| -rules.unit(*tier).upkeep);
+            let mut balance = balance;
+            for (coord, tier) in units {
+                if balance >= 0 {
+                    break;
+                }
+                balance += rules.unit(tier).upkeep;
+                board.tile_mut(coord).unwrap().occupant = Some(Occupant::Grave);
+            }
+            balance
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::ruleset::{load_default_ruleset, FogOfWar, Variants};
+    use crate::gameplay::topology::{FlatHexTopology, WrapMode};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn rules_with(variants: Variants) -> RuleSet {
+        load_default_ruleset().with_variants(&variants).unwrap()
+    }
+
+    fn land(team: i32) -> TileState {
+        TileState {
+            terrain: TerrainType::Land,
+            team,
+            occupant: None,
+            moved: false,
+            treasury: 0,
+        }
+    }
+
+    // All land, team 1 owns the columns left of `split` and team 2 the rest
+    fn split_board<'a>(topology: &'a FlatHexTopology, rules: &'a RuleSet, split: i32) -> Board<'a> {
+        Board::from_tiles(
+            topology,
+            rules,
+            topology
+                .coords()
+                .into_iter()
+                .map(|coord| (coord, land(if coord.x < split { 1 } else { 2 }))),
+        )
+    }
+
+    fn team_province(board: &Board, team: i32) -> Vec<IVec2> {
+        let coord = board
+            .coords()
+            .into_iter()
+            .find(|coord| board.tile(*coord).unwrap().team == team)
+            .unwrap();
+        province(board, coord)
+    }
+
+    #[test]
+    fn fog_of_war_hides_tiles_beyond_the_vision_radius() {
+        let topology = FlatHexTopology::new(10, 4, WrapMode::None);
+        let clear = rules_with(Variants::default());
+        let board = split_board(&topology, &clear, 2);
+        assert_eq!(visible_tiles(&board, 1).len(), topology.coords().len());
+
+        let foggy = rules_with(Variants {
+            fog_of_war: Some(FogOfWar { vision_radius: 1 }),
+            ..Default::default()
+        });
+        let board = split_board(&topology, &foggy, 2);
+        let visible = visible_tiles(&board, 1);
+        assert!(visible.iter().any(|coord| coord.x == 2));
+        assert!(visible.iter().all(|coord| coord.x <= 3));
+        let own_land: Vec<IVec2> = board
+            .coords()
+            .into_iter()
+            .filter(|coord| coord.x < 2)
+            .collect();
+        assert!(own_land.iter().all(|coord| visible.contains(coord)));
+    }
+
+    #[test]
+    fn memory_keeps_tiles_as_they_were_last_seen() {
+        let topology = FlatHexTopology::new(10, 4, WrapMode::None);
+        let foggy = rules_with(Variants {
+            fog_of_war: Some(FogOfWar { vision_radius: 1 }),
+            ..Default::default()
+        });
+        let near = IVec2::new(3, 1);
+        let far = IVec2::new(8, 2);
+        let mut board = split_board(&topology, &foggy, 2);
+        board.tile_mut(near).unwrap().occupant = Some(Occupant::Unit(UnitTier::Knight));
+        let mut memory = TeamMemory::default();
+        memory.observe(&board, 1);
+        let known = memory.known_board(&board);
+        assert_eq!(known.tile(near), board.tile(near));
+        assert!(known.tile(far).is_none());
+
+        // Team 1 lost all of its land, and the knight left while nobody was looking
+        let board = split_board(&topology, &foggy, 0);
+        assert!(memory.observe(&board, 1).is_empty());
+        let known = memory.known_board(&board);
+        assert_eq!(
+            known.tile(near).unwrap().occupant,
+            Some(Occupant::Unit(UnitTier::Knight))
+        );
+        assert_eq!(board.tile(near).unwrap().occupant, None);
+    }
+
+    #[test]
+    fn without_trees_graves_crumble_and_nothing_spreads() {
+        let topology = FlatHexTopology::new(6, 4, WrapMode::None);
+        let mut classic = load_default_ruleset();
+        classic.trees.pine_spread_chance = 1.0;
+        let treeless = classic
+            .with_variants(&Variants {
+                no_trees: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let center = IVec2::new(2, 2);
+        let neighbors = topology.neighbors(center);
+        let grave = IVec2::new(0, 0);
+
+        for rules in [&classic, &treeless].iter() {
+            let mut board = split_board(&topology, rules, 6);
+            board.tile_mut(IVec2::new(5, 3)).unwrap().occupant = Some(Occupant::Capital);
+            board.tile_mut(grave).unwrap().occupant = Some(Occupant::Grave);
+            for pine in neighbors.iter().take(2) {
+                board.tile_mut(*pine).unwrap().occupant = Some(Occupant::Tree(TreeKind::Pine));
+            }
+            start_turn(&mut board, 1, &mut StdRng::seed_from_u64(0));
+
+            let grave_after = board.tile(grave).unwrap().occupant;
+            let center_after = board.tile(center).unwrap().occupant;
+            if rules.variants.no_trees {
+                assert_eq!(grave_after, None);
+                assert_eq!(center_after, None);
+            } else {
+                assert!(matches!(grave_after, Some(Occupant::Tree(_))));
+                assert_eq!(center_after, Some(Occupant::Tree(TreeKind::Pine)));
+            }
+        }
+    }
+
+    #[test]
+    fn treasury_cap_limits_savings() {
+        let topology = FlatHexTopology::new(4, 4, WrapMode::None);
+        let capped = rules_with(Variants {
+            treasury_cap: Some(20),
+            ..Default::default()
+        });
+        let uncapped = rules_with(Variants::default());
+        // 18 saved and 8 tiles of income
+        for (rules, expected) in [(&capped, 20), (&uncapped, 26)].iter() {
+            let mut board = split_board(&topology, rules, 4);
+            let capital = board.tile_mut(IVec2::new(0, 0)).unwrap();
+            capital.occupant = Some(Occupant::Capital);
+            capital.treasury = 18;
+            start_turn(&mut board, 1, &mut StdRng::seed_from_u64(0));
+            assert_eq!(board.tile(IVec2::new(0, 0)).unwrap().treasury, *expected);
+        }
+    }
+
+    #[test]
+    fn terrain_income_pays_by_terrain() {
+        let topology = FlatHexTopology::new(4, 4, WrapMode::None);
+        let mut rules = rules_with(Variants {
+            terrain_income: true,
+            ..Default::default()
+        });
+        rules.income.terrain.insert(TerrainType::Land, 3);
+        let mut board = split_board(&topology, &rules, 2);
+        let tiles = team_province(&board, 1);
+        assert_eq!(province_income(&board, &tiles), 3 * tiles.len() as i32);
+
+        // Trees keep their own income
+        board.tile_mut(tiles[0]).unwrap().occupant = Some(Occupant::Tree(TreeKind::Pine));
+        assert_eq!(
+            province_income(&board, &tiles),
+            3 * (tiles.len() as i32 - 1)
+        );
+
+        let classic = rules_with(Variants::default());
+        let board = split_board(&topology, &classic, 2);
+        assert_eq!(province_income(&board, &tiles), tiles.len() as i32);
+    }
+
+    #[test]
+    fn terrain_changes_income_defense_and_movement() {
+        let topology = FlatHexTopology::new(6, 4, WrapMode::None);
+        let rules = rules_with(Variants::default());
+        let mut board = split_board(&topology, &rules, 4);
+        let home = IVec2::new(0, 0);
+        let tiles = province(&board, home);
+        board.tile_mut(home).unwrap().terrain = TerrainType::Plains;
+        assert_eq!(province_income(&board, &tiles), tiles.len() as i32 + 1);
+
+        // Mountains split provinces, nobody can walk over them
+        board.tile_mut(IVec2::new(2, 2)).unwrap().terrain = TerrainType::Mountain;
+        assert_eq!(province(&board, home).len(), tiles.len() - 1);
+
+        // A forest makes the empty tile too strong for a peasant
+        let forest = IVec2::new(4, 2);
+        let attacker = IVec2::new(3, 1);
+        board.tile_mut(forest).unwrap().terrain = TerrainType::Forest;
+        board.tile_mut(attacker).unwrap().occupant = Some(Occupant::Unit(UnitTier::Peasant));
+        assert_eq!(defense(&board, forest), 1);
+        assert!(!legal_moves(&board, attacker).contains(&forest));
+        assert!(legal_moves(&board, attacker).contains(&IVec2::new(4, 0)));
+        board.tile_mut(attacker).unwrap().occupant = Some(Occupant::Unit(UnitTier::Spearman));
+        assert!(legal_moves(&board, attacker).contains(&forest));
+
+        // and walking into one takes the rest of the turn
+        board.tile_mut(IVec2::new(1, 1)).unwrap().terrain = TerrainType::Forest;
+        apply_move(&mut board, attacker, IVec2::new(1, 1)).unwrap();
+        assert!(board.tile(IVec2::new(1, 1)).unwrap().moved);
+    }
+
+    #[test]
+    fn units_capture_weaker_land_along_the_border() {
+        let topology = FlatHexTopology::new(8, 4, WrapMode::None);
+        let rules = rules_with(Variants::default());
+        let mut board = split_board(&topology, &rules, 4);
+        let peasant = IVec2::new(1, 1);
+        board.tile_mut(peasant).unwrap().occupant = Some(Occupant::Unit(UnitTier::Peasant));
+        // A castle guards its own tile and the tiles around it
+        let castle = IVec2::new(5, 1);
+        board.tile_mut(castle).unwrap().occupant = Some(Occupant::Castle);
+
+        let targets = capture_targets(&board, peasant);
+        let border: Vec<IVec2> = board
+            .coords()
+            .into_iter()
+            .filter(|coord| coord.x == 4 || coord.x == 5)
+            .collect();
+        for coord in border.iter() {
+            let guarded = *coord == castle || topology.neighbors(castle).contains(coord);
+            assert_eq!(targets.contains(coord), !guarded, "{}", coord);
+        }
+        assert!(targets.iter().all(|coord| border.contains(coord)));
+        // Capturing doesn't depend on whether the unit already moved
+        board.tile_mut(peasant).unwrap().moved = true;
+        assert_eq!(capture_targets(&board, peasant).len(), targets.len());
+        assert!(capture_targets(&board, IVec2::new(0, 0)).is_empty());
+
+        // A knight is stronger than the castle
+        board.tile_mut(peasant).unwrap().occupant = Some(Occupant::Unit(UnitTier::Knight));
+        assert!(capture_targets(&board, peasant).contains(&IVec2::new(4, 2)));
+    }
+
+    #[test]
+    fn threatened_tiles_are_what_enemy_units_could_take() {
+        let topology = FlatHexTopology::new(8, 4, WrapMode::None);
+        let rules = rules_with(Variants::default());
+        let mut board = split_board(&topology, &rules, 4);
+        assert!(threatened_tiles(&board, 1).is_empty());
+
+        board.tile_mut(IVec2::new(6, 2)).unwrap().occupant =
+            Some(Occupant::Unit(UnitTier::Peasant));
+        let threatened = threatened_tiles(&board, 1);
+        assert!(!threatened.is_empty());
+        assert!(threatened.iter().all(|coord| coord.x == 2 || coord.x == 3));
+        // Our own units don't threaten us, and the enemy isn't threatened by its own
+        assert!(threatened_tiles(&board, 2).is_empty());
+
+        // A spearman on the border protects itself and its neighbors from peasants
+        let guard = IVec2::new(3, 1);
+        board.tile_mut(guard).unwrap().occupant = Some(Occupant::Unit(UnitTier::Spearman));
+        let threatened = threatened_tiles(&board, 1);
+        assert!(!threatened.contains(&guard));
+        assert!(!threatened.contains(&IVec2::new(2, 2)));
+        assert!(threatened.contains(&IVec2::new(3, 3)));
+    }
+
+    #[test]
+    fn moved_units_wait_for_their_next_turn() {
+        let topology = FlatHexTopology::new(6, 4, WrapMode::None);
+        let rules = rules_with(Variants::default());
+        let mut board = split_board(&topology, &rules, 4);
+        board.tile_mut(IVec2::new(0, 0)).unwrap().occupant = Some(Occupant::Capital);
+        board.tile_mut(IVec2::new(5, 3)).unwrap().occupant = Some(Occupant::Capital);
+        let unit = IVec2::new(1, 1);
+        board.tile_mut(unit).unwrap().occupant = Some(Occupant::Unit(UnitTier::Spearman));
+
+        // Taking enemy land ends the move, and the unit stays put through the other team's turn
+        let captured = IVec2::new(4, 2);
+        assert!(legal_moves(&board, unit).contains(&captured));
+        apply_move(&mut board, unit, IVec2::new(2, 2)).unwrap();
+        apply_move(&mut board, IVec2::new(2, 2), captured).unwrap();
+        assert!(legal_moves(&board, captured).is_empty());
+        assert!(apply_move(&mut board, captured, IVec2::new(2, 2)).is_none());
+        start_turn(&mut board, 2, &mut StdRng::seed_from_u64(0));
+        assert!(legal_moves(&board, captured).is_empty());
+
+        start_turn(&mut board, 1, &mut StdRng::seed_from_u64(0));
+        assert!(!legal_moves(&board, captured).is_empty());
+    }
+
+    #[test]
+    fn sudden_death_ends_the_game_after_the_round_limit() {
+        let topology = FlatHexTopology::new(6, 4, WrapMode::None);
+        let rules = rules_with(Variants {
+            sudden_death: Some(10),
+            ..Default::default()
+        });
+        let board = split_board(&topology, &rules, 4);
+        assert_eq!(game_over(&board, 9), None);
+        assert_eq!(game_over(&board, 10), Some(GameOver { winner: Some(1) }));
+
+        let tied = split_board(&topology, &rules, 3);
+        assert_eq!(game_over(&tied, 10), Some(GameOver { winner: None }));
+
+        let classic = rules_with(Variants::default());
+        let board = split_board(&topology, &classic, 4);
+        assert_eq!(game_over(&board, 1000), None);
+    }
+
+    #[test]
+    fn provinces_that_cant_pay_their_units_go_bankrupt() {
+        let topology = FlatHexTopology::new(4, 4, WrapMode::None);
+        let rules = rules_with(Variants::default());
+        let mut board = split_board(&topology, &rules, 2);
+        let capital = IVec2::new(0, 0);
+        let unit = IVec2::new(1, 1);
+        board.tile_mut(capital).unwrap().occupant = Some(Occupant::Capital);
+        board.tile_mut(IVec2::new(3, 3)).unwrap().occupant = Some(Occupant::Capital);
+
+        // 4 tiles feed a peasant
+        board.tile_mut(unit).unwrap().occupant = Some(Occupant::Unit(UnitTier::Peasant));
+        let report = start_turn(&mut board, 1, &mut StdRng::seed_from_u64(0));
+        assert!(report.bankrupt.is_empty());
+        assert_eq!(board.tile(capital).unwrap().treasury, 2);
+
+        // but not a knight, even with the savings
+        board.tile_mut(unit).unwrap().occupant = Some(Occupant::Unit(UnitTier::Knight));
+        let report = start_turn(&mut board, 1, &mut StdRng::seed_from_u64(0));
+        assert_eq!(report.bankrupt, vec![capital]);
+        assert_eq!(board.tile(unit).unwrap().occupant, Some(Occupant::Grave));
+        assert_eq!(board.tile(capital).unwrap().treasury, 0);
+        // Only the team on turn pays
+        assert_eq!(board.tile(IVec2::new(3, 3)).unwrap().treasury, 0);
+    }
+
+    #[test]
+    fn provinces_that_grow_together_pool_their_money() {
+        let topology = FlatHexTopology::new(8, 4, WrapMode::None);
+        let rules = rules_with(Variants::default());
+        // Team 2 holds the columns between the two provinces of team 1
+        let mut board = Board::from_tiles(
+            &topology,
+            &rules,
+            topology.coords().into_iter().map(|coord| {
+                let team = if coord.x == 2 || coord.x == 3 { 2 } else { 1 };
+                (coord, land(team))
+            }),
+        );
+        for (coord, treasury) in [(IVec2::new(0, 0), 5), (IVec2::new(7, 3), 7)].iter() {
+            let tile = board.tile_mut(*coord).unwrap();
+            tile.occupant = Some(Occupant::Capital);
+            tile.treasury = *treasury;
+        }
+        update_capitals(&mut board);
+        assert_eq!(province(&board, IVec2::new(0, 0)).len(), 4);
+        assert_eq!(board.tile(IVec2::new(7, 3)).unwrap().treasury, 7);
+
+        for coord in [
+            IVec2::new(2, 0),
+            IVec2::new(3, 1),
+            IVec2::new(2, 2),
+            IVec2::new(3, 3),
+        ]
+        .iter()
+        {
+            board.tile_mut(*coord).unwrap().team = 1;
+        }
+        update_capitals(&mut board);
+        let tiles = province(&board, IVec2::new(0, 0));
+        assert_eq!(tiles.len(), topology.coords().len());
+        let capitals: Vec<&IVec2> = tiles
+            .iter()
+            .filter(|coord| board.tile(**coord).unwrap().occupant == Some(Occupant::Capital))
+            .collect();
+        assert_eq!(capitals.len(), 1);
+        assert_eq!(province_treasury(&board, &tiles), Some(12));
+    }
+
+    #[test]
+    fn provinces_without_a_capital_build_a_new_one() {
+        let topology = FlatHexTopology::new(4, 4, WrapMode::None);
+        let rules = rules_with(Variants::default());
+        let mut board = split_board(&topology, &rules, 2);
+        // An empty tile is preferred over clearing a tree
+        let tiles = team_province(&board, 1);
+        for coord in tiles.iter().skip(1) {
+            board.tile_mut(*coord).unwrap().occupant = Some(Occupant::Tree(TreeKind::Pine));
+        }
+        update_capitals(&mut board);
+        assert_eq!(province_capital(&board, &tiles), Some(tiles[0]));
+        assert_eq!(province_treasury(&board, &tiles), Some(0));
+
+        // Without empty land a tree or grave makes way
+        board.tile_mut(tiles[0]).unwrap().occupant = Some(Occupant::Grave);
+        update_capitals(&mut board);
+        let capitals = tiles
+            .iter()
+            .filter(|coord| board.tile(**coord).unwrap().occupant == Some(Occupant::Capital))
+            .count();
+        assert_eq!(capitals, 1);
+
+        // A lone tile can't hold on to a capital
+        let mut board = split_board(&topology, &rules, 0);
+        let lone = board.tile_mut(IVec2::new(0, 0)).unwrap();
+        lone.team = 1;
+        lone.occupant = Some(Occupant::Capital);
+        lone.treasury = 9;
+        update_capitals(&mut board);
+        assert_eq!(board.tile(IVec2::new(0, 0)).unwrap().occupant, None);
+        assert_eq!(board.tile(IVec2::new(0, 0)).unwrap().treasury, 0);
+    }
+
+    #[test]
+    fn bankruptcy_rule_decides_which_units_starve() {
+        let topology = FlatHexTopology::new(4, 4, WrapMode::None);
+        let mut rules = rules_with(Variants::default());
+        let capital = IVec2::new(0, 0);
+        // 4 tiles of income against 26 upkeep
+        let knight = IVec2::new(1, 1);
+        let spearman = IVec2::new(0, 2);
+        let peasant = IVec2::new(1, 3);
+
+        for bankruptcy in [Bankruptcy::AllUnitsStarve, Bankruptcy::StarveUntilSolvent].iter() {
+            rules.bankruptcy = *bankruptcy;
+            let mut board = split_board(&topology, &rules, 2);
+            board.tile_mut(capital).unwrap().occupant = Some(Occupant::Capital);
+            for (coord, tier) in [
+                (knight, UnitTier::Knight),
+                (spearman, UnitTier::Spearman),
+                (peasant, UnitTier::Peasant),
+            ]
+            .iter()
+            {
+                board.tile_mut(*coord).unwrap().occupant = Some(Occupant::Unit(*tier));
+            }
+            let report = start_turn(&mut board, 1, &mut StdRng::seed_from_u64(0));
+            assert_eq!(report.bankrupt, vec![capital]);
+
+            let occupant = |coord: IVec2| board.tile(coord).unwrap().occupant;
+            assert_eq!(occupant(knight), Some(Occupant::Grave));
+            assert_eq!(occupant(spearman), Some(Occupant::Grave));
+            match bankruptcy {
+                Bankruptcy::AllUnitsStarve => {
+                    assert_eq!(occupant(peasant), Some(Occupant::Grave));
+                    assert_eq!(board.tile(capital).unwrap().treasury, 0);
+                }
+                // The knight and spearman go first, after them the peasant can be paid
+                Bankruptcy::StarveUntilSolvent => {
+                    assert_eq!(occupant(peasant), Some(Occupant::Unit(UnitTier::Peasant)));
+                    assert_eq!(board.tile(capital).unwrap().treasury, 2);
+                }
+            }
+        }
+    }
+}
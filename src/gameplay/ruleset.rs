@@ -0,0 +1,463 @@
+// Balance of the game: costs, upkeep, strengths, income, terrain, trees and bankruptcy.
+// Read from a ruleset asset so it can be tuned without compiling, the rules only take their numbers from here.
+// The variants a game is played with are picked per game and composed into the same RuleSet
+use super::components::{
+    Occupant, Purchase, Scenario, TerrainType, UnitTier, TERRAIN_TYPES, UNIT_TIERS,
+};
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const ASSETS_DIR: &str = "assets";
+
+// Used until a scenario picks another one, relative to the assets folder
+pub const DEFAULT_RULESET: &str = "rules/classic.rules.ron";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct UnitStats {
+    pub cost: i32,
+    // Paid by the province every turn
+    pub upkeep: i32,
+    pub strength: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct UnitTable {
+    pub peasant: UnitStats,
+    pub spearman: UnitStats,
+    pub knight: UnitStats,
+    pub baron: UnitStats,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct CastleRules {
+    pub cost: i32,
+    pub strength: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct IncomeRules {
+    pub per_tile: i32,
+    // Instead of per_tile, for tiles a tree grows on
+    pub per_tree: i32,
+    // Instead of per_tile and the terrain income with the terrain income variant, terrain that isn't listed earns nothing
+    #[serde(default)]
+    pub terrain: HashMap<TerrainType, i32>,
+}
+
+// What a kind of terrain does to the tiles that have it
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TerrainRules {
+    // Belongs to provinces, so units can walk and fight over it. Water never is, mountains might be
+    pub passable: bool,
+    // Added to per_tile, a tile never earns less than nothing
+    #[serde(default)]
+    pub income: i32,
+    // Added to the defense of the tile, attackers need that much more strength
+    #[serde(default)]
+    pub defense: i32,
+    // Units that move onto it are done for the turn
+    #[serde(default)]
+    pub ends_move: bool,
+    #[serde(default)]
+    pub trees: bool,
+    // Chance that a generated map turns a land tile into this terrain, or a coastal water tile for shallows and bridges
+    #[serde(default)]
+    pub frequency: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TreeRules {
+    // Chance per turn that an empty tile with enough pines around grows one
+    pub pine_spread_chance: f64,
+    pub pines_needed: usize,
+    // Palms only spread along the coast
+    pub palm_spread_chance: f64,
+    pub palms_needed: usize,
+}
+
+// What happens to a province that can't pay the upkeep of its units
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize)]
+pub enum Bankruptcy {
+    // All units of the province starve, like in Slay
+    AllUnitsStarve,
+    // The most expensive units starve one by one, until the rest can be paid
+    StarveUntilSolvent,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize)]
+pub struct FogOfWar {
+    // Tiles further than this from a team's land are hidden from it
+    pub vision_radius: i32,
+}
+
+// How the land of a new game is dealt between the teams
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize)]
+pub enum StartingPositions {
+    // Every tile goes to a random team, like in Slay
+    Random,
+    // Every team starts with one connected region
+    Clustered,
+}
+
+// Changes to the classic rules, chosen when a game is set up. All off by default
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Variants {
+    pub fog_of_war: Option<FogOfWar>,
+    // Graves crumble instead of growing into trees, and maps start without them
+    pub no_trees: bool,
+    // Provinces can't save more than this
+    pub treasury_cap: Option<i32>,
+    // None keeps the positions the map comes with
+    pub starting_positions: Option<StartingPositions>,
+    // Tiles earn the income of their terrain
+    pub terrain_income: bool,
+    // The game ends after this many rounds, the team with the most land wins
+    pub sudden_death: Option<i32>,
+}
+
+impl Variants {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if matches!(self.fog_of_war, Some(fog) if fog.vision_radius < 1) {
+            errors.push("The vision radius has to be at least 1".to_string());
+        }
+        if matches!(self.treasury_cap, Some(cap) if cap < 0) {
+            errors.push("The treasury cap can't be negative".to_string());
+        }
+        if matches!(self.sudden_death, Some(rounds) if rounds < 1) {
+            errors.push("Sudden death needs at least one round".to_string());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, TypeUuid)]
+#[uuid = "882963f2-a11c-4c18-bb54-82b3b9dd1038"]
+pub struct RuleSet {
+    pub units: UnitTable,
+    pub castle: CastleRules,
+    // How much a capital protects its own tile and its neighbors
+    pub capital_strength: i32,
+    pub income: IncomeRules,
+    // Every terrain type needs an entry
+    pub terrain: HashMap<TerrainType, TerrainRules>,
+    pub trees: TreeRules,
+    pub bankruptcy: Bankruptcy,
+    // Money every capital starts the game with
+    pub starting_treasury: i32,
+    // Not part of the file, they come from the scenario
+    #[serde(skip)]
+    pub variants: Variants,
+}
+
+impl RuleSet {
+    // Reads and validates the ruleset at `path`, relative to the assets folder.
+    // The errors are readable messages
+    pub fn load(path: &str) -> Result<RuleSet, Vec<String>> {
+        let full_path = Path::new(ASSETS_DIR).join(path);
+        let text = fs::read_to_string(&full_path)
+            .map_err(|error| vec![format!("{}: {}", full_path.display(), error)])?;
+        RuleSet::parse(text.as_bytes()).map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| format!("{}: {}", full_path.display(), error))
+                .collect()
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<RuleSet, Vec<String>> {
+        let rules: RuleSet = ron::de::from_bytes(bytes).map_err(|error| vec![error.to_string()])?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    // Everything that would make the rules misbehave, the numbers themselves are up to the designer
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut previous_strength = 0;
+        for tier in UNIT_TIERS.iter() {
+            let stats = self.unit(*tier);
+            if stats.cost <= 0 {
+                errors.push(format!("{:?} has to cost something", tier));
+            }
+            if stats.upkeep < 0 {
+                errors.push(format!("{:?} has a negative upkeep", tier));
+            }
+            // Merging looks units up by their summed strength, so every tier needs its own
+            if stats.strength <= previous_strength {
+                errors.push(format!("{:?} has to be stronger than the tier below", tier));
+            }
+            previous_strength = stats.strength;
+        }
+        if self.castle.cost <= 0 {
+            errors.push("The castle has to cost something".to_string());
+        }
+        if self.castle.strength < 0 || self.capital_strength < 0 {
+            errors.push("Castles and capitals can't have a negative strength".to_string());
+        }
+        if self.income.per_tile < 0
+            || self.income.per_tree < 0
+            || self.income.terrain.values().any(|income| *income < 0)
+        {
+            errors.push("Tiles can't cost income".to_string());
+        }
+        for terrain in TERRAIN_TYPES.iter() {
+            match self.terrain.get(terrain) {
+                Some(rules) => {
+                    if rules.defense < 0 {
+                        errors.push(format!("{:?} has a negative defense", terrain));
+                    }
+                    if !(0.0..=1.0).contains(&rules.frequency) {
+                        errors.push(format!(
+                            "{:?} frequency {} is not between 0 and 1",
+                            terrain, rules.frequency
+                        ));
+                    }
+                }
+                None => errors.push(format!("{:?} has no terrain rules", terrain)),
+            }
+        }
+        for chance in [self.trees.pine_spread_chance, self.trees.palm_spread_chance].iter() {
+            if !(0.0..=1.0).contains(chance) {
+                errors.push(format!(
+                    "Tree spread chance {} is not between 0 and 1",
+                    chance
+                ));
+            }
+        }
+        if self.starting_treasury < 0 {
+            errors.push("The starting treasury can't be negative".to_string());
+        }
+        if let Err(variant_errors) = self.variants.validate() {
+            errors.extend(variant_errors);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // The same rules, played with `variants`
+    pub fn with_variants(&self, variants: &Variants) -> Result<RuleSet, Vec<String>> {
+        variants.validate()?;
+        Ok(RuleSet {
+            variants: variants.clone(),
+            ..self.clone()
+        })
+    }
+
+    pub fn unit(&self, tier: UnitTier) -> &UnitStats {
+        match tier {
+            UnitTier::Peasant => &self.units.peasant,
+            UnitTier::Spearman => &self.units.spearman,
+            UnitTier::Knight => &self.units.knight,
+            UnitTier::Baron => &self.units.baron,
+        }
+    }
+
+    pub fn terrain(&self, terrain: TerrainType) -> &TerrainRules {
+        &self.terrain[&terrain]
+    }
+
+    pub fn passable(&self, terrain: TerrainType) -> bool {
+        self.terrain(terrain).passable
+    }
+
+    pub fn tier_from_strength(&self, strength: i32) -> Option<UnitTier> {
+        UNIT_TIERS
+            .iter()
+            .copied()
+            .find(|tier| self.unit(*tier).strength == strength)
+    }
+
+    // Two units standing on the same tile combine into the tier of their summed strength
+    pub fn merged_tier(&self, a: UnitTier, b: UnitTier) -> Option<UnitTier> {
+        self.tier_from_strength(self.unit(a).strength + self.unit(b).strength)
+    }
+
+    // How much an occupant protects its own tile and the neighboring tiles of the same team
+    pub fn occupant_strength(&self, occupant: Occupant) -> i32 {
+        match occupant {
+            Occupant::Unit(tier) => self.unit(tier).strength,
+            Occupant::Castle => self.castle.strength,
+            Occupant::Capital => self.capital_strength,
+            Occupant::Tree(_) | Occupant::Grave => 0,
+        }
+    }
+
+    pub fn purchase_cost(&self, item: Purchase) -> i32 {
+        match item {
+            Purchase::Unit(tier) => self.unit(tier).cost,
+            Purchase::Castle => self.castle.cost,
+        }
+    }
+}
+
+// Loads .rules.ron files through the asset server, so they are reloaded when they change on disk
+#[derive(Default)]
+pub struct RuleSetLoader;
+
+impl AssetLoader for RuleSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let rules =
+                RuleSet::parse(bytes).map_err(|errors| anyhow::anyhow!(errors.join(", ")))?;
+            load_context.set_default_asset(LoadedAsset::new(rules));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rules.ron"]
+    }
+}
+
+// The asset the rules of the current game come from, edits to it apply right away
+pub struct ActiveRuleSet {
+    pub path: String,
+    pub handle: Handle<RuleSet>,
+}
+
+impl FromWorld for ActiveRuleSet {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        ActiveRuleSet {
+            path: DEFAULT_RULESET.to_string(),
+            handle: asset_server.load(DEFAULT_RULESET),
+        }
+    }
+}
+
+// The game can't be played without rules, so a broken default ruleset stops it
+pub fn load_default_ruleset() -> RuleSet {
+    RuleSet::load(DEFAULT_RULESET).unwrap_or_else(|errors| {
+        panic!(
+            "Invalid ruleset {}:\n{}",
+            DEFAULT_RULESET,
+            errors.join("\n")
+        )
+    })
+}
+
+// Switches to the ruleset and variants of the scenario when a game starts
+pub fn load_scenario_ruleset(
+    scenario: Res<Scenario>,
+    asset_server: Res<AssetServer>,
+    mut active: ResMut<ActiveRuleSet>,
+    mut rules: ResMut<RuleSet>,
+) {
+    if scenario.rules != active.path {
+        // Loaded right away as well, the first turn starts before the asset server is done
+        match RuleSet::load(&scenario.rules) {
+            Ok(loaded) => {
+                *rules = loaded;
+                active.path = scenario.rules.clone();
+                active.handle = asset_server.load(scenario.rules.as_str());
+            }
+            Err(errors) => warn!("Keeping the {} rules: {}", active.path, errors.join(", ")),
+        }
+    }
+    match rules.with_variants(&scenario.variants) {
+        Ok(composed) => *rules = composed,
+        Err(errors) => {
+            warn!("Playing without variants: {}", errors.join(", "));
+            rules.variants = Variants::default();
+        }
+    }
+}
+
+// Takes over edits to the active ruleset file, files that don't validate never make it into the assets
+pub fn reload_ruleset(
+    mut events: EventReader<AssetEvent<RuleSet>>,
+    assets: Res<Assets<RuleSet>>,
+    active: Res<ActiveRuleSet>,
+    mut rules: ResMut<RuleSet>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if *handle == active.handle {
+                if let Some(reloaded) = assets.get(handle) {
+                    info!("Reloaded the rules from {}", active.path);
+                    // The variants of the running game stay
+                    *rules = RuleSet {
+                        variants: rules.variants.clone(),
+                        ..reloaded.clone()
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_shipped_ruleset_is_valid() {
+        let rules_dir = Path::new(ASSETS_DIR).join("rules");
+        let mut found = 0;
+        for entry in fs::read_dir(&rules_dir).unwrap() {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            if name.ends_with(".rules.ron") {
+                found += 1;
+                if let Err(errors) = RuleSet::load(&format!("rules/{}", name)) {
+                    panic!("{}", errors.join("\n"));
+                }
+            }
+        }
+        assert!(found > 0);
+    }
+
+    #[test]
+    fn broken_rules_are_rejected() {
+        let mut rules = load_default_ruleset();
+        rules.units.knight.strength = rules.units.spearman.strength;
+        rules.castle.cost = 0;
+        rules.trees.pine_spread_chance = 1.5;
+        rules.terrain.remove(&TerrainType::Bridge);
+        rules.terrain.get_mut(&TerrainType::Forest).unwrap().defense = -1;
+        let errors = rules.validate().unwrap_err();
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+    }
+
+    #[test]
+    fn broken_variants_are_rejected() {
+        let variants = Variants {
+            fog_of_war: Some(FogOfWar { vision_radius: 0 }),
+            sudden_death: Some(0),
+            ..Default::default()
+        };
+        let errors = load_default_ruleset().with_variants(&variants).unwrap_err();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn merging_follows_the_strengths() {
+        let rules = load_default_ruleset();
+        assert_eq!(
+            rules.merged_tier(UnitTier::Peasant, UnitTier::Spearman),
+            Some(UnitTier::Knight)
+        );
+        assert_eq!(
+            rules.merged_tier(UnitTier::Knight, UnitTier::Spearman),
+            None
+        );
+    }
+}
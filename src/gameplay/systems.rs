@@ -1,100 +1,921 @@
 use super::components::*;
+use super::helpers;
+use super::rules;
+use super::ruleset::RuleSet;
+use super::topology::HexTopology;
+use crate::app_state::{AppScreen, GameResult};
+use crate::input_actions::{Action, ActionState};
 use crate::math_helpers;
+use crate::orbit_camera::{
+    ground_axes, CameraBookmarks, CameraCommand, FocusCapital, OrbitCamera, OrbitCameraSettings,
+};
 use crate::rendering;
-use crate::rendering::components::*;
 use crate::wrapped_shader_functions;
 use bevy::prelude::*;
 use bevy::render::mesh::VertexAttributeValues;
+use bevy_mod_raycast::Intersection;
 use bevy_mod_raycast::RayCastMethod;
 use bevy_mod_raycast::RayCastSource;
+use hex_shader::{
+    OVERLAY_CAPITAL, OVERLAY_CAPTURABLE, OVERLAY_PATH, OVERLAY_REACHABLE, OVERLAY_THREATENED,
+};
 
-pub fn update_mouse_hovering_and_selected(
+// Finds the tile under the cursor and turns mouse input on it into hover and click events
+pub fn hex_picking_system(
     raycast_source_query: Query<&RayCastSource<HexRaycastLayer>>,
     raycast_mesh_query: Query<(
         &HexRaycastTarget,
-        &Handle<HexMaterial>,
         &Handle<Mesh>,
         &GlobalTransform,
+        Option<&Planet>,
     )>,
-    mouse_button_input: Res<Input<MouseButton>>,
+    actions: Res<ActionState>,
     meshes: Res<Assets<Mesh>>,
-    mut current_selection: ResMut<Selection>,
-    mut my_materials: ResMut<Assets<HexMaterial>>,
+    grid: Res<HexGrid>,
+    ui_interactions: Query<&Interaction>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut cursor: ResMut<HexCursor>,
+    mut last_hovered: Local<Option<IVec2>>,
+    mut hovered_events: EventWriter<HexHovered>,
+    mut clicked_events: EventWriter<HexClicked>,
 ) {
+    // Moving the mouse takes the cursor back from the gamepad
+    if cursor_moved_events.iter().count() > 0 {
+        cursor.mode = CursorMode::Mouse;
+    }
+
+    let mut hovered = None;
     for raycast_source in raycast_source_query.iter() {
         if let Some((entity, intersection)) = raycast_source.intersect_top() {
-            if let Ok((_raycast_mesh, material_handle, mesh_handle, transform)) =
+            if let Ok((_raycast_mesh, mesh_handle, transform, planet)) =
                 raycast_mesh_query.get(entity)
             {
-                if let Some(mesh) = meshes.get(mesh_handle.clone()) {
-                    let triangle_indices =
-                        rendering::helpers::calculate_vertex_indices_from_intersection(
-                            &intersection,
-                            mesh,
-                            transform.compute_matrix(),
-                        );
-
-                    if let Some(VertexAttributeValues::Float2(uvs)) =
-                        mesh.attribute(Mesh::ATTRIBUTE_UV_0)
-                    {
-                        let uv_v0 = Vec2::from(uvs[triangle_indices[0] as usize]);
-                        let uv_v1 = Vec2::from(uvs[triangle_indices[1] as usize]);
-                        let uv_v2 = Vec2::from(uvs[triangle_indices[2] as usize]);
-
-                        let tri = intersection.world_triangle().unwrap();
-                        let pos = intersection.position();
-                        let barycentric_coords =
-                            math_helpers::calculate_barycentric_coords(tri.v0, tri.v1, tri.v2, pos);
-                        let mut interpolated_uv: Vec2 = uv_v0 * barycentric_coords.x
-                            + uv_v1 * barycentric_coords.y
-                            + uv_v2 * barycentric_coords.z;
-                        // Temp thing in the shader, to get more hexes on the mesh
-                        interpolated_uv *= 5.0;
-
-                        if let Some(material) = my_materials.get_mut(material_handle) {
-                            let hex_id = interpolated_uv
-                                - wrapped_shader_functions::hex_relative_uv(interpolated_uv);
-                            let hex_coord = wrapped_shader_functions::hex_grid_coord(hex_id);
-
-                            // TODO: This should probably set some world state. And then we should translate it into the material
-                            material.highlighted_coord = hex_coord;
-                            if mouse_button_input.just_pressed(MouseButton::Left) {
-                                material.selected_coord = hex_coord;
-                                current_selection.coords = IVec2::new(
-                                    hex_coord.x.round().trunc() as i32,
-                                    hex_coord.y.round().trunc() as i32,
-                                );
-                            }
-                        }
+                let hex_coord = match (planet.map(|planet| planet.shape), grid.sphere()) {
+                    (Some(PlanetShape::Sphere { .. }), Some(sphere)) => {
+                        let local_pos = transform
+                            .compute_matrix()
+                            .inverse()
+                            .transform_point3(intersection.position());
+                        Some(sphere.index_to_coord(sphere.nearest_cell(local_pos)).as_f32())
                     }
-                }
+                    _ => meshes
+                        .get(mesh_handle.clone())
+                        .and_then(|mesh| pick_hex_coord_from_uv(&intersection, mesh, transform)),
+                };
+
+                // Picks past a wrapping edge land on the cell at the other side
+                hovered = hex_coord
+                    .map(|coord| grid.wrap(IVec2::new(coord.x.round() as i32, coord.y.round() as i32)))
+                    .filter(|coord| grid.contains(*coord));
             }
         }
     }
+
+    if cursor.mode == CursorMode::Mouse && cursor.coord != hovered {
+        cursor.coord = hovered;
+    }
+    if cursor.coord != *last_hovered {
+        *last_hovered = cursor.coord;
+        hovered_events.send(HexHovered {
+            coord: cursor.coord,
+        });
+    }
+
+    // Clicks on ui elements don't reach the map behind them
+    let pointer_over_ui = cursor.mode == CursorMode::Mouse
+        && ui_interactions
+            .iter()
+            .any(|interaction| *interaction != Interaction::None);
+    if let Some(coord) = cursor.coord {
+        if actions.just_pressed(Action::Select) && !pointer_over_ui {
+            clicked_events.send(HexClicked {
+                coord,
+                additive: actions.pressed(Action::MultiSelect),
+            });
+        }
+    }
+}
+
+// Hex coordinate under the intersection, for meshes that map the flat grid through their uvs
+fn pick_hex_coord_from_uv(
+    intersection: &Intersection,
+    mesh: &Mesh,
+    transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let triangle_indices = rendering::helpers::calculate_vertex_indices_from_intersection(
+        intersection,
+        mesh,
+        transform.compute_matrix(),
+    );
+
+    if let Some(VertexAttributeValues::Float2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        let uv_v0 = Vec2::from(uvs[triangle_indices[0] as usize]);
+        let uv_v1 = Vec2::from(uvs[triangle_indices[1] as usize]);
+        let uv_v2 = Vec2::from(uvs[triangle_indices[2] as usize]);
+
+        let tri = intersection.world_triangle().unwrap();
+        let pos = intersection.position();
+        let barycentric_coords =
+            math_helpers::calculate_barycentric_coords(tri.v0, tri.v1, tri.v2, pos);
+        let mut interpolated_uv: Vec2 = uv_v0 * barycentric_coords.x
+            + uv_v1 * barycentric_coords.y
+            + uv_v2 * barycentric_coords.z;
+        interpolated_uv *= rendering::helpers::HEX_UV_SCALE;
+
+        let hex_id = interpolated_uv - wrapped_shader_functions::hex_relative_uv(interpolated_uv);
+        Some(wrapped_shader_functions::hex_grid_coord(hex_id))
+    } else {
+        None
+    }
 }
 
-pub fn deselection_system(
+// Moves the HoverTag to the tile under the cursor
+pub fn hover_system(
     mut commands: Commands,
-    mut positions: Query<(Entity, &mut GridPosition), With<SelectedTag>>,
-    current_selection: Res<Selection>,
+    mut hovered_events: EventReader<HexHovered>,
     grid: Res<HexGrid>,
+    hovered_tiles: Query<Entity, With<HoverTag>>,
 ) {
-    for (entity, pos) in positions.iter_mut() {
-        if grid.coord_to_index(pos.position) != grid.coord_to_index(current_selection.coords) {
-            commands.entity(entity).remove::<SelectedTag>();
+    if let Some(event) = hovered_events.iter().last() {
+        for entity in hovered_tiles.iter() {
+            commands.entity(entity).remove::<HoverTag>();
+        }
+        if let Some(entity) = event.coord.and_then(|coord| helpers::tile_entity(&grid, coord)) {
+            commands.entity(entity).insert(HoverTag);
         }
     }
 }
 
 pub fn selection_system(
+    mut clicked_events: EventReader<HexClicked>,
+    mut selection: ResMut<Selection>,
+    preview: Res<MovePreview>,
+    mut move_requests: EventWriter<MoveRequested>,
+) {
+    for click in clicked_events.iter() {
+        if click.additive {
+            match selection.coords.iter().position(|coord| *coord == click.coord) {
+                Some(index) => {
+                    selection.coords.remove(index);
+                }
+                None => selection.coords.push(click.coord),
+            }
+            continue;
+        }
+
+        // Clicking a legal target of the selected unit moves it there
+        if let Some(from) = selection.primary() {
+            if selection.coords.len() == 1 && preview.targets.contains(&click.coord) {
+                move_requests.send(MoveRequested {
+                    from,
+                    to: click.coord,
+                });
+            }
+        }
+        selection.coords = vec![click.coord];
+    }
+}
+
+// Keeps the SelectedTag in line with the Selection
+pub fn update_selected_tags(
+    mut commands: Commands,
+    selection: Res<Selection>,
+    tiles: Query<(Entity, &GridPosition, Option<&SelectedTag>)>,
+    mut selection_changed: EventWriter<SelectionChanged>,
+) {
+    if !selection.is_changed() {
+        return;
+    }
+    for (entity, position, tag) in tiles.iter() {
+        match (selection.contains(position.position), tag.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(SelectedTag);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<SelectedTag>();
+            }
+            _ => {}
+        }
+    }
+    selection_changed.send(SelectionChanged);
+}
+
+pub fn update_move_preview(
+    selection: Res<Selection>,
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    mut hovered_events: EventReader<HexHovered>,
+    mut moved_events: EventReader<UnitMoved>,
+    mut purchased_events: EventReader<UnitPurchased>,
+    mut hovered: Local<Option<IVec2>>,
+    pending_purchase: Res<PendingPurchase>,
+    vision: Res<TeamVision>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    mut preview: ResMut<MovePreview>,
+) {
+    let mut dirty = selection.is_changed() || pending_purchase.is_changed() || vision.is_changed();
+    for event in hovered_events.iter() {
+        *hovered = event.coord;
+        dirty = true;
+    }
+    if moved_events.iter().count() > 0 || purchased_events.iter().count() > 0 {
+        dirty = true;
+    }
+    if !dirty {
+        return;
+    }
+
+    let board = helpers::build_board(&grid, &ruleset, &tiles);
+    let team = turn_order.team_for_turn(state.current().turn);
+    let on_turn = |coord: IVec2| board.tile(coord).map(|tile| tile.team) == Some(team);
+    // While a purchase is dragged the preview shows where it can be dropped instead
+    if let (Some(item), Some(coord)) = (pending_purchase.item, selection.primary()) {
+        preview.targets = if on_turn(coord) {
+            rules::placement_targets(&board, coord, item)
+        } else {
+            Vec::new()
+        };
+        preview.path = Vec::new();
+        return;
+    }
+    // Only the team on turn moves its units, and units out of sight don't give away where they could go
+    preview.targets = match selection.primary() {
+        Some(from)
+            if selection.coords.len() == 1
+                && vision.sees(grid.coord_to_index(from))
+                && on_turn(from) =>
+        {
+            rules::legal_moves(&board, from)
+        }
+        _ => Vec::new(),
+    };
+    preview.path = match (selection.primary(), *hovered) {
+        (Some(from), Some(to)) if preview.targets.contains(&to) => {
+            rules::move_path(&board, from, to).unwrap_or_default()
+        }
+        _ => Vec::new(),
+    };
+}
+
+pub fn execute_moves(
+    mut commands: Commands,
+    mut move_requests: EventReader<MoveRequested>,
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    mut history: ResMut<UndoHistory>,
+    mut moved_events: EventWriter<UnitMoved>,
+) {
+    let requests: Vec<(IVec2, IVec2)> = move_requests
+        .iter()
+        .map(|request| (request.from, request.to))
+        .collect();
+    if requests.is_empty() {
+        return;
+    }
+
+    let team = turn_order.team_for_turn(state.current().turn);
+    let mut board = helpers::build_board(&grid, &ruleset, &tiles);
+    for (from, to) in requests {
+        // Nobody moves the units of another team
+        if board.tile(from).map(|tile| tile.team) != Some(team) {
+            continue;
+        }
+        let before = board.tiles.clone();
+        // A capture can split or join provinces, which moves capitals and treasuries elsewhere
+        if let Some(outcome) = rules::apply_move(&mut board, from, to) {
+            helpers::write_changed_tiles(&mut commands, &grid, &before, &board.tiles);
+            history.boards.push(before);
+            moved_events.send(UnitMoved { outcome });
+        }
+    }
+}
+
+// Dropping a dragged purchase on the map asks to buy it there, for the selected province
+pub fn purchase_drop_system(
+    actions: Res<ActionState>,
+    selection: Res<Selection>,
+    mut hovered_events: EventReader<HexHovered>,
+    mut hovered: Local<Option<IVec2>>,
+    mut pending_purchase: ResMut<PendingPurchase>,
+    mut purchase_requests: EventWriter<PurchaseRequested>,
+) {
+    for event in hovered_events.iter() {
+        *hovered = event.coord;
+    }
+    if !actions.just_released(Action::Select) {
+        return;
+    }
+    if let Some(item) = pending_purchase.item.take() {
+        if let (Some(province), Some(target)) = (selection.primary(), *hovered) {
+            purchase_requests.send(PurchaseRequested {
+                province,
+                item,
+                target,
+            });
+        }
+    }
+}
+
+pub fn execute_purchases(
+    mut commands: Commands,
+    mut purchase_requests: EventReader<PurchaseRequested>,
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    mut history: ResMut<UndoHistory>,
+    mut purchased_events: EventWriter<UnitPurchased>,
+) {
+    let requests: Vec<(IVec2, Purchase, IVec2)> = purchase_requests
+        .iter()
+        .map(|request| (request.province, request.item, request.target))
+        .collect();
+    if requests.is_empty() {
+        return;
+    }
+
+    let team = turn_order.team_for_turn(state.current().turn);
+    let mut board = helpers::build_board(&grid, &ruleset, &tiles);
+    for (province, item, target) in requests {
+        // Only the team on turn spends the money of its provinces
+        if board.tile(province).map(|tile| tile.team) != Some(team) {
+            continue;
+        }
+        let before = board.tiles.clone();
+        if let Some(outcome) = rules::apply_purchase(&mut board, province, item, target) {
+            helpers::write_changed_tiles(&mut commands, &grid, &before, &board.tiles);
+            history.boards.push(before);
+            purchased_events.send(UnitPurchased { outcome });
+        }
+    }
+}
+
+// Fills the overlay layer with the move preview, threats, province outlines and capitals
+pub fn update_map_overlay(
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    changed_tiles: Query<(), Or<(Changed<Team>, Changed<Occupant>)>>,
+    move_preview: Res<MovePreview>,
+    selection: Res<Selection>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    vision: Res<TeamVision>,
+    mut overlaid_turn: Local<Option<i32>>,
+    mut overlay: ResMut<MapOverlay>,
+) {
+    let turn = state.current().turn;
+    let dirty = move_preview.is_changed()
+        || *overlaid_turn != Some(turn)
+        || vision.is_changed()
+        || changed_tiles.iter().next().is_some()
+        || overlay.cells.len() != grid.storage_len();
+    if !dirty {
+        return;
+    }
+    *overlaid_turn = Some(turn);
+
+    // Outlines, capitals and threats as far as the team on turn knows them
+    let current_team = turn_order.team_for_turn(turn);
+    let board = helpers::build_board(&grid, &ruleset, &tiles);
+    let board = match vision.memories.get(&current_team) {
+        Some(memory) => memory.known_board(&board),
+        None => board,
+    };
+    let mut cells = vec![0; grid.storage_len()];
+
+    for coord in board.coords() {
+        let tile = board.tile(coord).unwrap();
+        let index = grid.coord_to_index(coord);
+        if rules::is_land(&ruleset, tile) {
+            for (edge, neighbor) in grid.edges(coord).into_iter().enumerate() {
+                let same_province = neighbor
+                    .and_then(|neighbor| board.tile(neighbor))
+                    .map_or(false, |other| {
+                        rules::is_land(&ruleset, other) && other.team == tile.team
+                    });
+                if !same_province {
+                    cells[index] |= 1 << edge;
+                }
+            }
+        }
+        if tile.occupant == Some(Occupant::Capital) {
+            cells[index] |= OVERLAY_CAPITAL;
+        }
+    }
+
+    let mover_team = selection
+        .primary()
+        .and_then(|coord| board.tile(coord))
+        .map(|tile| tile.team);
+    for target in move_preview.targets.iter() {
+        let own = board.tile(*target).map(|tile| tile.team) == mover_team;
+        cells[grid.coord_to_index(*target)] |= if own {
+            OVERLAY_REACHABLE
+        } else {
+            OVERLAY_CAPTURABLE
+        };
+    }
+    for coord in move_preview.path.iter() {
+        cells[grid.coord_to_index(*coord)] |= OVERLAY_PATH;
+    }
+
+    for coord in rules::threatened_tiles(&board, current_team) {
+        cells[grid.coord_to_index(coord)] |= OVERLAY_THREATENED;
+    }
+
+    overlay.cells = cells;
+}
+
+// Every unit gets to move again when a new turn starts, and what happened before can't be undone anymore
+pub fn start_turn_system(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    mut started_turn: Local<Option<i32>>,
+    mut history: ResMut<UndoHistory>,
+    mut turn_events: EventWriter<TurnStarted>,
+    mut bankrupt_events: EventWriter<ProvinceBankrupt>,
+) {
+    // The state reports a change every frame, so the turn number tells when a new turn begins
+    let turn = state.current().turn;
+    if *started_turn == Some(turn) && !grid.is_added() {
+        return;
+    }
+    *started_turn = Some(turn);
+    history.boards.clear();
+
+    let team = turn_order.team_for_turn(turn);
+    let mut board = helpers::build_board(&grid, &ruleset, &tiles);
+    let before = board.tiles.clone();
+    let report = rules::start_turn(&mut board, team, &mut rand::thread_rng());
+    helpers::write_changed_tiles(&mut commands, &grid, &before, &board.tiles);
+    turn_events.send(TurnStarted { team });
+    for capital in report.bankrupt {
+        bankrupt_events.send(ProvinceBankrupt { capital });
+    }
+}
+
+// Lets the team on turn look at the board whenever its turn begins or tiles change,
+// and works out which tiles it sees, remembers or never saw
+pub fn update_team_vision(
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    changed_tiles: Query<(), Or<(Changed<Team>, Changed<Occupant>)>>,
+    mut observed_turn: Local<Option<i32>>,
+    mut vision: ResMut<TeamVision>,
+) {
+    let turn = state.current().turn;
+    let dirty = *observed_turn != Some(turn)
+        || changed_tiles.iter().next().is_some()
+        || ruleset.is_changed()
+        || vision.cells.len() != grid.storage_len();
+    if !dirty {
+        return;
+    }
+    *observed_turn = Some(turn);
+
+    let team = turn_order.team_for_turn(turn);
+    let board = helpers::build_board(&grid, &ruleset, &tiles);
+    let vision = &mut *vision;
+    vision.team = team;
+    let memory = vision.memories.entry(team).or_default();
+    let visible = memory.observe(&board, team);
+    vision.cells = (0..grid.storage_len())
+        .map(|index| {
+            if visible.contains(&grid.index_to_coord(index)) {
+                TileVisibility::Visible
+            } else if memory.tiles[index].is_some() {
+                TileVisibility::Remembered
+            } else {
+                TileVisibility::Unexplored
+            }
+        })
+        .collect();
+}
+
+// Ends the turn, asking first when the current player still has units that didn't move.
+// EndTurn again confirms, Cancel keeps playing
+pub fn end_turn_system(
+    actions: Res<ActionState>,
+    turn_order: Res<TurnOrder>,
+    units: Query<(&Team, &Occupant), Without<MovedTag>>,
+    mut end_turn_requests: EventReader<EndTurnRequested>,
+    mut prompt: ResMut<EndTurnPrompt>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let mut requested = actions.just_pressed(Action::EndTurn);
+    let mut confirmed = requested && prompt.unmoved_units.is_some();
+    for request in end_turn_requests.iter() {
+        requested = true;
+        confirmed |= request.confirmed;
+    }
+    if !requested {
+        return;
+    }
+
+    let turn = state.current().turn;
+    if !confirmed {
+        let team = turn_order.team_for_turn(turn);
+        let unmoved_units = units
+            .iter()
+            .filter(|(unit_team, occupant)| unit_team.number == team && occupant.is_unit())
+            .count();
+        if unmoved_units > 0 {
+            prompt.unmoved_units = Some(unmoved_units);
+            return;
+        }
+    }
+    prompt.unmoved_units = None;
+    state.set(GameState { turn: turn + 1 }).unwrap();
+}
+
+// Closes the end turn prompt, or drops the purchase that is being placed, or else the selection
+pub fn cancel_system(
+    actions: Res<ActionState>,
+    mut selection: ResMut<Selection>,
+    mut pending_purchase: ResMut<PendingPurchase>,
+    mut prompt: ResMut<EndTurnPrompt>,
+) {
+    if !actions.just_pressed(Action::Cancel) {
+        return;
+    }
+    if prompt.unmoved_units.is_some() {
+        prompt.unmoved_units = None;
+    } else if pending_purchase.item.is_some() {
+        pending_purchase.item = None;
+    } else if !selection.coords.is_empty() {
+        selection.coords.clear();
+    }
+}
+
+// Puts the board back the way it was before the last move or purchase of this turn
+pub fn undo_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    mut history: ResMut<UndoHistory>,
+    mut selection: ResMut<Selection>,
+) {
+    if !actions.just_pressed(Action::Undo) {
+        return;
+    }
+    let previous = match history.boards.pop() {
+        Some(previous) => previous,
+        None => return,
+    };
+    let board = helpers::build_board(&grid, &ruleset, &tiles);
+    helpers::write_changed_tiles(&mut commands, &grid, &board.tiles, &previous);
+    // The selection may point at a unit that is not there anymore
+    selection.coords.clear();
+}
+
+// Selects the next or previous unit of the current player that can still move, and brings the camera to it
+pub fn cycle_units_system(
+    actions: Res<ActionState>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    units: Query<(&GridPosition, &Team, &Occupant), Without<MovedTag>>,
+    grid: Res<HexGrid>,
+    planets: Query<(&Handle<Mesh>, &GlobalTransform, &Planet)>,
+    meshes: Res<Assets<Mesh>>,
+    mut selection: ResMut<Selection>,
+    mut cursor: ResMut<HexCursor>,
+    mut camera_commands: EventWriter<CameraCommand>,
+) {
+    // Shift+Tab also presses Tab, going back wins
+    let step = if actions.just_pressed(Action::CycleUnitsBack) {
+        -1
+    } else if actions.just_pressed(Action::CycleUnits) {
+        1
+    } else {
+        return;
+    };
+    let team = turn_order.team_for_turn(state.current().turn);
+    let mut coords: Vec<IVec2> = units
+        .iter()
+        .filter(|(_, unit_team, occupant)| unit_team.number == team && occupant.is_unit())
+        .map(|(position, _, _)| position.position)
+        .collect();
+    if coords.is_empty() {
+        return;
+    }
+    coords.sort_by_key(|coord| (coord.y, coord.x));
+    let len = coords.len() as i32;
+    let next = match selection
+        .primary()
+        .and_then(|current| coords.iter().position(|coord| *coord == current))
+    {
+        Some(index) => (index as i32 + step).rem_euclid(len),
+        None if step < 0 => len - 1,
+        None => 0,
+    };
+    let coord = coords[next as usize];
+    selection.coords = vec![coord];
+    cursor.coord = Some(coord);
+    cursor.mode = CursorMode::Directional;
+    if let Some(position) = helpers::hex_world_position(&grid, &planets, &meshes, coord) {
+        camera_commands.send(CameraCommand::CenterOn(position));
+    }
+}
+
+// Seconds a cursor direction is held before it starts repeating, and between repeats
+const CURSOR_REPEAT_DELAY: f32 = 0.4;
+const CURSOR_REPEAT_INTERVAL: f32 = 0.15;
+
+const CURSOR_ACTIONS: [Action; 4] = [
+    Action::CursorUp,
+    Action::CursorDown,
+    Action::CursorLeft,
+    Action::CursorRight,
+];
+
+// Steps the hex cursor to the neighbor that lies the most in the pushed direction, as seen from the camera,
+// and keeps the camera on it. Held directions repeat like held keys
+pub fn directional_cursor_system(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    grid: Res<HexGrid>,
+    selection: Res<Selection>,
+    planets: Query<(&Handle<Mesh>, &GlobalTransform, &Planet)>,
+    meshes: Res<Assets<Mesh>>,
+    cameras: Query<&OrbitCamera>,
+    mut cursor: ResMut<HexCursor>,
+    mut repeat_in: Local<f32>,
+    mut camera_commands: EventWriter<CameraCommand>,
+) {
+    if !CURSOR_ACTIONS.iter().any(|action| actions.pressed(*action)) {
+        return;
+    }
+    if CURSOR_ACTIONS.iter().any(|action| actions.just_pressed(*action)) {
+        *repeat_in = CURSOR_REPEAT_DELAY;
+    } else {
+        *repeat_in -= time.delta_seconds();
+        if *repeat_in > 0.0 {
+            return;
+        }
+        *repeat_in = CURSOR_REPEAT_INTERVAL;
+    }
+
+    // The first push only brings up the cursor where the player is likely looking
+    let from = match (cursor.mode, cursor.coord) {
+        (CursorMode::Directional, Some(coord)) => coord,
+        _ => {
+            let coord = cursor
+                .coord
+                .or_else(|| selection.primary())
+                .unwrap_or_else(|| grid.index_to_coord(grid.storage_len() / 2));
+            if grid.contains(coord) {
+                cursor.coord = Some(coord);
+                cursor.mode = CursorMode::Directional;
+            }
+            return;
+        }
+    };
+
+    let input = Vec2::new(
+        actions.value(Action::CursorRight) - actions.value(Action::CursorLeft),
+        actions.value(Action::CursorUp) - actions.value(Action::CursorDown),
+    );
+    let yaw = cameras.iter().next().map_or(0.0, |camera| camera.x);
+    let (forward, right) = ground_axes(yaw);
+    let direction = right * input.x + forward * input.y;
+
+    let position = |coord| helpers::hex_world_position(&grid, &planets, &meshes, coord);
+    let from_position = match position(from) {
+        Some(from_position) => from_position,
+        None => return,
+    };
+    let neighbors: Vec<(IVec2, Vec3)> = grid
+        .neighbors(from)
+        .into_iter()
+        .filter_map(|coord| position(coord).map(|neighbor| (coord, neighbor)))
+        .collect();
+    if let Some(next) = helpers::neighbor_in_direction(from_position, &neighbors, direction) {
+        cursor.coord = Some(next);
+        if let Some(target) = position(next) {
+            camera_commands.send(CameraCommand::CenterOn(target));
+        }
+    }
+}
+
+// Double clicking a hex centers the camera on it
+pub fn double_click_focus_system(
+    time: Res<Time>,
+    camera_settings: Res<OrbitCameraSettings>,
+    grid: Res<HexGrid>,
+    planets: Query<(&Handle<Mesh>, &GlobalTransform, &Planet)>,
+    meshes: Res<Assets<Mesh>>,
+    mut clicked_events: EventReader<HexClicked>,
+    mut last_click: Local<Option<(IVec2, f64)>>,
+    mut camera_commands: EventWriter<CameraCommand>,
+) {
+    let now = time.seconds_since_startup();
+    for click in clicked_events.iter() {
+        let double_click = match *last_click {
+            Some((coord, at)) => {
+                coord == click.coord && now - at <= camera_settings.double_click_seconds
+            }
+            None => false,
+        };
+        if double_click {
+            *last_click = None;
+            if let Some(center) =
+                helpers::hex_world_position(&grid, &planets, &meshes, click.coord)
+            {
+                camera_commands.send(CameraCommand::CenterOn(center));
+            }
+        } else {
+            *last_click = Some((click.coord, now));
+        }
+    }
+}
+
+// Looks up the capital of the current player when the camera is asked to focus on it
+pub fn focus_capital_system(
+    mut focus_requests: EventReader<FocusCapital>,
+    grid: Res<HexGrid>,
+    tiles: Query<(&GridPosition, &Team, &Occupant)>,
+    planets: Query<(&Handle<Mesh>, &GlobalTransform, &Planet)>,
+    meshes: Res<Assets<Mesh>>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    mut camera_commands: EventWriter<CameraCommand>,
+) {
+    if focus_requests.iter().count() == 0 {
+        return;
+    }
+    let current_team = turn_order.team_for_turn(state.current().turn);
+    let capital = tiles
+        .iter()
+        .find(|(_, team, occupant)| team.number == current_team && **occupant == Occupant::Capital)
+        .map(|(position, _, _)| position.position);
+    if let Some(center) =
+        capital.and_then(|coord| helpers::hex_world_position(&grid, &planets, &meshes, coord))
+    {
+        camera_commands.send(CameraCommand::CenterOn(center));
+    }
+}
+
+// Numbered camera bookmarks of the current player, saved with the modifier held and restored without
+pub fn camera_bookmark_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    camera_settings: Res<OrbitCameraSettings>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut cameras: Query<&mut OrbitCamera>,
+) {
+    let bindings = &camera_settings.bindings;
+    let slot = match bindings
+        .bookmarks
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))
+    {
+        Some(slot) => slot,
+        None => return,
+    };
+    let saving = bindings
+        .bookmark_save_modifiers
+        .iter()
+        .any(|key| keyboard_input.pressed(*key));
+    let player = turn_order.team_for_turn(state.current().turn);
+
+    for mut camera in cameras.iter_mut() {
+        if saving {
+            bookmarks.save(player, slot, camera.target_pose());
+        } else if let Some(pose) = bookmarks.bookmark(player, slot) {
+            camera.set_target_pose(pose);
+        }
+    }
+}
+
+// Stores the view of the player whose turn ended and brings back the view of the next one
+pub fn player_camera_system(
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut last_player: Local<Option<i32>>,
+    mut cameras: Query<&mut OrbitCamera>,
+) {
+    if !state.is_changed() {
+        return;
+    }
+    let player = turn_order.team_for_turn(state.current().turn);
+    if *last_player == Some(player) {
+        return;
+    }
+
+    for mut camera in cameras.iter_mut() {
+        if let Some(previous) = *last_player {
+            bookmarks.player_mut(previous).current = Some(camera.target_pose());
+        }
+        // Players that never had a turn yet keep looking where the previous player left off
+        if let Some(pose) = bookmarks.player_mut(player).current {
+            camera.set_target_pose(pose);
+        }
+    }
+    *last_player = Some(player);
+}
+
+// Ends the game once a single team is left
+pub fn check_game_over(
     mut commands: Commands,
-    mut positions: Query<(Entity, &mut GridPosition), Without<SelectedTag>>,
-    current_selection: Res<Selection>,
     grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    state: Res<State<GameState>>,
+    mut screen: ResMut<State<AppScreen>>,
+    turn_order: Res<TurnOrder>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    changed_tiles: Query<(), Or<(Changed<Team>, Changed<Occupant>)>>,
+    mut checked_turn: Local<Option<i32>>,
 ) {
-    for (entity, pos) in positions.iter_mut() {
-        if grid.coord_to_index(pos.position) == grid.coord_to_index(current_selection.coords) {
-            commands.entity(entity).insert(SelectedTag);
+    // Sudden death ends the game when a turn begins, conquest as soon as the land changes hands
+    let turn = state.current().turn;
+    if changed_tiles.iter().next().is_none() && *checked_turn == Some(turn) {
+        return;
+    }
+    *checked_turn = Some(turn);
+    let board = helpers::build_board(&grid, &ruleset, &tiles);
+    let rounds = turn / turn_order.teams.len() as i32;
+    if let Some(game_over) = rules::game_over(&board, rounds) {
+        commands.insert_resource(GameResult {
+            winner: game_over.winner,
+            turns: turn + 1,
+        });
+        // Another screen change may already be queued this frame, that one wins
+        if let Err(error) = screen.set(AppScreen::GameOver) {
+            warn!("Could not end the game: {:?}", error);
         }
     }
 }
@@ -0,0 +1,731 @@
+use bevy::math::{IVec2, Vec3};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Everything the gameplay rules need to know about the shape of a map.
+// Cells are addressed by an IVec2 coordinate, which doubles as the texel of the cell in the map_state texture.
+pub trait HexTopology {
+    // Amount of slots in the cell storage, indices are in 0..storage_len()
+    fn storage_len(&self) -> usize;
+
+    // Whether the coordinate addresses an actual cell
+    fn contains(&self, coord: IVec2) -> bool;
+
+    fn coord_to_index(&self, coord: IVec2) -> usize;
+
+    fn index_to_coord(&self, index: usize) -> IVec2;
+
+    fn neighbors(&self, coord: IVec2) -> Vec<IVec2>;
+
+    // The cell across every edge of the cell, in the order the shaders number the edges.
+    // None where the edge lies on the border of the map.
+    fn edges(&self, coord: IVec2) -> Vec<Option<IVec2>> {
+        self.neighbors(coord).into_iter().map(Some).collect()
+    }
+
+    // Maps a coordinate that went around a wrapping edge back onto the map
+    fn wrap(&self, coord: IVec2) -> IVec2 {
+        coord
+    }
+
+    // Amount of steps between two cells, falls back to a breadth first search
+    fn distance(&self, a: IVec2, b: IVec2) -> i32 {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert(a);
+        queue.push_back((a, 0));
+        while let Some((coord, dist)) = queue.pop_front() {
+            if coord == b {
+                return dist;
+            }
+            for neighbor in self.neighbors(coord) {
+                if visited.insert(neighbor) {
+                    queue.push_back((neighbor, dist + 1));
+                }
+            }
+        }
+        i32::MAX
+    }
+
+    fn coords(&self) -> Vec<IVec2> {
+        (0..self.storage_len())
+            .map(|index| self.index_to_coord(index))
+            .filter(|coord| self.contains(*coord))
+            .collect()
+    }
+}
+
+// All cells connected to `start` for which `predicate` holds, including `start` itself
+pub fn flood_fill<T: HexTopology + ?Sized>(
+    topology: &T,
+    start: IVec2,
+    mut predicate: impl FnMut(IVec2) -> bool,
+) -> Vec<IVec2> {
+    let mut region = Vec::new();
+    if !topology.contains(start) || !predicate(start) {
+        return region;
+    }
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(start);
+    queue.push_back(start);
+    while let Some(coord) = queue.pop_front() {
+        region.push(coord);
+        for neighbor in topology.neighbors(coord) {
+            if visited.insert(neighbor) && predicate(neighbor) {
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    region
+}
+
+// Cells at most `range` steps away from `start`, stepping only onto cells for which `passable` holds
+pub fn cells_in_range<T: HexTopology + ?Sized>(
+    topology: &T,
+    start: IVec2,
+    range: i32,
+    mut passable: impl FnMut(IVec2) -> bool,
+) -> Vec<IVec2> {
+    let mut visited = HashMap::new();
+    let mut queue = VecDeque::new();
+    visited.insert(start, 0);
+    queue.push_back(start);
+    while let Some(coord) = queue.pop_front() {
+        let dist = visited[&coord];
+        if dist >= range {
+            continue;
+        }
+        for neighbor in topology.neighbors(coord) {
+            if !visited.contains_key(&neighbor) && passable(neighbor) {
+                visited.insert(neighbor, dist + 1);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    visited.keys().copied().collect()
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize)]
+pub enum WrapMode {
+    None,
+    // Cylinder, the left and right edges are connected
+    Horizontal,
+    // Torus, both the left and right and the top and bottom edges are connected
+    Torus,
+}
+
+impl Default for WrapMode {
+    fn default() -> Self {
+        WrapMode::None
+    }
+}
+
+impl WrapMode {
+    pub fn wraps_x(&self) -> bool {
+        *self != WrapMode::None
+    }
+
+    pub fn wraps_y(&self) -> bool {
+        *self == WrapMode::Torus
+    }
+}
+
+// Flat map in doubled coordinates, matching the hex math in the shader.
+// Hex centers only exist where x + y is even, neighbors are 2 steps apart horizontally and 1 step diagonally.
+// Wrapping edges need an even size in that direction, otherwise the hexes on both sides would not line up.
+pub struct FlatHexTopology {
+    pub width: i32,
+    pub height: i32,
+    pub wrap: WrapMode,
+}
+
+impl FlatHexTopology {
+    pub fn new(width: i32, height: i32, wrap: WrapMode) -> Self {
+        assert!(
+            (!wrap.wraps_x() || width % 2 == 0) && (!wrap.wraps_y() || height % 2 == 0),
+            "A wrapping hex grid needs an even size along the wrapping axis"
+        );
+        Self {
+            width,
+            height,
+            wrap,
+        }
+    }
+}
+
+const FLAT_NEIGHBOR_OFFSETS: [(i32, i32); 6] = [(2, 0), (1, 1), (-1, 1), (-2, 0), (-1, -1), (1, -1)];
+
+impl HexTopology for FlatHexTopology {
+    fn storage_len(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    fn contains(&self, coord: IVec2) -> bool {
+        coord.x >= 0
+            && coord.y >= 0
+            && coord.x < self.width
+            && coord.y < self.height
+            && (coord.x + coord.y) % 2 == 0
+    }
+
+    fn coord_to_index(&self, coord: IVec2) -> usize {
+        (coord.y * self.width + coord.x) as usize
+    }
+
+    fn index_to_coord(&self, index: usize) -> IVec2 {
+        IVec2::new(index as i32 % self.width, index as i32 / self.width)
+    }
+
+    fn neighbors(&self, coord: IVec2) -> Vec<IVec2> {
+        self.edges(coord).into_iter().flatten().collect()
+    }
+
+    // Edge i faces the neighbor at 60 * i degrees, counter clockwise starting from +x
+    fn edges(&self, coord: IVec2) -> Vec<Option<IVec2>> {
+        FLAT_NEIGHBOR_OFFSETS
+            .iter()
+            .map(|(x, y)| self.wrap(coord + IVec2::new(*x, *y)))
+            .map(|neighbor| Some(neighbor).filter(|neighbor| self.contains(*neighbor)))
+            .collect()
+    }
+
+    fn wrap(&self, coord: IVec2) -> IVec2 {
+        IVec2::new(
+            if self.wrap.wraps_x() {
+                coord.x.rem_euclid(self.width)
+            } else {
+                coord.x
+            },
+            if self.wrap.wraps_y() {
+                coord.y.rem_euclid(self.height)
+            } else {
+                coord.y
+            },
+        )
+    }
+
+    fn distance(&self, a: IVec2, b: IVec2) -> i32 {
+        let a = self.wrap(a);
+        let b = self.wrap(b);
+        let mut dx = (a.x - b.x).abs();
+        let mut dy = (a.y - b.y).abs();
+        // Going around the edge might be shorter
+        if self.wrap.wraps_x() {
+            dx = dx.min(self.width - dx);
+        }
+        if self.wrap.wraps_y() {
+            dy = dy.min(self.height - dy);
+        }
+        dy + (dx - dy).max(0) / 2
+    }
+}
+
+// Goldberg polyhedron: the dual of a subdivided icosahedron.
+// Every vertex of the subdivided icosahedron becomes a cell, the 12 original vertices become pentagons and all others hexagons.
+pub struct GoldbergSphere {
+    pub subdivisions: u32,
+    // Unit vectors pointing at the center of every cell
+    pub centers: Vec<Vec3>,
+    pub neighbors: Vec<Vec<usize>>,
+    // Outline of every cell on the unit sphere, counter clockwise seen from the outside
+    pub corners: Vec<Vec<Vec3>>,
+    // The neighbor across the edge from corner k to corner k + 1
+    pub edge_neighbors: Vec<Vec<usize>>,
+    // Layout of the cells in the map_state texture
+    pub texture_width: i32,
+}
+
+const ICOSAHEDRON_FACES: [[usize; 3]; 20] = [
+    [0, 11, 5],
+    [0, 5, 1],
+    [0, 1, 7],
+    [0, 7, 10],
+    [0, 10, 11],
+    [1, 5, 9],
+    [5, 11, 4],
+    [11, 10, 2],
+    [10, 7, 6],
+    [7, 1, 8],
+    [3, 9, 4],
+    [3, 4, 2],
+    [3, 2, 6],
+    [3, 6, 8],
+    [3, 8, 9],
+    [4, 9, 5],
+    [2, 4, 11],
+    [6, 2, 10],
+    [8, 6, 7],
+    [9, 8, 1],
+];
+
+fn icosahedron_vertices() -> [Vec3; 12] {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    [
+        Vec3::new(-1.0, t, 0.0),
+        Vec3::new(1.0, t, 0.0),
+        Vec3::new(-1.0, -t, 0.0),
+        Vec3::new(1.0, -t, 0.0),
+        Vec3::new(0.0, -1.0, t),
+        Vec3::new(0.0, 1.0, t),
+        Vec3::new(0.0, -1.0, -t),
+        Vec3::new(0.0, 1.0, -t),
+        Vec3::new(t, 0.0, -1.0),
+        Vec3::new(t, 0.0, 1.0),
+        Vec3::new(-t, 0.0, -1.0),
+        Vec3::new(-t, 0.0, 1.0),
+    ]
+}
+
+impl GoldbergSphere {
+    // `subdivisions` is the amount of segments every icosahedron edge is split in, giving 10 * n^2 + 2 cells
+    pub fn new(subdivisions: u32) -> Self {
+        let n = subdivisions.max(1) as usize;
+        let ico = icosahedron_vertices();
+
+        // Subdivide every face, sharing the vertices on the edges between faces
+        let mut centers: Vec<Vec3> = Vec::new();
+        let mut lookup: HashMap<(i64, i64, i64), usize> = HashMap::new();
+        let mut triangles: Vec<[usize; 3]> = Vec::new();
+        for face in ICOSAHEDRON_FACES.iter() {
+            let (a, b, c) = (ico[face[0]], ico[face[1]], ico[face[2]]);
+            let mut local = vec![vec![0usize; n + 1]; n + 1];
+            for (i, row) in local.iter_mut().enumerate() {
+                for (j, vertex) in row.iter_mut().enumerate().take(n + 1 - i) {
+                    let p = (a + (b - a) * (i as f32 / n as f32) + (c - a) * (j as f32 / n as f32))
+                        .normalize();
+                    let key = (
+                        (p.x * 100_000.0).round() as i64,
+                        (p.y * 100_000.0).round() as i64,
+                        (p.z * 100_000.0).round() as i64,
+                    );
+                    *vertex = *lookup.entry(key).or_insert_with(|| {
+                        centers.push(p);
+                        centers.len() - 1
+                    });
+                }
+            }
+            for (i, (row, next_row)) in local.iter().zip(local.iter().skip(1)).enumerate() {
+                for j in 0..(n - i) {
+                    triangles.push([row[j], next_row[j], row[j + 1]]);
+                    if j + 1 < n - i {
+                        triangles.push([next_row[j], next_row[j + 1], row[j + 1]]);
+                    }
+                }
+            }
+        }
+
+        let mut neighbor_sets: Vec<HashSet<usize>> = vec![HashSet::new(); centers.len()];
+        let mut cell_triangles: Vec<Vec<usize>> = vec![Vec::new(); centers.len()];
+        for (triangle_index, triangle) in triangles.iter().enumerate() {
+            for k in 0..3 {
+                let (from, to) = (triangle[k], triangle[(k + 1) % 3]);
+                neighbor_sets[from].insert(to);
+                neighbor_sets[to].insert(from);
+                cell_triangles[triangle[k]].push(triangle_index);
+            }
+        }
+
+        let mut neighbors = Vec::with_capacity(centers.len());
+        let mut corners = Vec::with_capacity(centers.len());
+        for (cell, center) in centers.iter().enumerate() {
+            let mut cell_neighbors: Vec<usize> = neighbor_sets[cell].iter().copied().collect();
+            sort_around(*center, &mut cell_neighbors, |neighbor| centers[*neighbor]);
+            neighbors.push(cell_neighbors);
+
+            let mut cell_corners: Vec<Vec3> = cell_triangles[cell]
+                .iter()
+                .map(|triangle_index| {
+                    let [a, b, c] = triangles[*triangle_index];
+                    (centers[a] + centers[b] + centers[c]).normalize()
+                })
+                .collect();
+            sort_around(*center, &mut cell_corners, |corner| *corner);
+            corners.push(cell_corners);
+        }
+
+        // The neighbor sharing an edge lies in the direction of the middle of that edge
+        let edge_neighbors = corners
+            .iter()
+            .enumerate()
+            .map(|(cell, cell_corners)| {
+                (0..cell_corners.len())
+                    .map(|k| {
+                        let middle = cell_corners[k] + cell_corners[(k + 1) % cell_corners.len()];
+                        *neighbors[cell]
+                            .iter()
+                            .max_by(|a, b| {
+                                centers[**a]
+                                    .dot(middle)
+                                    .partial_cmp(&centers[**b].dot(middle))
+                                    .unwrap()
+                            })
+                            .unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let texture_width = (centers.len() as f32).sqrt().ceil() as i32;
+        Self {
+            subdivisions: n as u32,
+            centers,
+            neighbors,
+            corners,
+            edge_neighbors,
+            texture_width,
+        }
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn texture_height(&self) -> i32 {
+        (self.cell_count() as i32 + self.texture_width - 1) / self.texture_width
+    }
+
+    pub fn is_pentagon(&self, index: usize) -> bool {
+        self.neighbors[index].len() == 5
+    }
+
+    // Cell whose center is closest to `direction`, found by walking towards it over the neighbors
+    pub fn nearest_cell(&self, direction: Vec3) -> usize {
+        let direction = direction.normalize();
+        let mut current = 0;
+        let mut best = self.centers[current].dot(direction);
+        loop {
+            let mut improved = false;
+            for neighbor in self.neighbors[current].iter() {
+                let score = self.centers[*neighbor].dot(direction);
+                if score > best {
+                    best = score;
+                    current = *neighbor;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+}
+
+// Sorts items by their angle around `axis`, counter clockwise seen from the tip of the axis
+fn sort_around<T>(axis: Vec3, items: &mut [T], position: impl Fn(&T) -> Vec3) {
+    let helper = if axis.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+    let tangent = axis.cross(helper).normalize();
+    let bitangent = axis.cross(tangent);
+    let angle = |item: &T| {
+        let offset = position(item) - axis;
+        offset.dot(bitangent).atan2(offset.dot(tangent))
+    };
+    items.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+}
+
+impl HexTopology for GoldbergSphere {
+    fn storage_len(&self) -> usize {
+        (self.texture_width * self.texture_height()) as usize
+    }
+
+    fn contains(&self, coord: IVec2) -> bool {
+        coord.x >= 0
+            && coord.y >= 0
+            && coord.x < self.texture_width
+            && self.coord_to_index(coord) < self.cell_count()
+    }
+
+    fn coord_to_index(&self, coord: IVec2) -> usize {
+        (coord.y * self.texture_width + coord.x) as usize
+    }
+
+    fn index_to_coord(&self, index: usize) -> IVec2 {
+        IVec2::new(
+            index as i32 % self.texture_width,
+            index as i32 / self.texture_width,
+        )
+    }
+
+    fn neighbors(&self, coord: IVec2) -> Vec<IVec2> {
+        if !self.contains(coord) {
+            return Vec::new();
+        }
+        self.neighbors[self.coord_to_index(coord)]
+            .iter()
+            .map(|index| self.index_to_coord(*index))
+            .collect()
+    }
+
+    // Edge k runs from corner k to corner k + 1, matching the triangle fans of the sphere mesh
+    fn edges(&self, coord: IVec2) -> Vec<Option<IVec2>> {
+        if !self.contains(coord) {
+            return Vec::new();
+        }
+        self.edge_neighbors[self.coord_to_index(coord)]
+            .iter()
+            .map(|index| Some(self.index_to_coord(*index)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every neighbor relation goes both ways
+    fn assert_symmetric_neighbors(topology: &impl HexTopology) {
+        for coord in topology.coords() {
+            for neighbor in topology.neighbors(coord) {
+                assert!(
+                    topology.contains(neighbor),
+                    "{} has {} outside the map",
+                    coord,
+                    neighbor
+                );
+                assert!(
+                    topology.neighbors(neighbor).contains(&coord),
+                    "{} is a neighbor of {} but not the other way around",
+                    neighbor,
+                    coord
+                );
+            }
+        }
+    }
+
+    // Distance found by growing the range until `b` is in it
+    fn walked_distance(topology: &impl HexTopology, a: IVec2, b: IVec2) -> i32 {
+        (0..)
+            .find(|range| cells_in_range(topology, a, *range, |_| true).contains(&b))
+            .unwrap()
+    }
+
+    #[test]
+    fn goldberg_sphere_has_twelve_pentagons_and_hexagons_elsewhere() {
+        for subdivisions in 1..=4 {
+            let sphere = GoldbergSphere::new(subdivisions);
+            let n = subdivisions as usize;
+            assert_eq!(sphere.cell_count(), 10 * n * n + 2);
+            let pentagons = (0..sphere.cell_count())
+                .filter(|index| sphere.is_pentagon(*index))
+                .count();
+            assert_eq!(pentagons, 12);
+            for index in 0..sphere.cell_count() {
+                let degree = sphere.neighbors[index].len();
+                assert!(degree == 5 || degree == 6);
+                assert_eq!(sphere.corners[index].len(), degree);
+                assert_eq!(sphere.edge_neighbors[index].len(), degree);
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_are_symmetric() {
+        assert_symmetric_neighbors(&FlatHexTopology::new(9, 7, WrapMode::None));
+        assert_symmetric_neighbors(&GoldbergSphere::new(3));
+    }
+
+    #[test]
+    fn flat_edges_follow_the_shader_order_and_stop_at_the_border() {
+        let grid = FlatHexTopology::new(8, 6, WrapMode::None);
+        let inside = IVec2::new(3, 3);
+        let expected: Vec<Option<IVec2>> = FLAT_NEIGHBOR_OFFSETS
+            .iter()
+            .map(|(x, y)| Some(inside + IVec2::new(*x, *y)))
+            .collect();
+        assert_eq!(grid.edges(inside), expected);
+
+        let corner = grid.edges(IVec2::new(0, 0));
+        assert_eq!(corner.len(), 6);
+        assert_eq!(corner[0], Some(IVec2::new(2, 0)));
+        assert_eq!(corner[1], Some(IVec2::new(1, 1)));
+        assert!(corner[2..].iter().all(|edge| edge.is_none()));
+    }
+
+    #[test]
+    fn sphere_edges_lie_between_the_corners_they_connect() {
+        let sphere = GoldbergSphere::new(3);
+        for coord in sphere.coords() {
+            let index = sphere.coord_to_index(coord);
+            let edges: Vec<IVec2> = sphere.edges(coord).into_iter().flatten().collect();
+            let mut sorted_edges = edges.clone();
+            let mut neighbors = sphere.neighbors(coord);
+            sorted_edges.sort_by_key(|coord| (coord.x, coord.y));
+            neighbors.sort_by_key(|coord| (coord.x, coord.y));
+            assert_eq!(sorted_edges, neighbors);
+
+            // Both corners of edge k are corners of the cell across it
+            let corners = &sphere.corners[index];
+            for (k, neighbor) in edges.iter().enumerate() {
+                let across = &sphere.corners[sphere.coord_to_index(*neighbor)];
+                for corner in [corners[k], corners[(k + 1) % corners.len()]].iter() {
+                    assert!(across.iter().any(|other| other.distance(*corner) < 1e-4));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_cells_are_found_from_their_own_center() {
+        let sphere = GoldbergSphere::new(4);
+        for (index, center) in sphere.centers.iter().enumerate() {
+            assert_eq!(sphere.nearest_cell(*center), index);
+        }
+    }
+
+    #[test]
+    fn flat_distance_counts_hex_steps() {
+        let grid = FlatHexTopology::new(12, 12, WrapMode::None);
+        let origin = IVec2::new(4, 4);
+        assert_eq!(grid.distance(origin, origin), 0);
+        for neighbor in grid.neighbors(origin) {
+            assert_eq!(grid.distance(origin, neighbor), 1);
+        }
+        assert_eq!(grid.distance(origin, IVec2::new(8, 4)), 2);
+        assert_eq!(grid.distance(origin, IVec2::new(6, 8)), 4);
+        assert_eq!(grid.distance(origin, IVec2::new(10, 6)), 4);
+        // The closed form agrees with walking over the neighbors
+        for coord in grid.coords() {
+            assert_eq!(
+                grid.distance(origin, coord),
+                walked_distance(&grid, origin, coord)
+            );
+            assert_eq!(grid.distance(origin, coord), grid.distance(coord, origin));
+        }
+    }
+
+    #[test]
+    fn sphere_distance_walks_over_the_neighbors() {
+        let sphere = GoldbergSphere::new(2);
+        let origin = sphere.index_to_coord(0);
+        assert_eq!(sphere.distance(origin, origin), 0);
+        for neighbor in sphere.neighbors(origin) {
+            assert_eq!(sphere.distance(origin, neighbor), 1);
+            for second in sphere.neighbors(neighbor) {
+                assert!(sphere.distance(origin, second) <= 2);
+            }
+        }
+        for coord in sphere.coords() {
+            assert_eq!(
+                sphere.distance(origin, coord),
+                walked_distance(&sphere, origin, coord)
+            );
+            assert_eq!(
+                sphere.distance(coord, origin),
+                sphere.distance(origin, coord)
+            );
+        }
+    }
+
+    #[test]
+    fn flood_fill_stays_inside_the_predicate() {
+        let grid = FlatHexTopology::new(10, 10, WrapMode::None);
+        let region = flood_fill(&grid, IVec2::new(0, 0), |coord| coord.x < 4);
+        assert!(!region.is_empty());
+        assert!(region.iter().all(|coord| coord.x < 4));
+        assert_eq!(
+            region.len(),
+            grid.coords().iter().filter(|coord| coord.x < 4).count()
+        );
+        assert!(flood_fill(&grid, IVec2::new(6, 0), |coord| coord.x < 4).is_empty());
+        // Nothing is reached from a coordinate that isn't a cell
+        assert!(flood_fill(&grid, IVec2::new(1, 0), |_| true).is_empty());
+
+        let sphere = GoldbergSphere::new(3);
+        let everything = flood_fill(&sphere, sphere.index_to_coord(0), |_| true);
+        assert_eq!(everything.len(), sphere.cell_count());
+    }
+
+    #[test]
+    fn cells_in_range_grows_in_rings() {
+        let grid = FlatHexTopology::new(20, 20, WrapMode::None);
+        let center = IVec2::new(10, 10);
+        assert_eq!(cells_in_range(&grid, center, 0, |_| true).len(), 1);
+        assert_eq!(cells_in_range(&grid, center, 1, |_| true).len(), 7);
+        assert_eq!(cells_in_range(&grid, center, 2, |_| true).len(), 19);
+        // Blocked cells are neither entered nor walked through
+        let blocked = IVec2::new(12, 10);
+        let reached = cells_in_range(&grid, center, 2, |coord| coord != blocked);
+        assert!(!reached.contains(&blocked));
+        assert!(!reached.contains(&IVec2::new(14, 10)));
+        assert_eq!(reached.len(), 17);
+
+        let sphere = GoldbergSphere::new(3);
+        let pentagon = (0..sphere.cell_count())
+            .find(|index| sphere.is_pentagon(*index))
+            .unwrap();
+        let around = cells_in_range(&sphere, sphere.index_to_coord(pentagon), 1, |_| true);
+        assert_eq!(around.len(), 6);
+    }
+
+    #[test]
+    fn horizontal_wrap_connects_left_and_right() {
+        let grid = FlatHexTopology::new(10, 6, WrapMode::Horizontal);
+        let left = IVec2::new(0, 2);
+        let neighbors = grid.neighbors(left);
+        assert_eq!(neighbors.len(), 6);
+        assert!(neighbors.contains(&IVec2::new(8, 2)));
+        assert!(neighbors.contains(&IVec2::new(9, 1)));
+        assert!(neighbors.contains(&IVec2::new(9, 3)));
+        // The top and bottom stay borders
+        assert_eq!(grid.neighbors(IVec2::new(4, 0)).len(), 4);
+        assert_symmetric_neighbors(&grid);
+
+        assert_eq!(grid.distance(left, IVec2::new(8, 2)), 1);
+        assert_eq!(grid.distance(IVec2::new(1, 1), IVec2::new(9, 1)), 1);
+        assert_eq!(grid.distance(left, IVec2::new(6, 2)), 2);
+        assert_eq!(grid.wrap(IVec2::new(-2, 2)), IVec2::new(8, 2));
+        for coord in grid.coords() {
+            assert_eq!(
+                grid.distance(left, coord),
+                walked_distance(&grid, left, coord)
+            );
+        }
+
+        // A strip along the seam is one region
+        let seam = |coord: IVec2| coord.x <= 1 || coord.x >= 8;
+        let region = flood_fill(&grid, left, seam);
+        assert_eq!(
+            region.len(),
+            grid.coords()
+                .into_iter()
+                .filter(|coord| seam(*coord))
+                .count()
+        );
+        assert!(region.contains(&IVec2::new(9, 5)));
+    }
+
+    #[test]
+    fn torus_wrap_connects_both_axes() {
+        let grid = FlatHexTopology::new(10, 8, WrapMode::Torus);
+        for coord in grid.coords() {
+            assert_eq!(grid.neighbors(coord).len(), 6);
+        }
+        assert_symmetric_neighbors(&grid);
+
+        let corner = IVec2::new(0, 0);
+        assert!(grid.neighbors(corner).contains(&IVec2::new(9, 7)));
+        assert!(grid.neighbors(corner).contains(&IVec2::new(1, 7)));
+        assert_eq!(grid.distance(corner, IVec2::new(9, 7)), 1);
+        assert_eq!(grid.distance(corner, IVec2::new(0, 6)), 2);
+        assert_eq!(grid.wrap(IVec2::new(-1, -1)), IVec2::new(9, 7));
+        for coord in grid.coords() {
+            assert_eq!(
+                grid.distance(corner, coord),
+                walked_distance(&grid, corner, coord)
+            );
+        }
+
+        // The four corners meet, so a region around them is connected over both seams
+        let corners =
+            |coord: IVec2| (coord.x <= 1 || coord.x >= 8) && (coord.y <= 1 || coord.y >= 6);
+        let region = flood_fill(&grid, corner, corners);
+        assert_eq!(
+            region.len(),
+            grid.coords()
+                .into_iter()
+                .filter(|coord| corners(*coord))
+                .count()
+        );
+    }
+}
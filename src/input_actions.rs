@@ -0,0 +1,547 @@
+// Named actions between the physical inputs and the systems that react to them, so every input can be rebound
+use bevy::input::mouse::MouseScrollUnit::Line;
+use bevy::input::mouse::MouseWheel;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    // Picks the tile under the pointer, moves the selected unit there or drops a purchase
+    Select,
+    // Held to add tiles to the selection instead of replacing it
+    MultiSelect,
+    // Drops the selection and the purchase being placed
+    Cancel,
+    EndTurn,
+    // Takes back the last move or purchase of this turn
+    Undo,
+    // Held to rotate the camera by moving the pointer
+    RotateCamera,
+    // Axis, positive zooms in
+    Zoom,
+    // Selects the next unit that can still move, or the previous one
+    CycleUnits,
+    CycleUnitsBack,
+    Pause,
+    // Move the hex cursor to a neighbor, relative to the camera
+    CursorUp,
+    CursorDown,
+    CursorLeft,
+    CursorRight,
+    // Rates, turn the camera while held
+    CameraLeft,
+    CameraRight,
+    CameraUp,
+    CameraDown,
+}
+
+// Actions that can be rebound by pressing an input, the others are driven by axes
+pub const REBINDABLE_ACTIONS: [Action; 13] = [
+    Action::Select,
+    Action::MultiSelect,
+    Action::Cancel,
+    Action::EndTurn,
+    Action::Undo,
+    Action::RotateCamera,
+    Action::CycleUnits,
+    Action::CycleUnitsBack,
+    Action::Pause,
+    Action::CursorUp,
+    Action::CursorDown,
+    Action::CursorLeft,
+    Action::CursorRight,
+];
+
+// How far a stick has to be pushed to count as a press
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    // The second key pressed while the first one is held
+    Chord(KeyCode, KeyCode),
+    Mouse(MouseButton),
+    // On any connected gamepad
+    GamepadButton(GamepadButtonType),
+    // Lines scrolled this frame, touchpad scrolling in pixels is handled by the camera
+    MouseWheel,
+    // One half of a stick axis, on any connected gamepad
+    GamepadAxis(GamepadAxisType, AxisDirection),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionBindings {
+    pub bindings: HashMap<Action, Vec<InputBinding>>,
+}
+
+impl Default for ActionBindings {
+    fn default() -> Self {
+        use InputBinding::*;
+        let bindings = vec![
+            (
+                Action::Select,
+                vec![
+                    Mouse(MouseButton::Left),
+                    Key(KeyCode::Space),
+                    GamepadButton(GamepadButtonType::South),
+                ],
+            ),
+            (
+                Action::MultiSelect,
+                vec![Key(KeyCode::LShift), Key(KeyCode::RShift)],
+            ),
+            (
+                Action::Cancel,
+                vec![Key(KeyCode::Back), GamepadButton(GamepadButtonType::East)],
+            ),
+            (
+                Action::EndTurn,
+                vec![
+                    Key(KeyCode::Return),
+                    GamepadButton(GamepadButtonType::North),
+                ],
+            ),
+            (
+                Action::Undo,
+                vec![
+                    Chord(KeyCode::LControl, KeyCode::Z),
+                    Chord(KeyCode::RControl, KeyCode::Z),
+                    GamepadButton(GamepadButtonType::West),
+                ],
+            ),
+            // Left is taken by selection
+            (
+                Action::RotateCamera,
+                vec![Mouse(MouseButton::Right), Mouse(MouseButton::Middle)],
+            ),
+            (Action::Zoom, vec![MouseWheel]),
+            (
+                Action::CycleUnits,
+                vec![
+                    Key(KeyCode::Tab),
+                    GamepadButton(GamepadButtonType::RightTrigger),
+                ],
+            ),
+            (
+                Action::CycleUnitsBack,
+                vec![
+                    Chord(KeyCode::LShift, KeyCode::Tab),
+                    Chord(KeyCode::RShift, KeyCode::Tab),
+                    GamepadButton(GamepadButtonType::LeftTrigger),
+                ],
+            ),
+            (
+                Action::Pause,
+                vec![
+                    Key(KeyCode::Escape),
+                    GamepadButton(GamepadButtonType::Start),
+                ],
+            ),
+            (
+                Action::CursorUp,
+                vec![
+                    Key(KeyCode::Up),
+                    GamepadButton(GamepadButtonType::DPadUp),
+                    GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Positive),
+                ],
+            ),
+            (
+                Action::CursorDown,
+                vec![
+                    Key(KeyCode::Down),
+                    GamepadButton(GamepadButtonType::DPadDown),
+                    GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Negative),
+                ],
+            ),
+            (
+                Action::CursorLeft,
+                vec![
+                    Key(KeyCode::Left),
+                    GamepadButton(GamepadButtonType::DPadLeft),
+                    GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Negative),
+                ],
+            ),
+            (
+                Action::CursorRight,
+                vec![
+                    Key(KeyCode::Right),
+                    GamepadButton(GamepadButtonType::DPadRight),
+                    GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Positive),
+                ],
+            ),
+            (
+                Action::CameraLeft,
+                vec![GamepadAxis(
+                    GamepadAxisType::RightStickX,
+                    AxisDirection::Negative,
+                )],
+            ),
+            (
+                Action::CameraRight,
+                vec![GamepadAxis(
+                    GamepadAxisType::RightStickX,
+                    AxisDirection::Positive,
+                )],
+            ),
+            (
+                Action::CameraUp,
+                vec![GamepadAxis(
+                    GamepadAxisType::RightStickY,
+                    AxisDirection::Positive,
+                )],
+            ),
+            (
+                Action::CameraDown,
+                vec![GamepadAxis(
+                    GamepadAxisType::RightStickY,
+                    AxisDirection::Negative,
+                )],
+            ),
+        ];
+        ActionBindings {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl ActionBindings {
+    pub fn get(&self, action: Action) -> &[InputBinding] {
+        self.bindings
+            .get(&action)
+            .map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    // Actions missing from a settings file written by an older version get their default bindings
+    pub fn with_defaults(mut self) -> Self {
+        for (action, bindings) in ActionBindings::default().bindings {
+            self.bindings.entry(action).or_insert(bindings);
+        }
+        self
+    }
+}
+
+// What the actions did this frame
+#[derive(Default, Debug)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    just_released: HashSet<Action>,
+    values: HashMap<Action, f32>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    // Sum of the axes bound to the action, 1 for every held button
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+}
+
+// Gamepads that are plugged in, bevy doesn't keep track of them
+#[derive(Default, Debug)]
+pub struct Gamepads {
+    pub connected: Vec<Gamepad>,
+}
+
+// Raw input of one frame, the state of a single binding is read from it
+struct RawInput<'a> {
+    keyboard: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    gamepad: &'a Input<GamepadButton>,
+    gamepad_axes: &'a Axis<GamepadAxis>,
+    gamepads: &'a Gamepads,
+    wheel_lines: f32,
+}
+
+struct BindingState {
+    pressed: bool,
+    just_pressed: bool,
+    just_released: bool,
+    value: f32,
+}
+
+fn gamepad_matches<'a>(
+    mut buttons: impl Iterator<Item = &'a GamepadButton>,
+    button_type: GamepadButtonType,
+) -> bool {
+    buttons.any(|button| button.1 == button_type)
+}
+
+fn binding_state(binding: InputBinding, input: &RawInput) -> BindingState {
+    let (pressed, just_pressed, just_released) = match binding {
+        InputBinding::Key(key) => (
+            input.keyboard.pressed(key),
+            input.keyboard.just_pressed(key),
+            input.keyboard.just_released(key),
+        ),
+        InputBinding::Chord(modifier, key) => {
+            let held = input.keyboard.pressed(modifier);
+            (
+                held && input.keyboard.pressed(key),
+                held && input.keyboard.just_pressed(key),
+                input.keyboard.just_released(key),
+            )
+        }
+        InputBinding::Mouse(button) => (
+            input.mouse.pressed(button),
+            input.mouse.just_pressed(button),
+            input.mouse.just_released(button),
+        ),
+        InputBinding::GamepadButton(button_type) => (
+            gamepad_matches(input.gamepad.get_pressed(), button_type),
+            gamepad_matches(input.gamepad.get_just_pressed(), button_type),
+            gamepad_matches(input.gamepad.get_just_released(), button_type),
+        ),
+        InputBinding::MouseWheel => {
+            return BindingState {
+                pressed: input.wheel_lines != 0.0,
+                just_pressed: input.wheel_lines != 0.0,
+                just_released: false,
+                value: input.wheel_lines,
+            }
+        }
+        // Presses of axes are found by comparing with the previous frame
+        InputBinding::GamepadAxis(axis_type, direction) => {
+            let sign = match direction {
+                AxisDirection::Positive => 1.0,
+                AxisDirection::Negative => -1.0,
+            };
+            let value = input
+                .gamepads
+                .connected
+                .iter()
+                .filter_map(|gamepad| input.gamepad_axes.get(GamepadAxis(*gamepad, axis_type)))
+                .map(|value| (value * sign).max(0.0))
+                .fold(0.0, f32::max);
+            return BindingState {
+                pressed: value >= AXIS_PRESS_THRESHOLD,
+                just_pressed: false,
+                just_released: false,
+                value,
+            };
+        }
+    };
+    BindingState {
+        pressed,
+        just_pressed,
+        just_released,
+        value: pressed as i32 as f32,
+    }
+}
+
+fn track_gamepads(mut gamepad_events: EventReader<GamepadEvent>, mut gamepads: ResMut<Gamepads>) {
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                if !gamepads.connected.contains(gamepad) {
+                    gamepads.connected.push(*gamepad);
+                }
+            }
+            GamepadEventType::Disconnected => gamepads.connected.retain(|other| other != gamepad),
+            _ => {}
+        }
+    }
+}
+
+fn update_action_state(
+    bindings: Res<ActionBindings>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut state: ResMut<ActionState>,
+) {
+    let input = RawInput {
+        keyboard: &keyboard,
+        mouse: &mouse,
+        gamepad: &gamepad,
+        gamepad_axes: &gamepad_axes,
+        gamepads: &gamepads,
+        wheel_lines: mouse_wheel_events
+            .iter()
+            .filter(|event| matches!(event.unit, Line))
+            .map(|event| event.y)
+            .sum(),
+    };
+
+    *state = next_action_state(&bindings, &input, &state);
+}
+
+fn next_action_state(
+    bindings: &ActionBindings,
+    input: &RawInput,
+    previous: &ActionState,
+) -> ActionState {
+    let mut new_state = ActionState::default();
+    for (action, action_bindings) in bindings.bindings.iter() {
+        for binding in action_bindings.iter() {
+            let binding = binding_state(*binding, input);
+            if binding.pressed {
+                new_state.pressed.insert(*action);
+            }
+            if binding.just_pressed {
+                new_state.just_pressed.insert(*action);
+            }
+            if binding.just_released {
+                new_state.just_released.insert(*action);
+            }
+            *new_state.values.entry(*action).or_insert(0.0) += binding.value;
+        }
+    }
+    // Still held through another binding, so not released
+    let still_pressed = new_state.pressed.clone();
+    new_state
+        .just_released
+        .retain(|action| !still_pressed.contains(action));
+    // Sticks have no just_pressed of their own and a chord also ends when its modifier is let go,
+    // both only show against the last frame
+    for action in new_state.pressed.difference(&previous.pressed) {
+        new_state.just_pressed.insert(*action);
+    }
+    for action in previous.pressed.difference(&new_state.pressed) {
+        new_state.just_released.insert(*action);
+    }
+    new_state
+}
+
+pub struct InputActionsPlugin;
+
+impl Plugin for InputActionsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ActionBindings>()
+            .init_resource::<ActionState>()
+            .init_resource::<Gamepads>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                track_gamepads
+                    .system()
+                    .label("track_gamepads")
+                    .after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_action_state.system().after("track_gamepads"),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Button and key state that is fed to the actions, cleared between frames like bevy does
+    #[derive(Default)]
+    struct FakeInput {
+        keyboard: Input<KeyCode>,
+        mouse: Input<MouseButton>,
+        gamepad: Input<GamepadButton>,
+        gamepad_axes: Axis<GamepadAxis>,
+        gamepads: Gamepads,
+        state: ActionState,
+    }
+
+    impl FakeInput {
+        fn update(&mut self, bindings: &ActionBindings) -> &ActionState {
+            let input = RawInput {
+                keyboard: &self.keyboard,
+                mouse: &self.mouse,
+                gamepad: &self.gamepad,
+                gamepad_axes: &self.gamepad_axes,
+                gamepads: &self.gamepads,
+                wheel_lines: 0.0,
+            };
+            self.state = next_action_state(bindings, &input, &self.state);
+            self.keyboard.update();
+            self.mouse.update();
+            self.gamepad.update();
+            &self.state
+        }
+    }
+
+    #[test]
+    fn chords_need_the_modifier_held() {
+        let bindings = ActionBindings::default();
+        let mut input = FakeInput::default();
+
+        input.keyboard.press(KeyCode::Z);
+        assert!(!input.update(&bindings).pressed(Action::Undo));
+        input.keyboard.release(KeyCode::Z);
+        input.update(&bindings);
+
+        input.keyboard.press(KeyCode::LControl);
+        assert!(!input.update(&bindings).pressed(Action::Undo));
+        input.keyboard.press(KeyCode::Z);
+        let state = input.update(&bindings);
+        assert!(state.just_pressed(Action::Undo));
+        assert!(state.pressed(Action::Undo));
+
+        input.keyboard.release(KeyCode::LControl);
+        let state = input.update(&bindings);
+        assert!(!state.pressed(Action::Undo));
+        assert!(state.just_released(Action::Undo));
+    }
+
+    #[test]
+    fn actions_are_released_when_the_last_binding_is() {
+        let bindings = ActionBindings::default();
+        let mut input = FakeInput::default();
+
+        let south = GamepadButton(Gamepad(0), GamepadButtonType::South);
+        input.mouse.press(MouseButton::Left);
+        input.gamepad.press(south);
+        let state = input.update(&bindings);
+        assert!(state.just_pressed(Action::Select));
+        assert_eq!(state.value(Action::Select), 2.0);
+
+        input.gamepad.release(south);
+        let state = input.update(&bindings);
+        assert!(state.pressed(Action::Select));
+        assert!(!state.just_pressed(Action::Select));
+        assert!(!state.just_released(Action::Select));
+
+        input.mouse.release(MouseButton::Left);
+        let state = input.update(&bindings);
+        assert!(!state.pressed(Action::Select));
+        assert!(state.just_released(Action::Select));
+
+        let state = input.update(&bindings);
+        assert!(!state.just_released(Action::Select));
+    }
+
+    #[test]
+    fn with_defaults_only_fills_in_missing_actions() {
+        let mut bindings = ActionBindings {
+            bindings: HashMap::new(),
+        };
+        let undo = vec![InputBinding::Key(KeyCode::U)];
+        bindings.bindings.insert(Action::Undo, undo.clone());
+
+        let bindings = bindings.with_defaults();
+        let defaults = ActionBindings::default();
+        assert_eq!(bindings.get(Action::Undo), undo.as_slice());
+        assert_eq!(bindings.get(Action::Select), defaults.get(Action::Select));
+        assert_eq!(bindings.bindings.len(), defaults.bindings.len());
+    }
+}
@@ -0,0 +1,21 @@
+// The game as a library, shared by the game itself and the headless simulator in src/bin
+pub mod app_state;
+pub mod audio;
+pub mod gameplay;
+pub mod input_actions;
+pub mod localization;
+pub mod math_helpers;
+pub mod orbit_camera;
+pub mod rendering;
+pub mod settings;
+pub mod ui;
+pub mod wrapped_shader_functions;
+
+use bevy::prelude::*;
+use bevy::render::pipeline::PipelineDescriptor;
+
+#[derive(Clone, Default)]
+pub struct IronSlayGlobalResources {
+    pub hex_render_pipeline: Handle<PipelineDescriptor>,
+    pub hex_sphere_render_pipeline: Handle<PipelineDescriptor>,
+}
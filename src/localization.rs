@@ -0,0 +1,224 @@
+// Every text the player reads, looked up in Fluent string tables with one folder per language
+use bevy::prelude::*;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use unic_langid::LanguageIdentifier;
+
+const LOCALES_DIR: &str = "assets/locales";
+const LOCALE_FILE: &str = "ironslay.ftl";
+
+// Texts missing from a translation are taken from here
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+// Shipped languages with their names in their own language
+pub const LOCALES: [(&str, &str); 2] = [("en-US", "English"), ("nl-NL", "Nederlands")];
+
+pub fn locale_path(locale: &str) -> PathBuf {
+    PathBuf::from(LOCALES_DIR).join(locale).join(LOCALE_FILE)
+}
+
+pub fn language_name(locale: &str) -> &str {
+    LOCALES
+        .iter()
+        .find(|(id, _)| *id == locale)
+        .map_or(locale, |(_, name)| name)
+}
+
+// Parses the string table of a locale, the errors are readable messages
+pub fn load_resource(locale: &str) -> Result<FluentResource, Vec<String>> {
+    let path = locale_path(locale);
+    let text = fs::read_to_string(&path)
+        .map_err(|error| vec![format!("{}: {}", path.display(), error)])?;
+    FluentResource::try_new(text).map_err(|(_, errors)| {
+        errors
+            .iter()
+            .map(|error| format!("{}: {:?}", path.display(), error))
+            .collect()
+    })
+}
+
+fn load_bundle(locale: &str) -> Option<FluentBundle<FluentResource>> {
+    let id: LanguageIdentifier = match locale.parse() {
+        Ok(id) => id,
+        Err(error) => {
+            warn!("Ignoring locale {}: {}", locale, error);
+            return None;
+        }
+    };
+    let resource = match load_resource(locale) {
+        Ok(resource) => resource,
+        Err(errors) => {
+            warn!("Ignoring locale {}: {}", locale, errors.join(", "));
+            return None;
+        }
+    };
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // Bevy text doesn't know the unicode isolation marks Fluent puts around arguments
+    bundle.set_use_isolating(false);
+    if let Err(errors) = bundle.add_resource(resource) {
+        warn!("Problems in locale {}: {:?}", locale, errors);
+    }
+    Some(bundle)
+}
+
+pub struct Localization {
+    language: String,
+    bundles: HashMap<String, FluentBundle<FluentResource>>,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        let bundles = LOCALES
+            .iter()
+            .filter_map(|(locale, _)| {
+                load_bundle(locale).map(|bundle| (locale.to_string(), bundle))
+            })
+            .collect();
+        Localization {
+            language: DEFAULT_LOCALE.to_string(),
+            bundles,
+        }
+    }
+}
+
+impl Localization {
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn set_language(&mut self, language: &str) {
+        self.language = language.to_string();
+    }
+
+    pub fn text(&self, key: &str) -> String {
+        self.format(key, &[])
+    }
+
+    // The message `key` in the current language, falling back to the default language and then to the key itself
+    pub fn format(&self, key: &str, args: &[(&str, FluentValue)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args.iter() {
+            fluent_args.set(*name, value.clone());
+        }
+        [self.language.as_str(), DEFAULT_LOCALE]
+            .iter()
+            .filter_map(|locale| self.bundles.get(*locale))
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(key)?.value()?;
+                let mut errors = Vec::new();
+                let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+                if !errors.is_empty() {
+                    warn!("Problems formatting {}: {:?}", key, errors);
+                }
+                Some(text.into_owned())
+            })
+            .unwrap_or_else(|| key.to_string())
+    }
+}
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Localization>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluent_syntax::ast::Entry;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    fn message_ids(locale: &str) -> BTreeSet<String> {
+        let resource = load_resource(locale).unwrap_or_else(|errors| panic!("{:?}", errors));
+        resource
+            .entries()
+            .filter_map(|entry| match entry {
+                Entry::Message(message) => Some(message.id.name.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_key_is_defined_in_every_locale() {
+        let reference = message_ids(DEFAULT_LOCALE);
+        assert!(!reference.is_empty());
+        for (locale, _) in LOCALES.iter() {
+            let ids = message_ids(locale);
+            let missing: Vec<&String> = reference.difference(&ids).collect();
+            let unknown: Vec<&String> = ids.difference(&reference).collect();
+            assert!(missing.is_empty(), "{} misses {:?}", locale, missing);
+            assert!(
+                unknown.is_empty(),
+                "{} has unknown keys {:?}",
+                locale,
+                unknown
+            );
+        }
+    }
+
+    // Keys written out as the first argument of `.text(` or `.format(`, keys built at runtime are not found
+    fn literal_keys(dir: &Path, keys: &mut BTreeSet<String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                literal_keys(&path, keys);
+                continue;
+            }
+            // The tests below look up keys that don't exist on purpose
+            if path.extension().and_then(|extension| extension.to_str()) != Some("rs")
+                || path.ends_with("localization.rs")
+            {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            for call in [".text(", ".format("].iter() {
+                for (start, _) in source.match_indices(call) {
+                    let rest = source[start + call.len()..].trim_start();
+                    if let Some(rest) = rest.strip_prefix('"') {
+                        let end = rest.find('"').unwrap();
+                        keys.insert(rest[..end].to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_key_in_the_source_is_defined() {
+        let mut keys = BTreeSet::new();
+        literal_keys(Path::new("src"), &mut keys);
+        assert!(!keys.is_empty());
+        let ids = message_ids(DEFAULT_LOCALE);
+        let missing: Vec<&String> = keys.difference(&ids).collect();
+        assert!(
+            missing.is_empty(),
+            "{} misses {:?}",
+            DEFAULT_LOCALE,
+            missing
+        );
+    }
+
+    #[test]
+    fn counts_are_pluralized() {
+        let mut localization = Localization::default();
+        for (locale, _) in LOCALES.iter() {
+            localization.set_language(locale);
+            let one = localization.format("end-turn-unmoved", &[("count", 1.into())]);
+            let many = localization.format("end-turn-unmoved", &[("count", 3.into())]);
+            assert_ne!(one, many.replace('3', "1"), "{}", locale);
+        }
+    }
+
+    #[test]
+    fn missing_keys_fall_back_to_the_key() {
+        let localization = Localization::default();
+        assert_eq!(localization.text("no-such-key"), "no-such-key");
+    }
+}
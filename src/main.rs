@@ -1,41 +1,31 @@
 // Internal
-mod gameplay;
-mod math_helpers;
-mod orbit_camera;
-mod rendering;
-mod ui;
-mod wrapped_shader_functions;
-
-use orbit_camera::*;
-use rendering::components::*;
+use ironslay::app_state::{AppScreen, GameWorld};
+use ironslay::gameplay::topology::HexTopology;
+use ironslay::orbit_camera::*;
+use ironslay::rendering::components::*;
+use ironslay::{
+    audio, gameplay, input_actions, localization, rendering, settings, ui, IronSlayGlobalResources,
+};
 
 // External
-use bevy::math::IVec2;
 use bevy::prelude::*;
-use bevy::render::{
-    mesh::shape,
-    pipeline::{PipelineDescriptor, RenderPipeline},
-    texture::{Extent3d, FilterMode, TextureDimension, TextureFormat},
-};
+use bevy::render::{mesh::shape, pipeline::RenderPipeline};
 use bevy_mod_raycast::{DefaultRaycastingPlugin, RayCastMesh};
 use bevy_skybox::{SkyboxCamera, SkyboxPlugin};
 
-use bytemuck;
-
 use std::env;
 
-#[derive(Clone, Default)]
-
-pub struct IronSlayGlobalResources {
-    hex_render_pipeline: Handle<PipelineDescriptor>,
-}
 
 fn main() {
     let path = env::current_dir().unwrap();
     println!("The current working directory is {}", path.display());
 
     App::build()
+        .add_plugin(settings::SettingsPlugin)
+        .add_plugin(localization::LocalizationPlugin)
         .add_plugins(DefaultPlugins)
+        .add_plugin(input_actions::InputActionsPlugin)
+        .add_plugin(audio::GameAudioPlugin)
         .add_plugin(OrbitCameraPlugin)
         .add_plugin(DefaultRaycastingPlugin::<
             gameplay::components::HexRaycastLayer,
@@ -44,22 +34,26 @@ fn main() {
         // add this resource to your App to enable ambiguity detection
         //.insert_resource(ReportExecutionOrderAmbiguities)
         .add_plugin(gameplay::GamePlayPlugins)
-        .add_system_to_stage(
-            CoreStage::PostUpdate,
-            rendering::systems::update_map_texture.system(),
-        )
-        .add_asset::<HexMaterial>()
+        .add_plugin(rendering::RenderingPlugins)
         .insert_resource(IronSlayGlobalResources::default())
-        .add_startup_system(rendering::systems::setup.system().before("main_init"))
-        .add_startup_system(setup.system().label("main_init"))
+        .insert_resource(gameplay::components::Scenario::default())
+        .add_state(AppScreen::MainMenu)
+        .add_system_set(
+            SystemSet::on_enter(AppScreen::InGame)
+                .with_system(spawn_world.system().after("load_ruleset")),
+        )
+        .add_system_set(SystemSet::on_exit(AppScreen::InGame).with_system(despawn_world.system()))
         .add_plugin(ui::UIPlugins)
         .run();
 }
 
-fn setup(
+// Builds the planet of the scenario and everything around it when a game starts
+fn spawn_world(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     ironslay_resources: Res<IronSlayGlobalResources>,
+    scenario: Res<gameplay::components::Scenario>,
+    rules: Res<gameplay::ruleset::RuleSet>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut textures: ResMut<Assets<Texture>>,
@@ -69,18 +63,29 @@ fn setup(
     let texture_handle = asset_server.load("branding/bevy_logo_dark_big.png");
     let background_handle = asset_server.load("textures/paper_tileable.jpg");
 
-    let map_data_vec: Vec<u32> = vec![0; 8 * 8];
-    let mut map_texture = Texture::new_fill(
-        Extent3d::new(8, 8, 1),
-        TextureDimension::D2,
-        bytemuck::cast_slice(map_data_vec.as_slice()),
-        TextureFormat::R32Uint,
-    );
-    map_texture.sampler.min_filter = FilterMode::Nearest;
-    map_texture.sampler.mag_filter = FilterMode::Nearest;
-
-    // Load hexagon cap model
-    let hexagon_cap = asset_server.load("models/HexagonCap.gltf#Mesh0/Primitive0");
+    let planet = gameplay::components::Planet {
+        number: 0,
+        shape: scenario.planet_shape,
+    };
+    let grid = gameplay::components::HexGrid::for_scenario(&scenario);
+    let wrap = grid.wrap_mode();
+
+    let map_texture = rendering::helpers::new_cell_texture(grid.width, grid.height);
+    let overlay_texture = rendering::helpers::new_cell_texture(grid.width, grid.height);
+
+    // Load hexagon cap model, or generate the sphere
+    let (planet_mesh, planet_pipeline, planet_scale) = match grid.sphere() {
+        Some(sphere) => (
+            meshes.add(rendering::helpers::build_goldberg_mesh(sphere)),
+            ironslay_resources.hex_sphere_render_pipeline.clone(),
+            2.0,
+        ),
+        None => (
+            asset_server.load("models/HexagonCap.gltf#Mesh0/Primitive0"),
+            ironslay_resources.hex_render_pipeline.clone(),
+            1.0,
+        ),
+    };
 
     // create a new quad mesh. this is what we will apply the texture to
     let quad_width = 8.0;
@@ -104,10 +109,21 @@ fn setup(
         selected_coord: Vec2::new(10.0, 10.0),
         background_texture: background_handle,
         map_state: textures.add(map_texture),
+        overlay_state: textures.add(overlay_texture),
+        map_wrap: Vec2::new(
+            wrap.wraps_x() as i32 as f32,
+            wrap.wraps_y() as i32 as f32,
+        ),
+        map_size: Vec2::new(grid.width as f32, grid.height as f32),
     });
 
-    commands.insert_resource(gameplay::components::HexGrid::new(8, 8));
     commands.insert_resource(gameplay::components::Selection::default());
+    commands.insert_resource(gameplay::components::PendingPurchase::default());
+    commands.insert_resource(gameplay::components::UndoHistory::default());
+    commands.insert_resource(gameplay::components::TeamVision::default());
+    commands.insert_resource(gameplay::components::HexCursor::default());
+    commands.insert_resource(gameplay::components::EndTurnPrompt::default());
+    commands.insert_resource(CameraBookmarks::default());
 
     // add entities to the world
     // textured quad - modulated
@@ -124,7 +140,8 @@ fn setup(
             ..Default::default()
         },
         ..Default::default()
-    });
+    })
+    .insert(GameWorld);
     // plane with hex shader
     commands
         .spawn_bundle(MeshBundle {
@@ -136,46 +153,57 @@ fn setup(
             ..Default::default()
         })
         .insert(hex_material.clone())
-        .insert(RayCastMesh::<gameplay::components::HexRaycastLayer>::default());
+        .insert(RayCastMesh::<gameplay::components::HexRaycastLayer>::default())
+        .insert(GameWorld);
+    let mut board = gameplay::rules::Board::new(&grid, &rules);
+    gameplay::mapgen::fill_sandbox(&mut board, &grid, &mut rand::thread_rng());
+    let tiles = board.tiles;
     // planet mesh with hex shader
     commands
         .spawn_bundle(MeshBundle {
-            mesh: hexagon_cap,
+            mesh: planet_mesh,
             render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
-                ironslay_resources.hex_render_pipeline.clone(),
+                planet_pipeline,
             )]),
-            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
+            transform: Transform::from_scale(Vec3::splat(planet_scale)),
             ..Default::default()
         })
         .insert(hex_material)
         .insert(gameplay::components::HexRaycastTarget::default())
         // Hex spawning...
-        .insert(gameplay::components::Planet { number: 0 })
+        .insert(planet)
+        .insert(GameWorld)
         .with_children(|parent| {
-            for y in 0..8 {
-                for x in 0..8 {
-                    let terrain = if x < 3 || y < 3 {
-                        gameplay::components::TerrainType::Water
-                    } else {
-                        gameplay::components::TerrainType::Land
-                    };
-                    let mut e = parent.spawn();
-                    e.insert(gameplay::components::Team { number: 0 })
-                        .insert(gameplay::components::GridPosition {
-                            position: IVec2::new(x, y),
-                        })
-                        .insert(terrain);
-                    if terrain == gameplay::components::TerrainType::Land {
-                        e.insert(gameplay::components::SelectableTag);
+            for coord in grid.coords() {
+                let tile = match &tiles[grid.coord_to_index(coord)] {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let mut e = parent.spawn();
+                e.insert(gameplay::components::Team { number: tile.team })
+                    .insert(gameplay::components::GridPosition { position: coord })
+                    .insert(tile.terrain);
+                if rules.passable(tile.terrain) {
+                    e.insert(gameplay::components::SelectableTag);
+                }
+                if let Some(occupant) = tile.occupant {
+                    e.insert(occupant);
+                    if occupant == gameplay::components::Occupant::Capital {
+                        e.insert(gameplay::components::Resource {
+                            amount: tile.treasury,
+                        });
                     }
                 }
             }
         });
+    commands.insert_resource(grid);
     // light
-    commands.spawn_bundle(LightBundle {
-        transform: Transform::from_translation(Vec3::new(4.0, 8.0, 4.0)),
-        ..Default::default()
-    });
+    commands
+        .spawn_bundle(LightBundle {
+            transform: Transform::from_translation(Vec3::new(4.0, 8.0, 4.0)),
+            ..Default::default()
+        })
+        .insert(GameWorld);
     // camera
     commands
         .spawn_bundle(PerspectiveCameraBundle {
@@ -185,5 +213,23 @@ fn setup(
         })
         .insert(OrbitCamera::default())
         .insert(SkyboxCamera)
-        .insert(gameplay::components::HexRaycastSource::new());
+        .insert(gameplay::components::HexRaycastSource::new())
+        .insert(GameWorld);
+}
+
+// Clears the finished game, so the next one starts from scratch
+fn despawn_world(
+    mut commands: Commands,
+    world_entities: Query<Entity, With<GameWorld>>,
+    mut game_state: ResMut<State<gameplay::components::GameState>>,
+) {
+    for entity in world_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<gameplay::components::HexGrid>();
+    if *game_state.current() != gameplay::components::GameState::default() {
+        game_state
+            .overwrite_set(gameplay::components::GameState::default())
+            .unwrap();
+    }
 }
@@ -1,24 +1,35 @@
 use bevy::input::mouse::MouseMotion;
-use bevy::input::mouse::MouseScrollUnit::{Line, Pixel};
+use bevy::input::mouse::MouseScrollUnit::Pixel;
 use bevy::input::mouse::MouseWheel;
+use bevy::input::touch::{Touch, Touches};
 use bevy::prelude::*;
 use bevy::render::camera::Camera;
+use bevy::utils::HashMap;
+use crate::input_actions::{Action, ActionState};
+use serde::{Deserialize, Serialize};
 
 const LINE_TO_PIXEL_RATIO: f32 = 0.1;
 
 
 struct CameraState {
+    // Pitch limits, 0 looks straight down and PI/2 looks at the horizon
     min: f32,
     max: f32,
     zoom_max: f32,
     zoom_min: f32,
 }
 
+impl CameraState {
+    fn clamp_pitch(&self, y: f32) -> f32 {
+        y.max(self.min).min(self.max)
+    }
+}
+
 impl Default for CameraState {
     fn default() -> Self {
         CameraState{
-            min: 3.0,
-            max: 0.5,
+            min: 0.2,
+            max: 1.4,
             zoom_min: 5.0,
             zoom_max: 15.0,
 
@@ -26,6 +37,136 @@ impl Default for CameraState {
     }
 }
 
+// Which inputs drive the camera
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrbitCameraBindings {
+    pub pan_forward: Vec<KeyCode>,
+    pub pan_back: Vec<KeyCode>,
+    pub pan_left: Vec<KeyCode>,
+    pub pan_right: Vec<KeyCode>,
+    pub rotate_left: Vec<KeyCode>,
+    pub rotate_right: Vec<KeyCode>,
+    pub focus_capital: Vec<KeyCode>,
+    // Bookmark slots in order, pressed with one of the save modifiers they store the view instead of restoring it
+    pub bookmarks: Vec<KeyCode>,
+    pub bookmark_save_modifiers: Vec<KeyCode>,
+}
+
+impl Default for OrbitCameraBindings {
+    fn default() -> Self {
+        OrbitCameraBindings {
+            // The arrows move the hex cursor
+            pan_forward: vec![KeyCode::W],
+            pan_back: vec![KeyCode::S],
+            pan_left: vec![KeyCode::A],
+            pan_right: vec![KeyCode::D],
+            rotate_left: vec![KeyCode::Q],
+            rotate_right: vec![KeyCode::E],
+            focus_capital: vec![KeyCode::Home],
+            bookmarks: vec![
+                KeyCode::Key1,
+                KeyCode::Key2,
+                KeyCode::Key3,
+                KeyCode::Key4,
+                KeyCode::Key5,
+                KeyCode::Key6,
+                KeyCode::Key7,
+                KeyCode::Key8,
+                KeyCode::Key9,
+            ],
+            bookmark_save_modifiers: vec![KeyCode::LControl, KeyCode::RControl],
+        }
+    }
+}
+
+pub struct OrbitCameraSettings {
+    pub bindings: OrbitCameraBindings,
+    // Scales the rotate sensitivity of every camera, set from the user settings
+    pub sensitivity: f32,
+    // Pan speed in distances per second, so it feels the same at every zoom level
+    pub pan_speed: f32,
+    // Radians per second when rotating with the keyboard
+    pub key_rotate_speed: f32,
+    // Time in seconds the camera takes to get most of the way to where it is going, 0 snaps
+    pub smoothing: f32,
+    // Two clicks on the same hex within this many seconds center the camera on it
+    pub double_click_seconds: f64,
+}
+
+impl Default for OrbitCameraSettings {
+    fn default() -> Self {
+        OrbitCameraSettings {
+            bindings: OrbitCameraBindings::default(),
+            sensitivity: 1.0,
+            pan_speed: 0.5,
+            key_rotate_speed: 1.5,
+            smoothing: 0.15,
+            double_click_seconds: 0.35,
+        }
+    }
+}
+
+// Requests to move the camera from outside of the camera controller
+#[derive(Clone, Copy, Debug)]
+pub enum CameraCommand {
+    CenterOn(Vec3),
+}
+
+// Asks to look at the capital of the current player, the gameplay knows where that is and answers with a CameraCommand
+pub struct FocusCapital;
+
+// Where the camera currently is, it eases towards the OrbitCamera fields
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitPose {
+    pub x: f32,
+    pub y: f32,
+    pub distance: f32,
+    pub center: Vec3,
+}
+
+impl OrbitPose {
+    fn lerp(&self, target: &OrbitPose, t: f32) -> OrbitPose {
+        OrbitPose {
+            x: self.x + (target.x - self.x) * t,
+            y: self.y + (target.y - self.y) * t,
+            distance: self.distance + (target.distance - self.distance) * t,
+            center: self.center.lerp(target.center, t),
+        }
+    }
+}
+
+pub const MAX_CAMERA_BOOKMARKS: usize = 9;
+
+// The view of a single player, so everyone finds the camera where they left it in hot-seat games
+#[derive(Clone, Debug, Default)]
+pub struct PlayerCamera {
+    pub current: Option<OrbitPose>,
+    pub bookmarks: [Option<OrbitPose>; MAX_CAMERA_BOOKMARKS],
+}
+
+// Camera views per player number
+#[derive(Default)]
+pub struct CameraBookmarks {
+    pub players: HashMap<i32, PlayerCamera>,
+}
+
+impl CameraBookmarks {
+    pub fn player_mut(&mut self, player: i32) -> &mut PlayerCamera {
+        self.players.entry(player).or_default()
+    }
+
+    pub fn save(&mut self, player: i32, slot: usize, pose: OrbitPose) {
+        if slot < MAX_CAMERA_BOOKMARKS {
+            self.player_mut(player).bookmarks[slot] = Some(pose);
+        }
+    }
+
+    pub fn bookmark(&self, player: i32, slot: usize) -> Option<OrbitPose> {
+        self.players
+            .get(&player)
+            .and_then(|camera| camera.bookmarks.get(slot).copied().flatten())
+    }
+}
 
 pub struct OrbitCamera {
     pub x: f32,
@@ -34,6 +175,8 @@ pub struct OrbitCamera {
     pub center: Vec3,
     pub rotate_sensitivity: f32,
     pub zoom_sensitivity: f32,
+    // None until the camera is placed for the first time
+    pub pose: Option<OrbitPose>,
 }
 
 impl Default for OrbitCamera {
@@ -45,6 +188,7 @@ impl Default for OrbitCamera {
             center: Vec3::ZERO,
             rotate_sensitivity: 1.0,
             zoom_sensitivity: 0.8,
+            pose: None,
         }
     }
 }
@@ -59,81 +203,424 @@ impl OrbitCamera {
             center,
             rotate_sensitivity: 1.0,
             zoom_sensitivity: 0.8,
+            pose: None,
+        }
+    }
+
+    // Makes the camera move to `pose`, eased by the smoothing
+    pub fn set_target_pose(&mut self, pose: OrbitPose) {
+        self.x = pose.x;
+        self.y = pose.y;
+        self.distance = pose.distance;
+        self.center = pose.center;
+    }
+
+    pub fn target_pose(&self) -> OrbitPose {
+        OrbitPose {
+            x: self.x,
+            y: self.y,
+            distance: self.distance,
+            center: self.center,
         }
     }
 }
 
+// The one place the camera transform comes from, all input only changes the pose
+pub fn orbit_transform(pose: &OrbitPose) -> Transform {
+    let rot = Quat::from_axis_angle(Vec3::Y, pose.x) * Quat::from_axis_angle(-Vec3::X, pose.y);
+    let mut transform = Transform::from_translation(rot * Vec3::Y * pose.distance + pose.center);
+    transform.look_at(pose.center, Vec3::Y);
+    transform
+}
+
+// Forward and right along the ground for a camera turned `x` radians around the center.
+// The camera sits at -Z when x is 0, so forward, away from the camera, is +Z
+pub fn ground_axes(x: f32) -> (Vec3, Vec3) {
+    let forward = Quat::from_axis_angle(Vec3::Y, x) * Vec3::Z;
+    (forward, forward.cross(Vec3::Y))
+}
+
+// Angles to turn for a drag of `delta` pixels. Dragging across the full window height turns a full circle,
+// so the speed is the same at every resolution, and it does not depend on the frame time at all
+pub fn drag_rotation(delta: Vec2, window_height: f32, sensitivity: f32) -> Vec2 {
+    delta / window_height.max(1.0) * std::f32::consts::TAU * sensitivity
+}
+
+// Center offset for dragging the ground `delta` pixels, scaled by the distance so the ground follows the fingers
+pub fn drag_pan(delta: Vec2, window_height: f32, x: f32, distance: f32) -> Vec3 {
+    let (forward, right) = ground_axes(x);
+    let scaled = delta / window_height.max(1.0) * distance;
+    right * -scaled.x + forward * scaled.y
+}
+
+pub fn zoomed_distance(distance: f32, factor: f32, min: f32, max: f32) -> f32 {
+    (distance * factor).clamp(min, max)
+}
+
+// Zoom and pan for scrolling `delta` pixels. Scrolling straight up or down zooms, like a wheel does.
+// Touchpads pan when they scroll sideways or with shift held, pinching arrives as scrolling with control held and zooms
+pub fn pixel_scroll(delta: Vec2, pinching: bool, panning: bool) -> (f32, Vec2) {
+    if pinching || (!panning && delta.x == 0.0) {
+        (delta.y * LINE_TO_PIXEL_RATIO, Vec2::ZERO)
+    } else {
+        (0.0, delta)
+    }
+}
+
+fn any_pressed(keyboard_input: &Input<KeyCode>, keys: &[KeyCode]) -> bool {
+    keys.iter().any(|key| keyboard_input.pressed(*key))
+}
+
+fn window_height(windows: &Windows) -> f32 {
+    windows.get_primary().map_or(1.0, |window| window.height())
+}
+
 pub struct OrbitCameraPlugin;
 impl OrbitCameraPlugin {
     fn mouse_motion_system(
-        time: Res<Time>,
-        mut state: ResMut<CameraState>,
+        state: Res<CameraState>,
+        settings: Res<OrbitCameraSettings>,
+        windows: Res<Windows>,
         mut mouse_motion_event_reader: EventReader<MouseMotion>,
-        mouse_button_input: Res<Input<MouseButton>>,
-        mut query: Query<(&mut OrbitCamera, &mut Transform, &mut Camera)>,
+        actions: Res<ActionState>,
+        mut query: Query<&mut OrbitCamera>,
     ) {
         let mut delta = Vec2::ZERO;
         for event in mouse_motion_event_reader.iter() {
             delta += event.delta;
         }
-        for (mut camera, mut transform, _) in query.iter_mut() {
-            if mouse_button_input.pressed(MouseButton::Left) {
-                camera.x -= delta.x * camera.rotate_sensitivity * time.delta_seconds();
-                camera.y -= delta.y * camera.rotate_sensitivity * time.delta_seconds();
+        if !actions.pressed(Action::RotateCamera) {
+            return;
+        }
+        let height = window_height(&windows);
+        for mut camera in query.iter_mut() {
+            let rotation = drag_rotation(
+                delta,
+                height,
+                camera.rotate_sensitivity * settings.sensitivity,
+            );
+            camera.x -= rotation.x;
+            camera.y = state.clamp_pitch(camera.y - rotation.y);
+        }
+    }
+
+    // Pans the center over the ground plane and rotates around it with the keyboard
+    fn keyboard_system(
+        time: Res<Time>,
+        settings: Res<OrbitCameraSettings>,
+        keyboard_input: Res<Input<KeyCode>>,
+        mut focus_capital: EventWriter<FocusCapital>,
+        mut query: Query<&mut OrbitCamera>,
+    ) {
+        let bindings = &settings.bindings;
+        let axis = |positive: &[KeyCode], negative: &[KeyCode]| {
+            any_pressed(&keyboard_input, positive) as i32 as f32
+                - any_pressed(&keyboard_input, negative) as i32 as f32
+        };
+        let forward_input = axis(&bindings.pan_forward, &bindings.pan_back);
+        let right_input = axis(&bindings.pan_right, &bindings.pan_left);
+        let rotate_input = axis(&bindings.rotate_right, &bindings.rotate_left);
+
+        // Held keys are rates, so unlike the mouse they do scale with the frame time
+        for mut camera in query.iter_mut() {
+            camera.x += rotate_input * settings.key_rotate_speed * time.delta_seconds();
+
+            let (forward, right) = ground_axes(camera.x);
+            let pan = (forward * forward_input + right * right_input)
+                * settings.pan_speed
+                * camera.distance
+                * time.delta_seconds();
+            camera.center += pan;
+        }
 
-                camera.y = camera.y.max(state.min).min(state.max);
+        if bindings
+            .focus_capital
+            .iter()
+            .any(|key| keyboard_input.just_pressed(*key))
+        {
+            focus_capital.send(FocusCapital);
+        }
+    }
 
-                let rot = Quat::from_axis_angle(Vec3::unit_y(), camera.x)
-                    * Quat::from_axis_angle(-Vec3::unit_x(), camera.y);
-                transform.translation =
-                    (rot * Vec3::new(0.0, 1.0, 0.0)) * camera.distance + camera.center;
-                transform.look_at(camera.center, Vec3::unit_y());
+    // Turns the camera at a steady rate while the camera actions are held, which is how sticks drive it
+    fn action_rotate_system(
+        time: Res<Time>,
+        state: Res<CameraState>,
+        settings: Res<OrbitCameraSettings>,
+        actions: Res<ActionState>,
+        mut query: Query<&mut OrbitCamera>,
+    ) {
+        let yaw = actions.value(Action::CameraRight) - actions.value(Action::CameraLeft);
+        let pitch = actions.value(Action::CameraUp) - actions.value(Action::CameraDown);
+        if yaw == 0.0 && pitch == 0.0 {
+            return;
+        }
+        let speed = settings.key_rotate_speed * settings.sensitivity * time.delta_seconds();
+        for mut camera in query.iter_mut() {
+            camera.x += yaw * speed;
+            camera.y = state.clamp_pitch(camera.y + pitch * speed);
+        }
+    }
+
+    fn command_system(
+        mut commands: EventReader<CameraCommand>,
+        mut query: Query<&mut OrbitCamera>,
+    ) {
+        for command in commands.iter() {
+            match command {
+                CameraCommand::CenterOn(center) => {
+                    for mut camera in query.iter_mut() {
+                        camera.center = *center;
+                    }
+                }
             }
         }
     }
 
+    // The Zoom action zooms, wheels drive it by scrolling in lines. Smooth scrolling wheels and touchpads scroll in pixels,
+    // see pixel_scroll for which of those zoom and which pan
     fn zoom_system(
-        mut state: ResMut<CameraState>,
+        state: Res<CameraState>,
+        windows: Res<Windows>,
+        keyboard_input: Res<Input<KeyCode>>,
+        actions: Res<ActionState>,
         mut mouse_wheel_event_reader: EventReader<MouseWheel>,
-        mut query: Query<(&mut OrbitCamera, &mut Transform, &mut Camera)>,
+        mut query: Query<&mut OrbitCamera>,
     ) {
-        let mut total = 0.0;
+        let pinching = any_pressed(&keyboard_input, &[KeyCode::LControl, KeyCode::RControl]);
+        let panning = any_pressed(&keyboard_input, &[KeyCode::LShift, KeyCode::RShift]);
+        let mut zoom = actions.value(Action::Zoom);
+        let mut pan = Vec2::ZERO;
         for event in mouse_wheel_event_reader.iter() {
-            total += event.y
-                * match event.unit {
-                    Line => 1.0,
-                    Pixel => LINE_TO_PIXEL_RATIO,
-                };
+            if matches!(event.unit, Pixel) {
+                let (scroll_zoom, scroll_pan) =
+                    pixel_scroll(Vec2::new(event.x, event.y), pinching, panning);
+                zoom += scroll_zoom;
+                pan += scroll_pan;
+            }
+        }
+        let height = window_height(&windows);
+        for mut camera in query.iter_mut() {
+            let factor = camera.zoom_sensitivity.powf(zoom);
+            camera.distance =
+                zoomed_distance(camera.distance, factor, state.zoom_min, state.zoom_max);
+            camera.center += drag_pan(pan, height, camera.x, camera.distance);
         }
+    }
+
+    // One finger rotates, two fingers pan and pinch to zoom
+    fn touch_system(
+        state: Res<CameraState>,
+        settings: Res<OrbitCameraSettings>,
+        windows: Res<Windows>,
+        touches: Res<Touches>,
+        mut query: Query<&mut OrbitCamera>,
+    ) {
+        let active: Vec<&Touch> = touches.iter().collect();
+        let height = window_height(&windows);
+        for mut camera in query.iter_mut() {
+            match active.as_slice() {
+                [touch] => {
+                    let rotation = drag_rotation(
+                        touch.position() - touch.previous_position(),
+                        height,
+                        camera.rotate_sensitivity * settings.sensitivity,
+                    );
+                    camera.x -= rotation.x;
+                    camera.y = state.clamp_pitch(camera.y - rotation.y);
+                }
+                [a, b] => {
+                    let span = a.position().distance(b.position());
+                    let previous_span = a.previous_position().distance(b.previous_position());
+                    if span > 0.0 && previous_span > 0.0 {
+                        camera.distance = zoomed_distance(
+                            camera.distance,
+                            previous_span / span,
+                            state.zoom_min,
+                            state.zoom_max,
+                        );
+                    }
+                    let delta = (a.position() - a.previous_position()
+                        + b.position()
+                        - b.previous_position())
+                        * 0.5;
+                    camera.center += drag_pan(delta, height, camera.x, camera.distance);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Eases the camera towards the pose the input systems ask for
+    fn update_transform_system(
+        time: Res<Time>,
+        state: Res<CameraState>,
+        settings: Res<OrbitCameraSettings>,
+        mut query: Query<(&mut OrbitCamera, &mut Transform, &Camera)>,
+    ) {
+        // Exponential decay, so the smoothing is the same at every frame rate
+        let t = if settings.smoothing > 0.0 {
+            1.0 - (-time.delta_seconds() / settings.smoothing).exp()
+        } else {
+            1.0
+        };
         for (mut camera, mut transform, _) in query.iter_mut() {
-            camera.distance *= camera.zoom_sensitivity.powf(total);
-            let translation = &mut transform.translation;
-            camera.distance = camera.distance.clamp(state.zoom_min,state.zoom_max);
-            *translation =
-                (*translation - camera.center).normalize() * camera.distance + camera.center;
+            let pose = match camera.pose {
+                Some(pose) => pose.lerp(&camera.target_pose(), t),
+                // Cameras spawned after startup are placed right away
+                None => {
+                    camera.y = state.clamp_pitch(camera.y);
+                    camera.target_pose()
+                }
+            };
+            if camera.pose == Some(pose) {
+                continue;
+            }
+            camera.pose = Some(pose);
+            *transform = orbit_transform(&pose);
         }
     }
 }
 impl Plugin for OrbitCameraPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<CameraState>()
-            .add_system(Self::mouse_motion_system.system())
-            .add_system(Self::zoom_system.system())
+            .init_resource::<OrbitCameraSettings>()
+            .add_event::<CameraCommand>()
+            .add_event::<FocusCapital>()
+            .init_resource::<CameraBookmarks>()
+            .add_system(Self::mouse_motion_system.system().before("orbit_camera_transform"))
+            .add_system(Self::keyboard_system.system().before("orbit_camera_transform"))
+            .add_system(Self::action_rotate_system.system().before("orbit_camera_transform"))
+            .add_system(Self::command_system.system().before("orbit_camera_transform"))
+            .add_system(Self::zoom_system.system().before("orbit_camera_transform"))
+            .add_system(Self::touch_system.system().before("orbit_camera_transform"))
+            .add_system(
+                Self::update_transform_system
+                    .system()
+                    .label("orbit_camera_transform"),
+            )
             .add_startup_system_to_stage(bevy::app::StartupStage::PostStartup, self::initial_camera_position.system());
     }
 
 }
 
 fn initial_camera_position(
-    state: ResMut<CameraState>,
+    state: Res<CameraState>,
     mut query: Query<(&mut OrbitCamera, &mut Transform, &mut Camera)>,){
         for (mut camera, mut transform, _) in query.iter_mut() {
-        camera.y = camera.y.max(state.min).min(state.max);
-        let rot = Quat::from_axis_angle(Vec3::unit_y(), camera.x)
-            * Quat::from_axis_angle(-Vec3::unit_x(), camera.y);
-        transform.translation =
-            (rot * Vec3::new(0.0, 1.0, 0.0)) * camera.distance + camera.center;
-        transform.look_at(camera.center, Vec3::unit_y());
+        camera.y = state.clamp_pitch(camera.y);
+        let pose = camera.target_pose();
+        *transform = orbit_transform(&pose);
+        camera.pose = Some(pose);
+        }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn pose(x: f32, y: f32, distance: f32, center: Vec3) -> OrbitPose {
+        OrbitPose {
+            x,
+            y,
+            distance,
+            center,
+        }
+    }
+
+    #[test]
+    fn orbit_transform_keeps_the_distance_and_looks_at_the_center() {
+        let center = Vec3::new(1.0, -2.0, 0.5);
+        for &(x, y) in &[(0.0, 0.2), (1.0, 0.7), (-2.5, 1.4), (4.0, 1.0)] {
+            let transform = orbit_transform(&pose(x, y, 7.0, center));
+            let to_center = center - transform.translation;
+            assert!((to_center.length() - 7.0).abs() < EPSILON);
+            let looking = transform.rotation * -Vec3::Z;
+            assert!(looking.distance(to_center.normalize()) < EPSILON);
         }
+    }
+
+    #[test]
+    fn orbit_transform_at_the_horizon_sits_behind_the_center() {
+        let transform = orbit_transform(&pose(0.0, std::f32::consts::FRAC_PI_2, 5.0, Vec3::ZERO));
+        assert!(transform.translation.distance(Vec3::new(0.0, 0.0, -5.0)) < EPSILON);
+    }
 
+    #[test]
+    fn ground_axes_point_away_from_the_camera_along_the_ground() {
+        for &x in &[0.0, 0.8, -1.3, 3.0] {
+            let (forward, right) = ground_axes(x);
+            assert!(forward.y.abs() < EPSILON && right.y.abs() < EPSILON);
+            assert!(forward.dot(right).abs() < EPSILON);
+
+            let camera = orbit_transform(&pose(x, 1.0, 5.0, Vec3::ZERO)).translation;
+            assert!(forward.dot(-camera) > 0.0);
+            // Right as seen through the camera
+            let camera_right = orbit_transform(&pose(x, 1.0, 5.0, Vec3::ZERO)).rotation * Vec3::X;
+            assert!(right.dot(camera_right) > 0.9);
+        }
+    }
+
+    #[test]
+    fn drag_rotation_does_not_depend_on_resolution_or_event_split() {
+        let small = drag_rotation(Vec2::new(36.0, 18.0), 720.0, 1.0);
+        let large = drag_rotation(Vec2::new(72.0, 36.0), 1440.0, 1.0);
+        assert!(small.distance(large) < EPSILON);
+
+        let split = drag_rotation(Vec2::new(12.0, 6.0), 720.0, 1.0) * 3.0;
+        assert!(small.distance(split) < EPSILON);
+
+        let full_height = drag_rotation(Vec2::new(0.0, 720.0), 720.0, 1.0);
+        assert!((full_height.y - std::f32::consts::TAU).abs() < EPSILON);
+    }
+
+    #[test]
+    fn drag_pan_moves_the_center_across_the_ground() {
+        let pan = drag_pan(Vec2::new(0.0, 360.0), 720.0, 0.0, 10.0);
+        assert!(pan.distance(Vec3::new(0.0, 0.0, 5.0)) < EPSILON);
+        let pan = drag_pan(Vec2::new(72.0, 0.0), 720.0, 0.0, 10.0);
+        assert!(pan.y.abs() < EPSILON && (pan.length() - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn zoom_steps_compose_and_clamp() {
+        let once = zoomed_distance(10.0, 0.8f32.powf(2.0), 5.0, 15.0);
+        let twice = zoomed_distance(zoomed_distance(10.0, 0.8, 5.0, 15.0), 0.8, 5.0, 15.0);
+        assert!((once - twice).abs() < EPSILON);
+        assert_eq!(zoomed_distance(10.0, 0.1, 5.0, 15.0), 5.0);
+        assert_eq!(zoomed_distance(10.0, 10.0, 5.0, 15.0), 15.0);
+    }
+
+    #[test]
+    fn pixel_scrolling_zooms_unless_it_pans() {
+        // A smooth scrolling wheel
+        let (zoom, pan) = pixel_scroll(Vec2::new(0.0, 20.0), false, false);
+        assert!((zoom - 20.0 * LINE_TO_PIXEL_RATIO).abs() < EPSILON);
+        assert_eq!(pan, Vec2::ZERO);
+        // Touchpad scrolling sideways or with shift held
+        assert_eq!(
+            pixel_scroll(Vec2::new(3.0, 20.0), false, false),
+            (0.0, Vec2::new(3.0, 20.0))
+        );
+        assert_eq!(
+            pixel_scroll(Vec2::new(0.0, 20.0), false, true),
+            (0.0, Vec2::new(0.0, 20.0))
+        );
+        // Pinching zooms whichever way the fingers go
+        let (zoom, pan) = pixel_scroll(Vec2::new(3.0, -20.0), true, false);
+        assert!((zoom + 20.0 * LINE_TO_PIXEL_RATIO).abs() < EPSILON);
+        assert_eq!(pan, Vec2::ZERO);
+    }
+
+    #[test]
+    fn pitch_is_kept_between_the_limits() {
+        let state = CameraState::default();
+        assert_eq!(state.clamp_pitch(-1.0), state.min);
+        assert_eq!(state.clamp_pitch(10.0), state.max);
+        assert_eq!(state.clamp_pitch(1.0), 1.0);
+        assert!(state.min < state.max);
+    }
 }
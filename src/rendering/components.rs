@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
 use bevy::render::renderer::RenderResources;
+use bevy::utils::HashMap;
+use crate::gameplay::components::Occupant;
 
 // Custom material for our custom shader
 #[derive(RenderResources, TypeUuid)]
@@ -10,7 +12,13 @@ pub struct HexMaterial {
     pub highlighted_coord: Vec2,
     pub selected_coord: Vec2,
     pub background_texture: Handle<Texture>,
-    pub map_state: Handle<Texture>
+    pub map_state: Handle<Texture>,
+    // Highlight categories per cell, see the OVERLAY_ constants
+    pub overlay_state: Handle<Texture>,
+    // 1.0 on the axes where the map wraps around
+    pub map_wrap: Vec2,
+    // Size of map_state in cells, the rust-gpu shader can't query texture sizes
+    pub map_size: Vec2,
 }
 impl Default for HexMaterial {
     fn default() -> Self {
@@ -20,6 +28,57 @@ impl Default for HexMaterial {
             selected_coord: Vec2::new(10.0, 10.0),
             background_texture: Default::default(),
             map_state: Default::default(),
+            overlay_state: Default::default(),
+            map_wrap: Vec2::ZERO,
+            map_size: Vec2::ZERO,
         }
     }
-}
\ No newline at end of file
+}
+
+// Mesh and material used to present one kind of tile occupant
+pub struct OccupantModel {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+    pub scale: f32,
+}
+
+// All occupant models, loaded once at startup
+#[derive(Default)]
+pub struct OccupantModels {
+    pub models: HashMap<Occupant, OccupantModel>,
+}
+
+// Bookkeeping of which model entity presents which tile, and the models that are parked for reuse
+#[derive(Default)]
+pub struct OccupantPresentation {
+    pub active: HashMap<Entity, (Occupant, Entity)>,
+    pub pool: HashMap<Occupant, Vec<Entity>>,
+}
+
+// Marks an entity as the visual of a tile occupant
+pub struct OccupantVisual {
+    pub kind: Occupant,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum OccupantAnimationKind {
+    Appear,
+    Move { from: Vec3, to: Vec3 },
+    Capture,
+}
+
+pub struct OccupantAnimation {
+    pub kind: OccupantAnimationKind,
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+impl OccupantAnimation {
+    pub fn new(kind: OccupantAnimationKind, duration: f32) -> Self {
+        Self {
+            kind,
+            elapsed: 0.0,
+            duration,
+        }
+    }
+}
@@ -1,7 +1,14 @@
 use bevy::prelude::*;
-use bevy::render::mesh::VertexAttributeValues;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+use bevy::render::pipeline::PrimitiveTopology;
+use bevy::render::texture::{Extent3d, FilterMode, TextureDimension, TextureFormat};
 use bevy_mod_raycast::Intersection;
+use crate::gameplay::topology::{GoldbergSphere, HexTopology};
 use crate::math_helpers::vec3_all_eq;
+use crate::wrapped_shader_functions;
+
+// Temp thing in the shader, to get more hexes on the mesh
+pub const HEX_UV_SCALE: f32 = 5.0;
 
 // Temp fix to obtain vertex indices. In an ideal world, this would be supplied by bevy_mod_raycast's Intersection directly.
 pub fn calculate_vertex_indices_from_intersection(
@@ -39,4 +46,151 @@ pub fn calculate_vertex_indices_from_intersection(
         .unwrap();
 
     [tri_idx_v0 as u32, tri_idx_v1 as u32, tri_idx_v2 as u32]
-}
\ No newline at end of file
+}
+
+// Texture with one u32 per cell, read with texelFetch by the hex shaders
+pub fn new_cell_texture(width: i32, height: i32) -> Texture {
+    let data: Vec<u32> = vec![0; (width * height) as usize];
+    let mut texture = Texture::new_fill(
+        Extent3d::new(width as u32, height as u32, 1),
+        TextureDimension::D2,
+        bytemuck::cast_slice(data.as_slice()),
+        TextureFormat::R32Uint,
+    );
+    texture.sampler.min_filter = FilterMode::Nearest;
+    texture.sampler.mag_filter = FilterMode::Nearest;
+    texture
+}
+
+// Inverse of the picking math: the mesh uv at the center of the hex at `coord`
+pub fn hex_coord_to_uv(coord: IVec2) -> Vec2 {
+    wrapped_shader_functions::hex_grid_coord_to_id(coord.as_f32()) / HEX_UV_SCALE
+}
+
+// Finds the point on the mesh surface that is mapped to `uv`, together with the interpolated surface normal.
+// Both are in mesh space. Returns None when no triangle covers the uv.
+pub fn mesh_surface_at_uv(mesh: &Mesh, uv: Vec2) -> Option<(Vec3, Vec3)> {
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float3(positions)) => positions,
+        _ => return None,
+    };
+    let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float3(normals)) => Some(normals),
+        _ => None,
+    };
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float2(uvs)) => uvs,
+        _ => return None,
+    };
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        Some(Indices::U32(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        None => (0..positions.len()).collect(),
+    };
+
+    for tri in indices.chunks_exact(3) {
+        let uv0 = Vec2::from(uvs[tri[0]]);
+        let uv1 = Vec2::from(uvs[tri[1]]);
+        let uv2 = Vec2::from(uvs[tri[2]]);
+
+        // Barycentric coordinates in uv space
+        let v0 = uv1 - uv0;
+        let v1 = uv2 - uv0;
+        let v2 = uv - uv0;
+        let denom = v0.x * v1.y - v1.x * v0.y;
+        if denom.abs() < f32::EPSILON {
+            continue;
+        }
+        let v = (v2.x * v1.y - v1.x * v2.y) / denom;
+        let w = (v0.x * v2.y - v2.x * v0.y) / denom;
+        let u = 1.0 - v - w;
+        if u < 0.0 || v < 0.0 || w < 0.0 {
+            continue;
+        }
+
+        let position = Vec3::from(positions[tri[0]]) * u
+            + Vec3::from(positions[tri[1]]) * v
+            + Vec3::from(positions[tri[2]]) * w;
+        let normal = match normals {
+            Some(normals) => (Vec3::from(normals[tri[0]]) * u
+                + Vec3::from(normals[tri[1]]) * v
+                + Vec3::from(normals[tri[2]]) * w)
+                .normalize(),
+            None => (Vec3::from(positions[tri[1]]) - Vec3::from(positions[tri[0]]))
+                .cross(Vec3::from(positions[tri[2]]) - Vec3::from(positions[tri[0]]))
+                .normalize(),
+        };
+        return Some((position, normal));
+    }
+
+    None
+}
+
+// World space transform that places an object on the surface of the planet at the hex `coord`, standing up along the surface normal
+pub fn hex_surface_transform(
+    planet_mesh: &Mesh,
+    planet_transform: &GlobalTransform,
+    coord: IVec2,
+    scale: f32,
+) -> Option<Transform> {
+    let (position, normal) = mesh_surface_at_uv(planet_mesh, hex_coord_to_uv(coord))?;
+    let up = (planet_transform.rotation * normal).normalize();
+    Some(Transform {
+        translation: planet_transform.compute_matrix().transform_point3(position),
+        rotation: Quat::from_rotation_arc(Vec3::Y, up),
+        scale: Vec3::splat(scale),
+    })
+}
+
+// Same as hex_surface_transform, for planets covered by a goldberg sphere
+pub fn sphere_surface_transform(
+    sphere: &GoldbergSphere,
+    planet_transform: &GlobalTransform,
+    coord: IVec2,
+    scale: f32,
+) -> Option<Transform> {
+    if !sphere.contains(coord) {
+        return None;
+    }
+    let center = sphere.centers[sphere.coord_to_index(coord)];
+    let up = (planet_transform.rotation * center).normalize();
+    Some(Transform {
+        translation: planet_transform.compute_matrix().transform_point3(center),
+        rotation: Quat::from_rotation_arc(Vec3::Y, up),
+        scale: Vec3::splat(scale),
+    })
+}
+
+// Unit sphere mesh with a triangle fan per cell.
+// The uv does not map a texture: the integer part of x holds the cell index and its fraction (k + 0.5) / 8 the edge k the triangle touches,
+// y goes from 0 at the cell center to 1 at its border.
+pub fn build_goldberg_mesh(sphere: &GoldbergSphere) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+
+    for (cell, center) in sphere.centers.iter().enumerate() {
+        let corners = &sphere.corners[cell];
+        for k in 0..corners.len() {
+            let cell_and_edge = cell as f32 + (k as f32 + 0.5) / 8.0;
+            let triangle = [
+                (*center, 0.0),
+                (corners[k], 1.0),
+                (corners[(k + 1) % corners.len()], 1.0),
+            ];
+            for (position, border) in triangle.iter() {
+                positions.push((*position).into());
+                normals.push((*position).into());
+                uvs.push([cell_and_edge, *border]);
+            }
+        }
+    }
+
+    let indices = (0..positions.len() as u32).collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
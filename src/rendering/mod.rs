@@ -1,3 +1,45 @@
+use bevy::prelude::*;
+
 pub mod helpers;
 pub mod systems;
 pub mod components;
+
+use crate::app_state::{run_if_in_game, AppScreen};
+
+pub struct RenderingPlugins;
+
+impl Plugin for RenderingPlugins {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<components::HexMaterial>()
+            .init_resource::<components::OccupantModels>()
+            .init_resource::<components::OccupantPresentation>()
+            .add_startup_system(systems::setup.system())
+            .add_startup_system(systems::setup_occupant_models.system())
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::new()
+                    .with_run_criteria(run_if_in_game.system())
+                    .with_system(systems::update_map_texture.system())
+                    .with_system(systems::update_overlay_texture.system())
+                    .with_system(systems::sync_hex_material_selection.system()),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_in_game.system())
+                    .with_system(
+                        systems::sync_occupant_models
+                            .system()
+                            .label("sync_occupant_models"),
+                    )
+                    .with_system(
+                        systems::animate_occupant_models
+                            .system()
+                            .after("sync_occupant_models"),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppScreen::InGame)
+                    .with_system(systems::clear_occupant_models.system()),
+            );
+    }
+}
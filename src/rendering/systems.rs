@@ -1,12 +1,19 @@
 use super::components::*;
+use super::helpers;
 use crate::gameplay::components::*;
+use crate::gameplay::topology::HexTopology;
+use crate::wrapped_shader_functions;
 use crate::IronSlayGlobalResources;
 use bevy::core::FromBytes;
 use bevy::prelude::*;
 use bevy::render::{
     pipeline::PipelineDescriptor,
     render_graph::{base, AssetRenderResourcesNode, RenderGraph},
-    shader::{ShaderSource, ShaderStage, ShaderStages},
+    shader::{ShaderStage, ShaderStages},
+};
+use hex_shader::{
+    MAP_FLAG_HOVERED, MAP_FLAG_SELECTED, MAP_VISIBILITY_REMEMBERED, MAP_VISIBILITY_SHIFT,
+    MAP_VISIBILITY_UNEXPLORED,
 };
 
 const VERTEX_SHADER: &str = r#"
@@ -28,7 +35,7 @@ void main() {
 }
 "#;
 
-// Unused, as we use the rust-gpu shader. But briefly kept for set/binding reference
+// Unused, the fragment shader is either glsl_shaders/hex_shader.frag or the rust-gpu hex_shader. But briefly kept for set/binding reference
 const _FRAGMENT_SHADER: &str = r#"
 #version 450
 layout(location = 0) out vec4 o_Target;
@@ -40,6 +47,28 @@ void main() {
 }
 "#;
 
+// How every kind of occupant is presented: (occupant, model, tint, scale)
+// Until dedicated models are made, all of them are tinted and scaled variants of the single hexagon model
+const OCCUPANT_MODEL_TABLE: &[(Occupant, &str, [f32; 3], f32)] = &[
+    (Occupant::Unit(UnitTier::Peasant), "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.9, 0.8, 0.6], 0.10),
+    (Occupant::Unit(UnitTier::Spearman), "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.7, 0.7, 0.8], 0.12),
+    (Occupant::Unit(UnitTier::Knight), "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.5, 0.5, 0.9], 0.14),
+    (Occupant::Unit(UnitTier::Baron), "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.8, 0.3, 0.8], 0.16),
+    (Occupant::Castle, "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.5, 0.5, 0.5], 0.18),
+    (Occupant::Capital, "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.9, 0.7, 0.1], 0.16),
+    (Occupant::Tree(TreeKind::Pine), "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.1, 0.4, 0.1], 0.12),
+    (Occupant::Tree(TreeKind::Palm), "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.4, 0.6, 0.1], 0.12),
+    (Occupant::Grave, "models/SingleHexagon.gltf#Mesh0/Primitive0", [0.3, 0.3, 0.3], 0.08),
+];
+
+// Coordinate that never matches a tile, for when nothing is hovered or selected
+const NO_COORD: f32 = -1000.0;
+
+const OCCUPANT_APPEAR_DURATION: f32 = 0.25;
+const OCCUPANT_MOVE_DURATION: f32 = 0.4;
+const OCCUPANT_MOVE_HOP_HEIGHT: f32 = 0.15;
+const OCCUPANT_CAPTURE_DURATION: f32 = 0.3;
+
 pub fn setup(
     mut ironslay_resources: ResMut<IronSlayGlobalResources>,
     asset_server: ResMut<AssetServer>,
@@ -50,14 +79,15 @@ pub fn setup(
     asset_server.watch_for_changes().unwrap();
 
     // Create a new shader pipeline
-    //let hex_shader_spriv = include_bytes!(env!("hex_shader.spv"));
     let pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
         vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
-        fragment: Some(asset_server.load::<Shader, _>("glsl_shaders/hex_shader.frag"))
-        // fragment: Some(shaders.add(Shader::new(
-        //     ShaderStage::Fragment,
-        //     ShaderSource::spirv_from_bytes(hex_shader_spriv),
-        // ))),
+        fragment: Some(hex_fragment_shader(&asset_server, &mut shaders)),
+    }));
+
+    // Spherical planets encode the cell index in the uvs, so they use their own fragment shader
+    let sphere_pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
+        fragment: Some(asset_server.load::<Shader, _>("glsl_shaders/hex_sphere_shader.frag")),
     }));
 
     // Add an AssetRenderResourcesNode to our Render Graph. This will bind MyMaterial resources to our shader
@@ -72,11 +102,34 @@ pub fn setup(
         .unwrap();
 
     ironslay_resources.hex_render_pipeline = pipeline_handle;
+    ironslay_resources.hex_sphere_render_pipeline = sphere_pipeline_handle;
+}
+
+// The rust-gpu build of hex_shader, compiled by build.rs
+#[cfg(feature = "rust-gpu-shader")]
+fn hex_fragment_shader(_asset_server: &AssetServer, shaders: &mut Assets<Shader>) -> Handle<Shader> {
+    let hex_shader_spirv = include_bytes!(env!("hex_shader.spv"));
+    shaders.add(Shader::new(
+        ShaderStage::Fragment,
+        bevy::render::shader::ShaderSource::spirv_from_bytes(hex_shader_spirv),
+    ))
+}
+
+// The GLSL shader is loaded as an asset, so it hot reloads
+#[cfg(not(feature = "rust-gpu-shader"))]
+fn hex_fragment_shader(asset_server: &AssetServer, _shaders: &mut Assets<Shader>) -> Handle<Shader> {
+    asset_server.load::<Shader, _>("glsl_shaders/hex_shader.frag")
 }
 
 pub fn update_map_texture(
-    grid_positions: Query<(&GridPosition, &TerrainType)>,
+    grid_positions: Query<(
+        &GridPosition,
+        &TerrainType,
+        Option<&HoverTag>,
+        Option<&SelectedTag>,
+    )>,
     hex_grid: Res<HexGrid>,
+    vision: Res<TeamVision>,
     mut textures: ResMut<Assets<Texture>>,
     hex_materials: Res<Assets<HexMaterial>>,
 ) {
@@ -95,11 +148,328 @@ pub fn update_map_texture(
     );
 
     let mut map_buffer: Vec<u32> = vec![0; (hex_grid.width * hex_grid.height) as usize];
-    for (coord, terrain_type) in grid_positions.iter() {
-        map_buffer[hex_grid.coord_to_index(coord.position)] = match terrain_type {
-            TerrainType::Land => 0,
-            TerrainType::Water => 1,
+    for (coord, terrain_type, hovered, selected) in grid_positions.iter() {
+        let index = hex_grid.coord_to_index(coord.position);
+        // Unexplored tiles don't even tell their terrain
+        let visibility = vision.visibility(index);
+        let mut texel = match visibility {
+            TileVisibility::Visible => 0,
+            TileVisibility::Remembered => MAP_VISIBILITY_REMEMBERED << MAP_VISIBILITY_SHIFT,
+            TileVisibility::Unexplored => MAP_VISIBILITY_UNEXPLORED << MAP_VISIBILITY_SHIFT,
         };
+        if visibility != TileVisibility::Unexplored {
+            texel |= terrain_index(*terrain_type);
+        }
+        if hovered.is_some() {
+            texel |= MAP_FLAG_HOVERED;
+        }
+        if selected.is_some() {
+            texel |= MAP_FLAG_SELECTED;
+        }
+        map_buffer[index] = texel;
     }
     texture.data = Vec::from_bytes(bytemuck::cast_slice(map_buffer.as_slice()));
 }
+
+// The lowest bits of a map_state texel
+fn terrain_index(terrain: TerrainType) -> u32 {
+    match terrain {
+        TerrainType::Land => hex_shader::TERRAIN_LAND,
+        TerrainType::Water => hex_shader::TERRAIN_WATER,
+        TerrainType::Mountain => hex_shader::TERRAIN_MOUNTAIN,
+        TerrainType::Forest => hex_shader::TERRAIN_FOREST,
+        TerrainType::Plains => hex_shader::TERRAIN_PLAINS,
+        TerrainType::Desert => hex_shader::TERRAIN_DESERT,
+        TerrainType::Shallows => hex_shader::TERRAIN_SHALLOWS,
+        TerrainType::Bridge => hex_shader::TERRAIN_BRIDGE,
+    }
+}
+
+pub fn update_overlay_texture(
+    overlay: Res<MapOverlay>,
+    hex_grid: Res<HexGrid>,
+    mut textures: ResMut<Assets<Texture>>,
+    hex_materials: Res<Assets<HexMaterial>>,
+) {
+    if !overlay.is_changed() || overlay.cells.len() != (hex_grid.width * hex_grid.height) as usize {
+        return;
+    }
+    for (_, hex_material) in hex_materials.iter() {
+        if let Some(texture) = textures.get_mut(&hex_material.overlay_state) {
+            texture.data = Vec::from_bytes(bytemuck::cast_slice(overlay.cells.as_slice()));
+        }
+    }
+}
+
+// Pushes the hovered tile and the primary selection to the hex materials
+pub fn sync_hex_material_selection(
+    hovered_tiles: Query<&GridPosition, With<HoverTag>>,
+    selection: Res<Selection>,
+    mut hex_materials: ResMut<Assets<HexMaterial>>,
+) {
+    let highlighted_coord = hovered_tiles
+        .iter()
+        .next()
+        .map_or(Vec2::splat(NO_COORD), |position| position.position.as_f32());
+    let selected_coord = selection
+        .primary()
+        .map_or(Vec2::splat(NO_COORD), |coord| coord.as_f32());
+
+    let ids: Vec<_> = hex_materials.ids().collect();
+    for id in ids {
+        // Only touch the material when something changed, as that re-uploads it
+        let up_to_date = hex_materials.get(id).map_or(true, |material| {
+            material.highlighted_coord == highlighted_coord
+                && material.selected_coord == selected_coord
+        });
+        if !up_to_date {
+            if let Some(material) = hex_materials.get_mut(id) {
+                material.highlighted_coord = highlighted_coord;
+                material.selected_coord = selected_coord;
+            }
+        }
+    }
+}
+
+pub fn setup_occupant_models(
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut occupant_models: ResMut<OccupantModels>,
+) {
+    for (occupant, path, tint, scale) in OCCUPANT_MODEL_TABLE.iter() {
+        occupant_models.models.insert(
+            *occupant,
+            OccupantModel {
+                mesh: asset_server.load(*path),
+                material: materials.add(Color::rgb(tint[0], tint[1], tint[2]).into()),
+                scale: *scale,
+            },
+        );
+    }
+}
+
+// Makes sure every occupied tile in sight has a model standing on it, reusing models of units that moved and models parked in the pool.
+// Models on tiles that go out of sight shrink away like captured ones
+pub fn sync_occupant_models(
+    mut commands: Commands,
+    occupant_models: Res<OccupantModels>,
+    mut presentation: ResMut<OccupantPresentation>,
+    meshes: Res<Assets<Mesh>>,
+    grid: Res<HexGrid>,
+    vision: Res<TeamVision>,
+    tiles: Query<(Entity, &GridPosition, &Occupant, &Parent)>,
+    planets: Query<(&Handle<Mesh>, &GlobalTransform, &Planet)>,
+    mut visuals: Query<(&mut Transform, &mut Visible), With<OccupantVisual>>,
+) {
+    let presentation = &mut *presentation;
+
+    // Release the models of tiles whose occupant left or changed, or that went out of sight
+    let stale_tiles: Vec<Entity> = presentation
+        .active
+        .iter()
+        .filter(|(tile, (kind, _))| match tiles.get(**tile) {
+            Ok((_, grid_position, occupant, _)) => {
+                occupant != kind || !vision.sees(grid.coord_to_index(grid_position.position))
+            }
+            Err(_) => true,
+        })
+        .map(|(tile, _)| *tile)
+        .collect();
+    let mut released: Vec<(Occupant, Entity)> = stale_tiles
+        .iter()
+        .filter_map(|tile| presentation.active.remove(tile))
+        .collect();
+
+    for (tile, grid_position, occupant, parent) in tiles.iter() {
+        if presentation.active.contains_key(&tile)
+            || !vision.sees(grid.coord_to_index(grid_position.position))
+        {
+            continue;
+        }
+        let model = match occupant_models.models.get(occupant) {
+            Some(model) => model,
+            None => continue,
+        };
+        // The planet mesh might still be loading, in which case we try again next frame
+        let target = match planets.get(parent.0).ok().and_then(|(mesh, transform, planet)| {
+            match (planet.shape, grid.sphere()) {
+                (PlanetShape::Sphere { .. }, Some(sphere)) => helpers::sphere_surface_transform(
+                    sphere,
+                    transform,
+                    grid_position.position,
+                    model.scale,
+                ),
+                _ => helpers::hex_surface_transform(
+                    meshes.get(mesh)?,
+                    transform,
+                    grid_position.position,
+                    model.scale,
+                ),
+            }
+        }) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        // A unit that left a tile this frame and shows up on another one has moved there
+        let moved_visual = if occupant.is_unit() {
+            released
+                .iter()
+                .position(|(kind, _)| kind == occupant)
+                .map(|index| released.swap_remove(index).1)
+        } else {
+            None
+        };
+
+        let visual = if let Some(visual) = moved_visual {
+            let mut from = target.translation;
+            if let Ok((mut transform, _)) = visuals.get_mut(visual) {
+                from = transform.translation;
+                transform.rotation = target.rotation;
+                transform.scale = target.scale;
+            }
+            commands.entity(visual).insert(OccupantAnimation::new(
+                OccupantAnimationKind::Move {
+                    from,
+                    to: target.translation,
+                },
+                OCCUPANT_MOVE_DURATION,
+            ));
+            visual
+        } else if let Some(visual) = presentation
+            .pool
+            .get_mut(occupant)
+            .and_then(|pooled| pooled.pop())
+        {
+            if let Ok((mut transform, mut visible)) = visuals.get_mut(visual) {
+                *transform = target;
+                transform.scale = Vec3::ZERO;
+                visible.is_visible = true;
+            }
+            commands.entity(visual).insert(OccupantAnimation::new(
+                OccupantAnimationKind::Appear,
+                OCCUPANT_APPEAR_DURATION,
+            ));
+            visual
+        } else {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: model.mesh.clone(),
+                    material: model.material.clone(),
+                    transform: Transform {
+                        scale: Vec3::ZERO,
+                        ..target
+                    },
+                    ..Default::default()
+                })
+                .insert(OccupantVisual { kind: *occupant })
+                .insert(OccupantAnimation::new(
+                    OccupantAnimationKind::Appear,
+                    OCCUPANT_APPEAR_DURATION,
+                ))
+                .id()
+        };
+        presentation.active.insert(tile, (*occupant, visual));
+    }
+
+    // Whatever was left behind got captured or removed
+    for (_, visual) in released {
+        commands.entity(visual).insert(OccupantAnimation::new(
+            OccupantAnimationKind::Capture,
+            OCCUPANT_CAPTURE_DURATION,
+        ));
+    }
+}
+
+pub fn animate_occupant_models(
+    mut commands: Commands,
+    time: Res<Time>,
+    occupant_models: Res<OccupantModels>,
+    mut presentation: ResMut<OccupantPresentation>,
+    mut animated: Query<(
+        Entity,
+        &OccupantVisual,
+        &mut OccupantAnimation,
+        &mut Transform,
+        &mut Visible,
+    )>,
+) {
+    for (entity, visual, mut animation, mut transform, mut visible) in animated.iter_mut() {
+        animation.elapsed += time.delta_seconds();
+        let t = (animation.elapsed / animation.duration).min(1.0);
+        let eased = wrapped_shader_functions::smoothstep(0.0, 1.0, t);
+        let scale = occupant_models
+            .models
+            .get(&visual.kind)
+            .map_or(1.0, |model| model.scale);
+
+        match animation.kind {
+            OccupantAnimationKind::Appear => {
+                transform.scale = Vec3::splat(scale * eased);
+            }
+            OccupantAnimationKind::Move { from, to } => {
+                let up = transform.rotation * Vec3::Y;
+                let hop = (t * std::f32::consts::PI).sin() * OCCUPANT_MOVE_HOP_HEIGHT;
+                transform.translation = from.lerp(to, eased) + up * hop;
+            }
+            OccupantAnimationKind::Capture => {
+                transform.scale = Vec3::splat(scale * (1.0 - eased));
+                if t >= 1.0 {
+                    // Park the model so the next occupant of the same kind can reuse it
+                    visible.is_visible = false;
+                    presentation
+                        .pool
+                        .entry(visual.kind)
+                        .or_insert_with(Vec::new)
+                        .push(entity);
+                }
+            }
+        }
+
+        if t >= 1.0 {
+            commands.entity(entity).remove::<OccupantAnimation>();
+        }
+    }
+}
+
+// Occupant models are not children of the planet, so they are cleared separately when a game ends
+pub fn clear_occupant_models(
+    mut commands: Commands,
+    mut presentation: ResMut<OccupantPresentation>,
+    visuals: Query<Entity, With<OccupantVisual>>,
+) {
+    for entity in visuals.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *presentation = OccupantPresentation::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_terrain_gets_its_own_shader_color() {
+        let pattern_uv = hex_shader::Vec2::new(0.3, 0.1);
+        let colors: Vec<hex_shader::Vec3> = TERRAIN_TYPES
+            .iter()
+            .map(|terrain| hex_shader::terrain_color(terrain_index(*terrain), 0.5, pattern_uv))
+            .collect();
+        // Unknown terrain indices come out white
+        let unknown = hex_shader::terrain_color(u32::MAX, 0.5, pattern_uv);
+        for (terrain, color) in TERRAIN_TYPES.iter().zip(colors.iter()) {
+            assert_ne!(*color, unknown, "{:?}", terrain);
+            assert_eq!(
+                colors.iter().filter(|other| *other == color).count(),
+                1,
+                "{:?}",
+                terrain
+            );
+        }
+        let water = colors[TERRAIN_TYPES
+            .iter()
+            .position(|t| *t == TerrainType::Water)
+            .unwrap()];
+        assert!(water.z > water.x && water.z > water.y);
+    }
+}
@@ -0,0 +1,346 @@
+// User settings, kept in a ron file next to the game so they survive restarts
+use crate::input_actions::{Action, ActionBindings, InputBinding, REBINDABLE_ACTIONS};
+use crate::localization::{Localization, DEFAULT_LOCALE, LOCALES};
+use crate::orbit_camera::{OrbitCameraBindings, OrbitCameraSettings};
+use bevy::prelude::*;
+use bevy::window::WindowMode;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+const SETTINGS_FILE: &str = "settings.ron";
+// A settings file that could not be read is moved here, so saving doesn't overwrite the hand edits
+const BROKEN_SETTINGS_FILE: &str = "settings.ron.bak";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    // All volumes go from 0 to 1, the channels are scaled by the master volume
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master_volume: 0.8,
+            music_volume: 0.6,
+            effects_volume: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub fullscreen: bool,
+    pub vsync: bool,
+    // Only read at startup
+    pub msaa_samples: u32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            fullscreen: false,
+            vsync: true,
+            msaa_samples: 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings,
+    pub graphics: GraphicsSettings,
+    pub camera_sensitivity: f32,
+    pub camera_bindings: OrbitCameraBindings,
+    pub input: ActionBindings,
+    // One of localization::LOCALES
+    pub language: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            audio: AudioSettings::default(),
+            graphics: GraphicsSettings::default(),
+            camera_sensitivity: 1.0,
+            camera_bindings: OrbitCameraBindings::default(),
+            input: ActionBindings::default(),
+            language: DEFAULT_LOCALE.to_string(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        PathBuf::from(SETTINGS_FILE)
+    }
+
+    // Falls back to the defaults when there is no settings file yet, or it can't be read
+    pub fn load() -> Self {
+        let text = match fs::read_to_string(Self::path()) {
+            Ok(text) => text,
+            Err(_) => return Settings::default(),
+        };
+        match Self::from_ron(&text) {
+            Ok(settings) => settings,
+            Err(error) => {
+                warn!(
+                    "Could not read {}, moving it to {}: {}",
+                    SETTINGS_FILE, BROKEN_SETTINGS_FILE, error
+                );
+                if let Err(error) = fs::rename(Self::path(), BROKEN_SETTINGS_FILE) {
+                    error!("Could not move {}: {}", SETTINGS_FILE, error);
+                }
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let text = match self.to_ron() {
+            Ok(text) => text,
+            Err(error) => {
+                error!("Could not serialize the settings: {}", error);
+                return;
+            }
+        };
+        if let Err(error) = fs::write(Self::path(), text) {
+            error!("Could not write {}: {}", SETTINGS_FILE, error);
+        }
+    }
+
+    pub fn from_ron(text: &str) -> ron::Result<Self> {
+        ron::de::from_str::<Settings>(text).map(Settings::sanitized)
+    }
+
+    pub fn to_ron(&self) -> ron::Result<String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    // Keeps values from a hand edited file in range
+    pub fn sanitized(mut self) -> Self {
+        let audio = &mut self.audio;
+        for volume in [
+            &mut audio.master_volume,
+            &mut audio.music_volume,
+            &mut audio.effects_volume,
+        ]
+        .iter_mut()
+        {
+            **volume = volume.clamp(0.0, 1.0);
+        }
+        self.camera_sensitivity = self.camera_sensitivity.clamp(0.1, 5.0);
+        self.graphics.msaa_samples = match self.graphics.msaa_samples {
+            1 | 2 | 4 | 8 => self.graphics.msaa_samples,
+            _ => 4,
+        };
+        self.input = self.input.with_defaults();
+        if !LOCALES.iter().any(|(id, _)| *id == self.language) {
+            self.language = DEFAULT_LOCALE.to_string();
+        }
+        self
+    }
+}
+
+// Bindings that can be changed from the settings screen, the camera ones only take keys
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BindingSlot {
+    PanForward,
+    PanBack,
+    PanLeft,
+    PanRight,
+    RotateLeft,
+    RotateRight,
+    FocusCapital,
+    Action(Action),
+}
+
+const CAMERA_BINDING_SLOTS: [BindingSlot; 7] = [
+    BindingSlot::PanForward,
+    BindingSlot::PanBack,
+    BindingSlot::PanLeft,
+    BindingSlot::PanRight,
+    BindingSlot::RotateLeft,
+    BindingSlot::RotateRight,
+    BindingSlot::FocusCapital,
+];
+
+pub fn binding_slots() -> Vec<BindingSlot> {
+    REBINDABLE_ACTIONS
+        .iter()
+        .map(|action| BindingSlot::Action(*action))
+        .chain(CAMERA_BINDING_SLOTS.iter().copied())
+        .collect()
+}
+
+impl BindingSlot {
+    fn camera_keys<'a>(&self, bindings: &'a OrbitCameraBindings) -> Option<&'a Vec<KeyCode>> {
+        match self {
+            BindingSlot::PanForward => Some(&bindings.pan_forward),
+            BindingSlot::PanBack => Some(&bindings.pan_back),
+            BindingSlot::PanLeft => Some(&bindings.pan_left),
+            BindingSlot::PanRight => Some(&bindings.pan_right),
+            BindingSlot::RotateLeft => Some(&bindings.rotate_left),
+            BindingSlot::RotateRight => Some(&bindings.rotate_right),
+            BindingSlot::FocusCapital => Some(&bindings.focus_capital),
+            BindingSlot::Action(_) => None,
+        }
+    }
+
+    fn camera_keys_mut<'a>(
+        &self,
+        bindings: &'a mut OrbitCameraBindings,
+    ) -> Option<&'a mut Vec<KeyCode>> {
+        match self {
+            BindingSlot::PanForward => Some(&mut bindings.pan_forward),
+            BindingSlot::PanBack => Some(&mut bindings.pan_back),
+            BindingSlot::PanLeft => Some(&mut bindings.pan_left),
+            BindingSlot::PanRight => Some(&mut bindings.pan_right),
+            BindingSlot::RotateLeft => Some(&mut bindings.rotate_left),
+            BindingSlot::RotateRight => Some(&mut bindings.rotate_right),
+            BindingSlot::FocusCapital => Some(&mut bindings.focus_capital),
+            BindingSlot::Action(_) => None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn bindings(&self, slot: BindingSlot) -> Vec<InputBinding> {
+        match slot {
+            BindingSlot::Action(action) => self.input.get(action).to_vec(),
+            _ => slot
+                .camera_keys(&self.camera_bindings)
+                .map_or(Vec::new(), |keys| {
+                    keys.iter().map(|key| InputBinding::Key(*key)).collect()
+                }),
+        }
+    }
+
+    // Replaces the bindings of the slot, returns false if the slot can't take this kind of input
+    pub fn rebind(&mut self, slot: BindingSlot, binding: InputBinding) -> bool {
+        match (slot, binding) {
+            (BindingSlot::Action(action), _) => {
+                self.input.bindings.insert(action, vec![binding]);
+                true
+            }
+            (_, InputBinding::Key(key)) => match slot.camera_keys_mut(&mut self.camera_bindings) {
+                Some(keys) => {
+                    *keys = vec![key];
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let settings = Settings::load();
+        app.insert_resource(Msaa {
+            samples: settings.graphics.msaa_samples,
+        })
+        .insert_resource(settings)
+        .add_system(apply_settings.system());
+    }
+}
+
+// Pushes changed settings to the systems that use them
+fn apply_settings(
+    settings: Res<Settings>,
+    mut camera_settings: ResMut<OrbitCameraSettings>,
+    mut action_bindings: ResMut<ActionBindings>,
+    mut localization: ResMut<Localization>,
+    mut windows: ResMut<Windows>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    camera_settings.sensitivity = settings.camera_sensitivity;
+    camera_settings.bindings = settings.camera_bindings.clone();
+    *action_bindings = settings.input.clone();
+    // Switching rebuilds the menus, so only touch it when the language really changed
+    if localization.language() != settings.language {
+        localization.set_language(&settings.language);
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_vsync(settings.graphics.vsync);
+        window.set_mode(if settings.graphics.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_survive_a_round_trip() {
+        let mut settings = Settings::default();
+        settings.audio.music_volume = 0.25;
+        settings.graphics.fullscreen = true;
+        settings.graphics.msaa_samples = 8;
+        settings.camera_sensitivity = 2.5;
+        settings.language = LOCALES.last().unwrap().0.to_string();
+        settings.rebind(
+            BindingSlot::Action(Action::Undo),
+            InputBinding::Chord(KeyCode::LAlt, KeyCode::U),
+        );
+        settings.rebind(BindingSlot::PanLeft, InputBinding::Key(KeyCode::H));
+
+        let text = settings.to_ron().unwrap();
+        assert_eq!(Settings::from_ron(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_get_their_defaults() {
+        let settings = Settings::from_ron("(audio: (music_volume: 0.5))").unwrap();
+        assert_eq!(settings.audio.music_volume, 0.5);
+        assert_eq!(
+            settings.audio.master_volume,
+            AudioSettings::default().master_volume
+        );
+        assert_eq!(settings.graphics, GraphicsSettings::default());
+        assert_eq!(settings.camera_bindings, OrbitCameraBindings::default());
+        assert_eq!(settings.input, ActionBindings::default());
+    }
+
+    #[test]
+    fn out_of_range_values_are_sanitized() {
+        let mut settings = Settings::default();
+        settings.audio.master_volume = 3.0;
+        settings.audio.effects_volume = -1.0;
+        settings.camera_sensitivity = 100.0;
+        settings.graphics.msaa_samples = 3;
+        settings.language = "xx-XX".to_string();
+        settings.input.bindings.remove(&Action::Select);
+
+        let settings = settings.sanitized();
+        assert_eq!(settings.audio.master_volume, 1.0);
+        assert_eq!(settings.audio.effects_volume, 0.0);
+        assert_eq!(settings.camera_sensitivity, 5.0);
+        assert_eq!(settings.graphics.msaa_samples, 4);
+        assert_eq!(settings.language, DEFAULT_LOCALE);
+        assert_eq!(
+            settings.input.get(Action::Select),
+            ActionBindings::default().get(Action::Select)
+        );
+    }
+
+    #[test]
+    fn a_broken_file_is_an_error_instead_of_the_defaults() {
+        assert!(Settings::from_ron("(audio: (master_volume: \"loud\"))").is_err());
+    }
+}
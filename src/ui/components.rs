@@ -1,5 +1,38 @@
+use super::types::UiAction;
+use crate::app_state::AppScreen;
+
 pub struct Units;
 pub struct Turn;
 pub struct Resources;
 
 pub struct Tile;
+
+// The minimap image, clicking it moves the camera
+pub struct Minimap;
+
+// A button of the widget toolkit, clicking it sends its action
+pub struct ActionButton {
+    pub action: UiAction,
+    pub enabled: bool,
+}
+
+// Text shown in the tooltip panel while the widget is hovered
+pub struct Tooltip {
+    pub text: String,
+}
+
+// The single panel that shows the tooltip of the hovered widget
+pub struct TooltipPanel;
+
+// A column of labels, one per item
+pub struct List {
+    pub items: Vec<String>,
+}
+
+// The dialog asking to confirm the end of the turn
+pub struct EndTurnPromptRoot;
+
+// Top node of everything shown on a screen, despawned when the screen is left
+pub struct ScreenRoot {
+    pub screen: AppScreen,
+}
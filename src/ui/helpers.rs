@@ -0,0 +1,439 @@
+use super::types::*;
+use crate::gameplay::components::{
+    HexGrid, Occupant, PlanetShape, Purchase, TerrainType, TreeKind, UnitTier, UNIT_TIERS,
+};
+use crate::gameplay::rules::{self, Board};
+use crate::gameplay::ruleset::{RuleSet, StartingPositions};
+use crate::gameplay::topology::{HexTopology, WrapMode};
+use crate::input_actions::{Action, AxisDirection, InputBinding};
+use crate::localization::Localization;
+use crate::settings::BindingSlot;
+use bevy::prelude::*;
+use bevy::render::texture::{Extent3d, TextureDimension, TextureFormat};
+use bevy::utils::HashMap;
+use fluent_bundle::FluentValue;
+use std::f32::consts::PI;
+
+// Flat grids get one pixel per doubled-width column and row, the pixels between two cells show the left one.
+// Spheres get an equirectangular projection
+pub fn build_minimap_layout(
+    grid: &HexGrid,
+    cell_positions: HashMap<IVec2, Vec3>,
+    surface: MinimapSurface,
+) -> MinimapLayout {
+    let (width, height, pixel_cells) = match grid.sphere() {
+        Some(sphere) => {
+            let width = MINIMAP_SPHERE_RESOLUTION;
+            let height = MINIMAP_SPHERE_RESOLUTION / 2;
+            let mut pixel_cells = Vec::with_capacity((width * height) as usize);
+            for py in 0..height {
+                for px in 0..width {
+                    let direction = equirectangular_direction(px, py, width, height);
+                    pixel_cells.push(Some(sphere.index_to_coord(sphere.nearest_cell(direction))));
+                }
+            }
+            (width, height, pixel_cells)
+        }
+        None => {
+            let width = grid.width as u32;
+            let height = grid.height as u32;
+            let mut pixel_cells = Vec::with_capacity((width * height) as usize);
+            for py in 0..grid.height {
+                for px in 0..grid.width {
+                    // Row 0 of the image is the top of the map
+                    let y = grid.height - 1 - py;
+                    let cell = [IVec2::new(px, y), IVec2::new(px - 1, y), IVec2::new(px + 1, y)]
+                        .iter()
+                        .copied()
+                        .find(|coord| grid.contains(*coord));
+                    pixel_cells.push(cell);
+                }
+            }
+            (width, height, pixel_cells)
+        }
+    };
+
+    let mut cell_pixels = HashMap::default();
+    for (index, cell) in pixel_cells.iter().enumerate() {
+        if let Some(cell) = cell {
+            let pixel = IVec2::new((index as u32 % width) as i32, (index as u32 / width) as i32);
+            cell_pixels.entry(*cell).or_insert(pixel);
+        }
+    }
+
+    MinimapLayout {
+        width,
+        height,
+        pixel_cells,
+        cell_pixels,
+        cell_positions,
+        surface,
+    }
+}
+
+fn equirectangular_direction(px: u32, py: u32, width: u32, height: u32) -> Vec3 {
+    let longitude = (px as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+    let latitude = PI * 0.5 - (py as f32 + 0.5) / height as f32 * PI;
+    Vec3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        latitude.cos() * longitude.cos(),
+    )
+}
+
+pub fn new_minimap_texture(width: u32, height: u32) -> Texture {
+    let mut texture = Texture::new_fill(
+        Extent3d::new(width, height, 1),
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    texture.sampler.min_filter = bevy::render::texture::FilterMode::Nearest;
+    texture.sampler.mag_filter = bevy::render::texture::FilterMode::Nearest;
+    texture
+}
+
+pub fn put_pixel(texture: &mut Texture, width: u32, pixel: IVec2, color: Color) {
+    let height = texture.data.len() as u32 / 4 / width;
+    if pixel.x < 0 || pixel.y < 0 || pixel.x as u32 >= width || pixel.y as u32 >= height {
+        return;
+    }
+    let offset = ((pixel.y as u32 * width + pixel.x as u32) * 4) as usize;
+    let color = color.as_rgba_f32();
+    for channel in 0..4 {
+        texture.data[offset + channel] = (color[channel] * 255.0) as u8;
+    }
+}
+
+// Bresenham, so the frustum outline is one pixel wide
+pub fn draw_line(texture: &mut Texture, width: u32, from: IVec2, to: IVec2, color: Color) {
+    let delta = IVec2::new((to.x - from.x).abs(), -(to.y - from.y).abs());
+    let step = IVec2::new((to.x - from.x).signum(), (to.y - from.y).signum());
+    let mut error = delta.x + delta.y;
+    let mut current = from;
+    loop {
+        put_pixel(texture, width, current, color);
+        if current == to {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= delta.y {
+            error += delta.y;
+            current.x += step.x;
+        }
+        if doubled <= delta.x {
+            error += delta.x;
+            current.y += step.y;
+        }
+    }
+}
+
+// Where a ray from the camera meets the planet surface
+pub fn intersect_surface(surface: MinimapSurface, origin: Vec3, direction: Vec3) -> Option<Vec3> {
+    match surface {
+        MinimapSurface::Plane { height } => {
+            if direction.y.abs() < 1e-5 {
+                return None;
+            }
+            let t = (height - origin.y) / direction.y;
+            if t > 0.0 {
+                Some(origin + direction * t)
+            } else {
+                None
+            }
+        }
+        MinimapSurface::Sphere { center, radius } => {
+            let offset = origin - center;
+            let b = offset.dot(direction);
+            let c = offset.length_squared() - radius * radius;
+            let discriminant = b * b - c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let t = -b - discriminant.sqrt();
+            if t > 0.0 {
+                Some(origin + direction * t)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// Cell closest to a world position
+pub fn nearest_cell(layout: &MinimapLayout, position: Vec3) -> Option<IVec2> {
+    layout
+        .cell_positions
+        .iter()
+        .min_by(|(_, a), (_, b)| {
+            a.distance_squared(position)
+                .partial_cmp(&b.distance_squared(position))
+                .unwrap()
+        })
+        .map(|(coord, _)| *coord)
+}
+
+pub fn province_summary(board: &Board, coord: IVec2) -> Option<ProvinceSummary> {
+    let tile = board.tile(coord)?;
+    if !rules::is_land(board.rules, tile) {
+        return None;
+    }
+    let province = rules::province(board, coord);
+    let mut units = [0; 4];
+    for tile in province.iter().filter_map(|coord| board.tile(*coord)) {
+        if let Some(Occupant::Unit(tier)) = tile.occupant {
+            if let Some(index) = UNIT_TIERS.iter().position(|t| *t == tier) {
+                units[index] += 1;
+            }
+        }
+    }
+    Some(ProvinceSummary {
+        treasury: rules::province_treasury(board, &province),
+        income: rules::province_income(board, &province),
+        upkeep: rules::province_upkeep(board, &province),
+        units,
+    })
+}
+
+pub fn tile_summary(board: &Board, coord: IVec2) -> Option<TileSummary> {
+    let tile = board.tile(coord)?;
+    Some(TileSummary {
+        terrain: tile.terrain,
+        team: tile.team,
+        occupant: tile.occupant,
+        defense: rules::defense(board, coord),
+    })
+}
+
+pub fn terrain_name(loc: &Localization, terrain: TerrainType) -> String {
+    loc.text(match terrain {
+        TerrainType::Land => "terrain-land",
+        TerrainType::Water => "terrain-water",
+        TerrainType::Mountain => "terrain-mountain",
+        TerrainType::Forest => "terrain-forest",
+        TerrainType::Plains => "terrain-plains",
+        TerrainType::Desert => "terrain-desert",
+        TerrainType::Shallows => "terrain-shallows",
+        TerrainType::Bridge => "terrain-bridge",
+    })
+}
+
+pub fn tier_name(loc: &Localization, tier: UnitTier) -> String {
+    loc.text(match tier {
+        UnitTier::Peasant => "tier-peasant",
+        UnitTier::Spearman => "tier-spearman",
+        UnitTier::Knight => "tier-knight",
+        UnitTier::Baron => "tier-baron",
+    })
+}
+
+// "3 knights", in the plural form of the language
+pub fn unit_count(loc: &Localization, tier: UnitTier, count: i32) -> String {
+    let key = match tier {
+        UnitTier::Peasant => "units-peasant",
+        UnitTier::Spearman => "units-spearman",
+        UnitTier::Knight => "units-knight",
+        UnitTier::Baron => "units-baron",
+    };
+    loc.format(key, &[("count", count.into())])
+}
+
+pub fn occupant_name(loc: &Localization, occupant: Option<Occupant>) -> String {
+    let key = match occupant {
+        None => "occupant-empty",
+        Some(Occupant::Unit(tier)) => return tier_name(loc, tier),
+        Some(Occupant::Castle) => "occupant-castle",
+        Some(Occupant::Capital) => "occupant-capital",
+        Some(Occupant::Tree(TreeKind::Pine)) => "occupant-pine",
+        Some(Occupant::Tree(TreeKind::Palm)) => "occupant-palm",
+        Some(Occupant::Grave) => "occupant-grave",
+    };
+    loc.text(key)
+}
+
+pub fn purchase_name(loc: &Localization, item: Purchase) -> String {
+    match item {
+        Purchase::Unit(tier) => tier_name(loc, tier),
+        Purchase::Castle => loc.text("occupant-castle"),
+    }
+}
+
+// Cost, what it does and which units it merges into
+pub fn purchase_tooltip(
+    loc: &Localization,
+    ruleset: &RuleSet,
+    item: Purchase,
+    treasury: Option<i32>,
+) -> String {
+    let cost = ruleset.purchase_cost(item);
+    let mut sentences = vec![loc.format(
+        "purchase-cost",
+        &[
+            ("name", purchase_name(loc, item).into()),
+            ("cost", cost.into()),
+        ],
+    )];
+    match item {
+        Purchase::Unit(tier) => {
+            let merges: Vec<String> = UNIT_TIERS
+                .iter()
+                .filter_map(|other| {
+                    ruleset.merged_tier(tier, *other).map(|merged| {
+                        loc.format(
+                            "unit-merge",
+                            &[
+                                ("other", tier_name(loc, *other).into()),
+                                ("merged", tier_name(loc, merged).into()),
+                            ],
+                        )
+                    })
+                })
+                .collect();
+            sentences.push(loc.format(
+                "unit-stats",
+                &[
+                    ("strength", ruleset.unit(tier).strength.into()),
+                    ("upkeep", ruleset.unit(tier).upkeep.into()),
+                ],
+            ));
+            if !merges.is_empty() {
+                sentences.push(loc.format("unit-merges", &[("merges", merges.join(", ").into())]));
+            }
+        }
+        Purchase::Castle => sentences.push(loc.format(
+            "castle-defense",
+            &[("strength", ruleset.castle.strength.into())],
+        )),
+    }
+    match treasury {
+        Some(treasury) if treasury < cost => {
+            sentences.push(loc.format("not-enough-gold", &[("treasury", treasury.into())]))
+        }
+        None => sentences.push(loc.text("needs-capital")),
+        _ => {}
+    }
+    sentences.join(" ")
+}
+
+// Texts of the HUD buttons
+pub fn button_label(loc: &Localization, ruleset: &RuleSet, action: UiAction) -> Option<String> {
+    match action {
+        UiAction::NextTurn => Some(loc.text("next-turn")),
+        UiAction::Buy(item) => Some(loc.format(
+            "purchase-button",
+            &[
+                ("name", purchase_name(loc, item).into()),
+                ("cost", ruleset.purchase_cost(item).into()),
+            ],
+        )),
+        _ => None,
+    }
+}
+
+pub fn planet_shape_name(loc: &Localization, shape: PlanetShape) -> String {
+    match shape {
+        PlanetShape::HexagonCap => loc.text("planet-hexagon-cap"),
+        PlanetShape::Sphere { subdivisions } => loc.format(
+            "planet-sphere",
+            &[("cells", (10 * subdivisions * subdivisions + 2).into())],
+        ),
+    }
+}
+
+pub fn wrap_name(loc: &Localization, wrap: WrapMode) -> String {
+    loc.text(match wrap {
+        WrapMode::None => "wrap-none",
+        WrapMode::Horizontal => "wrap-cylinder",
+        WrapMode::Torus => "wrap-torus",
+    })
+}
+
+pub fn starting_positions_name(loc: &Localization, positions: Option<StartingPositions>) -> String {
+    loc.text(match positions {
+        None => "positions-map",
+        Some(StartingPositions::Random) => "positions-random",
+        Some(StartingPositions::Clustered) => "positions-clustered",
+    })
+}
+
+// The number, or "Off" for a variant that isn't played
+pub fn optional_value(loc: &Localization, value: Option<i32>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => on_off(loc, false),
+    }
+}
+
+pub fn action_name(loc: &Localization, action: Action) -> String {
+    loc.text(match action {
+        Action::Select => "action-select",
+        Action::MultiSelect => "action-multi-select",
+        Action::Cancel => "action-cancel",
+        Action::EndTurn => "action-end-turn",
+        Action::Undo => "action-undo",
+        Action::RotateCamera => "action-rotate-camera",
+        Action::Zoom => "action-zoom",
+        Action::CycleUnits => "action-cycle-units",
+        Action::CycleUnitsBack => "action-cycle-units-back",
+        Action::Pause => "action-pause",
+        Action::CursorUp => "action-cursor-up",
+        Action::CursorDown => "action-cursor-down",
+        Action::CursorLeft => "action-cursor-left",
+        Action::CursorRight => "action-cursor-right",
+        Action::CameraLeft => "action-camera-left",
+        Action::CameraRight => "action-camera-right",
+        Action::CameraUp => "action-camera-up",
+        Action::CameraDown => "action-camera-down",
+    })
+}
+
+pub fn binding_name(loc: &Localization, slot: BindingSlot) -> String {
+    let key = match slot {
+        BindingSlot::PanForward => "binding-pan-forward",
+        BindingSlot::PanBack => "binding-pan-back",
+        BindingSlot::PanLeft => "binding-pan-left",
+        BindingSlot::PanRight => "binding-pan-right",
+        BindingSlot::RotateLeft => "binding-rotate-left",
+        BindingSlot::RotateRight => "binding-rotate-right",
+        BindingSlot::FocusCapital => "binding-focus-capital",
+        BindingSlot::Action(action) => return action_name(loc, action),
+    };
+    loc.text(key)
+}
+
+// Keys and buttons keep their english names from bevy
+pub fn input_name(loc: &Localization, binding: &InputBinding) -> String {
+    let button = |name: String| -> [(&str, FluentValue); 1] { [("button", name.into())] };
+    match binding {
+        InputBinding::Key(key) => format!("{:?}", key),
+        InputBinding::Chord(modifier, key) => format!("{:?}+{:?}", modifier, key),
+        InputBinding::Mouse(mouse_button) => {
+            loc.format("input-mouse", &button(format!("{:?}", mouse_button)))
+        }
+        InputBinding::GamepadButton(pad_button) => {
+            loc.format("input-pad", &button(format!("{:?}", pad_button)))
+        }
+        InputBinding::MouseWheel => loc.text("input-mouse-wheel"),
+        InputBinding::GamepadAxis(axis, AxisDirection::Positive) => {
+            loc.format("input-pad", &button(format!("{:?}+", axis)))
+        }
+        InputBinding::GamepadAxis(axis, AxisDirection::Negative) => {
+            loc.format("input-pad", &button(format!("{:?}-", axis)))
+        }
+    }
+}
+
+pub fn bindings_name(loc: &Localization, bindings: &[InputBinding]) -> String {
+    if bindings.is_empty() {
+        return "-".to_string();
+    }
+    bindings
+        .iter()
+        .map(|binding| input_name(loc, binding))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn on_off(loc: &Localization, value: bool) -> String {
+    loc.text(if value { "on" } else { "off" })
+}
@@ -1,20 +1,61 @@
 use bevy::prelude::*;
 
 pub mod components;
+mod helpers;
+mod screens;
 mod setup;
 mod systems;
 mod types;
+mod widgets;
+
+use crate::app_state::run_if_in_game;
 
 pub struct UIPlugins;
 
 impl Plugin for UIPlugins {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(systems::update_units.system())
-            .add_system(systems::update_resources.system())
-            .add_system(systems::update_turns.system())
-            .add_system(systems::button_system.system())
-            .add_system(systems::update_tile.system())
-            .init_resource::<types::ButtonMaterials>()
+        app.add_event::<types::UiActionEvent>()
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_in_game.system())
+                    .with_system(
+                        systems::update_selection_summary
+                            .system()
+                            .label("selection_summary"),
+                    )
+                    .with_system(systems::update_units.system().after("selection_summary"))
+                    .with_system(systems::update_resources.system().after("selection_summary"))
+                    .with_system(systems::update_turns.system())
+                    .with_system(systems::update_tile.system().after("selection_summary"))
+                    .with_system(
+                        systems::update_purchase_buttons
+                            .system()
+                            .after("selection_summary")
+                            .before("ui_buttons"),
+                    )
+                    .with_system(systems::handle_ui_actions.system().after("ui_buttons"))
+                    .with_system(systems::update_button_labels.system())
+                    .with_system(screens::end_turn_prompt_system.system())
+                    .with_system(systems::build_minimap_layout.system().label("minimap_layout"))
+                    .with_system(systems::update_minimap.system().after("minimap_layout"))
+                    .with_system(systems::minimap_click_system.system().after("minimap_layout")),
+            )
+            .add_system(widgets::button_system.system().label("ui_buttons"))
+            .add_system(widgets::tooltip_system.system())
+            .add_system(widgets::list_system.system())
+            .add_system(screens::handle_menu_actions.system().after("ui_buttons"))
+            .add_system(screens::pause_system.system().before("rebind"))
+            .add_system(
+                screens::rebind_system
+                    .system()
+                    .label("rebind")
+                    .before("ui_buttons"),
+            )
+            .add_system(screens::sync_screen_roots.system())
+            .init_resource::<types::UiTheme>()
+            .init_resource::<types::MinimapState>()
+            .init_resource::<types::SelectionSummary>()
+            .init_resource::<types::Rebinding>()
             .add_startup_system(setup::setup.system());
     }
 }
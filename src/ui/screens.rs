@@ -0,0 +1,721 @@
+// Menus around the game: every AppScreen has a root node that is spawned when the screen is entered
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use super::components::*;
+use super::helpers::{
+    binding_name, bindings_name, on_off, optional_value, planet_shape_name,
+    starting_positions_name, wrap_name,
+};
+use super::setup;
+use super::types::*;
+use super::widgets;
+use crate::app_state::{AppScreen, GameResult};
+use crate::gameplay::components::{EndTurnPrompt, PlanetShape, Scenario};
+use crate::gameplay::ruleset::{FogOfWar, RuleSet, StartingPositions};
+use crate::gameplay::topology::WrapMode;
+use crate::input_actions::{Action, ActionState, InputBinding};
+use crate::localization::{language_name, Localization, LOCALES};
+use crate::settings::{binding_slots, BindingSlot, Settings};
+
+const MENU_BUTTON_SIZE: (f32, f32) = (260.0, 55.0);
+const SMALL_BUTTON_SIZE: (f32, f32) = (40.0, 30.0);
+const VOLUME_STEP: f32 = 0.1;
+const SENSITIVITY_STEP: f32 = 0.1;
+const FLAT_MAP_SIZES: [i32; 3] = [8, 12, 16];
+const SPHERE_SUBDIVISIONS: [u32; 2] = [4, 6];
+// The values the variants cycle through, None turns them off
+const VISION_RADII: [Option<i32>; 4] = [None, Some(1), Some(2), Some(3)];
+const TREASURY_CAPS: [Option<i32>; 3] = [None, Some(50), Some(100)];
+const SUDDEN_DEATH_ROUNDS: [Option<i32>; 3] = [None, Some(20), Some(40)];
+
+fn menu_button_size() -> Size<Val> {
+    Size::new(Val::Px(MENU_BUTTON_SIZE.0), Val::Px(MENU_BUTTON_SIZE.1))
+}
+
+fn small_button_size() -> Size<Val> {
+    Size::new(Val::Px(SMALL_BUTTON_SIZE.0), Val::Px(SMALL_BUTTON_SIZE.1))
+}
+
+// Full screen node with a centered column panel, `build` fills the panel
+fn menu(
+    commands: &mut Commands,
+    theme: &UiTheme,
+    title: &str,
+    build: impl FnOnce(&mut ChildBuilder),
+) -> Entity {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: theme.transparent.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            widgets::panel(
+                parent,
+                theme,
+                Style {
+                    flex_direction: FlexDirection::ColumnReverse,
+                    align_items: AlignItems::Center,
+                    padding: Rect::all(Val::Px(20.0)),
+                    ..Default::default()
+                },
+            )
+            .with_children(|parent| {
+                widgets::label(parent, theme, title, theme.font_size * 1.5);
+                build(parent);
+            });
+        })
+        .id()
+}
+
+// A label followed by its buttons
+fn row(parent: &mut ChildBuilder, theme: &UiTheme, text: String, buttons: &[(UiAction, String)]) {
+    widgets::group(
+        parent,
+        theme,
+        Style {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::SpaceBetween,
+            size: Size::new(Val::Px(400.0), Val::Auto),
+            ..Default::default()
+        },
+    )
+    .with_children(|parent| {
+        widgets::label(parent, theme, text, theme.small_font_size);
+        widgets::group(parent, theme, Style::default()).with_children(|parent| {
+            for (action, text) in buttons.iter() {
+                let size = if text.chars().count() <= 1 {
+                    small_button_size()
+                } else {
+                    Size::new(Val::Px(120.0), Val::Px(SMALL_BUTTON_SIZE.1))
+                };
+                widgets::button(parent, theme, *action, text.as_str(), size);
+            }
+        });
+    });
+}
+
+fn main_menu(commands: &mut Commands, theme: &UiTheme, loc: &Localization) -> Entity {
+    menu(commands, theme, &loc.text("game-title"), |parent| {
+        widgets::button(
+            parent,
+            theme,
+            UiAction::NewGame,
+            loc.text("menu-new-game"),
+            menu_button_size(),
+        );
+        widgets::button(
+            parent,
+            theme,
+            UiAction::OpenSettings,
+            loc.text("menu-settings"),
+            menu_button_size(),
+        );
+        widgets::button(
+            parent,
+            theme,
+            UiAction::Quit,
+            loc.text("menu-quit"),
+            menu_button_size(),
+        );
+    })
+}
+
+fn new_game_setup(
+    commands: &mut Commands,
+    theme: &UiTheme,
+    loc: &Localization,
+    scenario: &Scenario,
+) -> Entity {
+    let change = |action: UiAction| [(action, loc.text("menu-change"))];
+    menu(commands, theme, &loc.text("new-game-title"), |parent| {
+        row(
+            parent,
+            theme,
+            loc.format(
+                "setup-planet",
+                &[(
+                    "planet",
+                    planet_shape_name(loc, scenario.planet_shape).into(),
+                )],
+            ),
+            &change(UiAction::CyclePlanetShape),
+        );
+        // Spheres have neither a size nor edges to wrap
+        if scenario.planet_shape == PlanetShape::HexagonCap {
+            row(
+                parent,
+                theme,
+                loc.format(
+                    "setup-size",
+                    &[
+                        ("width", scenario.width.into()),
+                        ("height", scenario.height.into()),
+                    ],
+                ),
+                &change(UiAction::CycleMapSize),
+            );
+            row(
+                parent,
+                theme,
+                loc.format(
+                    "setup-wrap",
+                    &[("wrap", wrap_name(loc, scenario.wrap).into())],
+                ),
+                &change(UiAction::CycleWrap),
+            );
+        }
+        let variants = &scenario.variants;
+        let variant_rows = [
+            (
+                VariantControl::FogOfWar,
+                "setup-fog-of-war",
+                optional_value(loc, variants.fog_of_war.map(|fog| fog.vision_radius)),
+            ),
+            (
+                VariantControl::Trees,
+                "setup-trees",
+                on_off(loc, !variants.no_trees),
+            ),
+            (
+                VariantControl::TreasuryCap,
+                "setup-treasury-cap",
+                optional_value(loc, variants.treasury_cap),
+            ),
+            (
+                VariantControl::StartingPositions,
+                "setup-starting-positions",
+                starting_positions_name(loc, variants.starting_positions),
+            ),
+            (
+                VariantControl::TerrainIncome,
+                "setup-terrain-income",
+                on_off(loc, variants.terrain_income),
+            ),
+            (
+                VariantControl::SuddenDeath,
+                "setup-sudden-death",
+                optional_value(loc, variants.sudden_death),
+            ),
+        ];
+        for (control, key, value) in variant_rows.iter() {
+            row(
+                parent,
+                theme,
+                loc.format(*key, &[("value", value.clone().into())]),
+                &change(UiAction::CycleVariant(*control)),
+            );
+        }
+        widgets::button(
+            parent,
+            theme,
+            UiAction::StartGame,
+            loc.text("menu-start"),
+            menu_button_size(),
+        );
+        widgets::button(
+            parent,
+            theme,
+            UiAction::ToMainMenu,
+            loc.text("menu-back"),
+            menu_button_size(),
+        );
+    })
+}
+
+fn pause_menu(commands: &mut Commands, theme: &UiTheme, loc: &Localization) -> Entity {
+    menu(commands, theme, &loc.text("paused-title"), |parent| {
+        widgets::button(
+            parent,
+            theme,
+            UiAction::Resume,
+            loc.text("menu-resume"),
+            menu_button_size(),
+        );
+        widgets::button(
+            parent,
+            theme,
+            UiAction::OpenSettings,
+            loc.text("menu-settings"),
+            menu_button_size(),
+        );
+        widgets::button(
+            parent,
+            theme,
+            UiAction::ToMainMenu,
+            loc.text("menu-main-menu"),
+            menu_button_size(),
+        );
+    })
+}
+
+fn settings_screen(
+    commands: &mut Commands,
+    theme: &UiTheme,
+    loc: &Localization,
+    settings: &Settings,
+    rebinding: &Rebinding,
+) -> Entity {
+    let percent = |volume: f32| format!("{:.0}%", volume * 100.0);
+    let stepper = |control: SettingControl| {
+        [
+            (UiAction::Adjust(control, -1), "-".to_string()),
+            (UiAction::Adjust(control, 1), "+".to_string()),
+        ]
+    };
+    let column = Style {
+        flex_direction: FlexDirection::ColumnReverse,
+        margin: Rect::all(Val::Px(10.0)),
+        ..Default::default()
+    };
+    let setting = |key: &str, value: String| loc.format(key, &[("value", value.into())]);
+    let toggle = |action: UiAction| [(action, loc.text("menu-toggle"))];
+    menu(commands, theme, &loc.text("settings-title"), |parent| {
+        let audio = &settings.audio;
+        // General settings on the left, bindings to the right of them
+        widgets::group(parent, theme, Style::default()).with_children(|parent| {
+            widgets::group(parent, theme, column.clone()).with_children(|parent| {
+                row(
+                    parent,
+                    theme,
+                    setting("setting-master-volume", percent(audio.master_volume)),
+                    &stepper(SettingControl::MasterVolume),
+                );
+                row(
+                    parent,
+                    theme,
+                    setting("setting-music-volume", percent(audio.music_volume)),
+                    &stepper(SettingControl::MusicVolume),
+                );
+                row(
+                    parent,
+                    theme,
+                    setting("setting-effects-volume", percent(audio.effects_volume)),
+                    &stepper(SettingControl::EffectsVolume),
+                );
+                row(
+                    parent,
+                    theme,
+                    setting(
+                        "setting-camera-sensitivity",
+                        format!("{:.1}", settings.camera_sensitivity),
+                    ),
+                    &stepper(SettingControl::CameraSensitivity),
+                );
+                row(
+                    parent,
+                    theme,
+                    setting(
+                        "setting-fullscreen",
+                        on_off(loc, settings.graphics.fullscreen),
+                    ),
+                    &toggle(UiAction::ToggleFullscreen),
+                );
+                row(
+                    parent,
+                    theme,
+                    setting("setting-vsync", on_off(loc, settings.graphics.vsync)),
+                    &toggle(UiAction::ToggleVsync),
+                );
+                row(
+                    parent,
+                    theme,
+                    setting(
+                        "setting-language",
+                        language_name(&settings.language).to_string(),
+                    ),
+                    &[(UiAction::CycleLanguage, loc.text("menu-change"))],
+                );
+            });
+            // Actions in one column and the camera keys in the other
+            let (action_slots, camera_slots): (Vec<BindingSlot>, Vec<BindingSlot>) =
+                binding_slots()
+                    .into_iter()
+                    .partition(|slot| matches!(slot, BindingSlot::Action(_)));
+            for slots in [action_slots, camera_slots].iter() {
+                widgets::group(parent, theme, column.clone()).with_children(|parent| {
+                    for slot in slots.iter() {
+                        let bindings = if rebinding.slot == Some(*slot) {
+                            loc.text("binding-waiting")
+                        } else {
+                            bindings_name(loc, &settings.bindings(*slot))
+                        };
+                        row(
+                            parent,
+                            theme,
+                            loc.format(
+                                "binding-row",
+                                &[
+                                    ("name", binding_name(loc, *slot).into()),
+                                    ("bindings", bindings.into()),
+                                ],
+                            ),
+                            &[(UiAction::Rebind(*slot), loc.text("menu-rebind"))],
+                        );
+                    }
+                });
+            }
+        });
+        widgets::button(
+            parent,
+            theme,
+            UiAction::CloseSettings,
+            loc.text("menu-back"),
+            menu_button_size(),
+        );
+    })
+}
+
+fn game_over(
+    commands: &mut Commands,
+    theme: &UiTheme,
+    loc: &Localization,
+    result: Option<&GameResult>,
+) -> Entity {
+    let text = match result {
+        Some(GameResult {
+            winner: Some(winner),
+            turns,
+        }) => loc.format(
+            "game-over-winner",
+            &[("team", (*winner).into()), ("turns", (*turns).into())],
+        ),
+        _ => loc.text("game-over-no-winner"),
+    };
+    menu(commands, theme, &loc.text("game-over-title"), |parent| {
+        widgets::label(parent, theme, text, theme.font_size);
+        widgets::button(
+            parent,
+            theme,
+            UiAction::NewGame,
+            loc.text("menu-new-game"),
+            menu_button_size(),
+        );
+        widgets::button(
+            parent,
+            theme,
+            UiAction::ToMainMenu,
+            loc.text("menu-main-menu"),
+            menu_button_size(),
+        );
+    })
+}
+
+fn end_turn_prompt(
+    commands: &mut Commands,
+    theme: &UiTheme,
+    loc: &Localization,
+    unmoved_units: usize,
+) -> Entity {
+    let text = loc.format("end-turn-unmoved", &[("count", unmoved_units.into())]);
+    menu(commands, theme, &loc.text("end-turn-title"), |parent| {
+        widgets::label(parent, theme, text, theme.font_size);
+        widgets::button(
+            parent,
+            theme,
+            UiAction::ConfirmEndTurn,
+            loc.text("end-turn-confirm"),
+            menu_button_size(),
+        );
+        widgets::button(
+            parent,
+            theme,
+            UiAction::KeepPlaying,
+            loc.text("end-turn-keep-playing"),
+            menu_button_size(),
+        );
+    })
+}
+
+// Shows the end turn prompt over the HUD while it waits for an answer.
+// It belongs to the InGame screen, so it goes away with the game
+pub fn end_turn_prompt_system(
+    mut commands: Commands,
+    theme: Res<UiTheme>,
+    loc: Res<Localization>,
+    prompt: Res<EndTurnPrompt>,
+    roots: Query<Entity, With<EndTurnPromptRoot>>,
+) {
+    if !prompt.is_changed() && !loc.is_changed() {
+        return;
+    }
+    for entity in roots.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(unmoved_units) = prompt.unmoved_units {
+        let root = end_turn_prompt(&mut commands, &theme, &loc, unmoved_units);
+        commands
+            .entity(root)
+            .insert(EndTurnPromptRoot)
+            .insert(ScreenRoot {
+                screen: AppScreen::InGame,
+            });
+    }
+}
+
+// Spawns the root of the current screen and despawns the ones that were left.
+// The HUD stays while the game is paused underneath, screens that show values are rebuilt when they change.
+pub fn sync_screen_roots(
+    mut commands: Commands,
+    screen: Res<State<AppScreen>>,
+    theme: Res<UiTheme>,
+    loc: Res<Localization>,
+    scenario: Res<Scenario>,
+    ruleset: Res<RuleSet>,
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    result: Option<Res<GameResult>>,
+    mut ui_materials: ResMut<Assets<ColorMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut minimap: ResMut<MinimapState>,
+    roots: Query<(Entity, &ScreenRoot)>,
+) {
+    let current = *screen.current();
+    // The HUD updates its own texts when the language changes
+    let stale = match current {
+        AppScreen::InGame => false,
+        AppScreen::NewGameSetup => scenario.is_changed() || loc.is_changed(),
+        AppScreen::Settings => settings.is_changed() || rebinding.is_changed() || loc.is_changed(),
+        _ => loc.is_changed(),
+    };
+    let game_running = screen.inactives().contains(&AppScreen::InGame);
+
+    let mut shown = false;
+    for (entity, root) in roots.iter() {
+        let keep = if root.screen == current {
+            !stale
+        } else {
+            root.screen == AppScreen::InGame && game_running
+        };
+        if keep {
+            shown |= root.screen == current;
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    if shown {
+        return;
+    }
+
+    let root = match current {
+        AppScreen::MainMenu => main_menu(&mut commands, &theme, &loc),
+        AppScreen::NewGameSetup => new_game_setup(&mut commands, &theme, &loc, &scenario),
+        AppScreen::InGame => setup::hud(
+            &mut commands,
+            &theme,
+            &loc,
+            &ruleset,
+            &mut ui_materials,
+            &mut textures,
+            &mut minimap,
+        ),
+        AppScreen::Paused => pause_menu(&mut commands, &theme, &loc),
+        AppScreen::Settings => settings_screen(&mut commands, &theme, &loc, &settings, &rebinding),
+        AppScreen::GameOver => game_over(&mut commands, &theme, &loc, result.as_deref()),
+    };
+    commands.entity(root).insert(ScreenRoot { screen: current });
+}
+
+fn next<T: Copy + PartialEq>(options: &[T], current: T) -> T {
+    let index = options.iter().position(|option| *option == current);
+    options[index.map_or(0, |index| (index + 1) % options.len())]
+}
+
+pub fn handle_menu_actions(
+    mut actions: EventReader<UiActionEvent>,
+    mut screen: ResMut<State<AppScreen>>,
+    mut scenario: ResMut<Scenario>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    for event in actions.iter() {
+        let result = match event.action {
+            UiAction::NewGame => screen.set(AppScreen::NewGameSetup),
+            UiAction::StartGame => screen.set(AppScreen::InGame),
+            UiAction::Resume => screen.pop(),
+            UiAction::OpenSettings => screen.push(AppScreen::Settings),
+            UiAction::CloseSettings => {
+                settings.save();
+                rebinding.slot = None;
+                screen.pop()
+            }
+            UiAction::ToMainMenu => screen.replace(AppScreen::MainMenu),
+            UiAction::Quit => {
+                app_exit.send(AppExit);
+                Ok(())
+            }
+            UiAction::CyclePlanetShape => {
+                let shapes: Vec<PlanetShape> = std::iter::once(PlanetShape::HexagonCap)
+                    .chain(
+                        SPHERE_SUBDIVISIONS
+                            .iter()
+                            .map(|subdivisions| PlanetShape::Sphere {
+                                subdivisions: *subdivisions,
+                            }),
+                    )
+                    .collect();
+                scenario.planet_shape = next(&shapes, scenario.planet_shape);
+                Ok(())
+            }
+            UiAction::CycleMapSize => {
+                let size = next(&FLAT_MAP_SIZES, scenario.width);
+                scenario.width = size;
+                scenario.height = size;
+                Ok(())
+            }
+            UiAction::CycleWrap => {
+                let wraps = [WrapMode::None, WrapMode::Horizontal, WrapMode::Torus];
+                scenario.wrap = next(&wraps, scenario.wrap);
+                Ok(())
+            }
+            UiAction::CycleVariant(control) => {
+                let variants = &mut scenario.variants;
+                match control {
+                    VariantControl::FogOfWar => {
+                        let radius = variants.fog_of_war.map(|fog| fog.vision_radius);
+                        variants.fog_of_war = next(&VISION_RADII, radius)
+                            .map(|vision_radius| FogOfWar { vision_radius });
+                    }
+                    VariantControl::Trees => variants.no_trees = !variants.no_trees,
+                    VariantControl::TreasuryCap => {
+                        variants.treasury_cap = next(&TREASURY_CAPS, variants.treasury_cap)
+                    }
+                    VariantControl::StartingPositions => {
+                        let positions = [
+                            None,
+                            Some(StartingPositions::Random),
+                            Some(StartingPositions::Clustered),
+                        ];
+                        variants.starting_positions = next(&positions, variants.starting_positions);
+                    }
+                    VariantControl::TerrainIncome => {
+                        variants.terrain_income = !variants.terrain_income
+                    }
+                    VariantControl::SuddenDeath => {
+                        variants.sudden_death = next(&SUDDEN_DEATH_ROUNDS, variants.sudden_death)
+                    }
+                }
+                Ok(())
+            }
+            UiAction::Adjust(control, steps) => {
+                let settings = &mut *settings;
+                let (value, step) = match control {
+                    SettingControl::MasterVolume => {
+                        (&mut settings.audio.master_volume, VOLUME_STEP)
+                    }
+                    SettingControl::MusicVolume => (&mut settings.audio.music_volume, VOLUME_STEP),
+                    SettingControl::EffectsVolume => {
+                        (&mut settings.audio.effects_volume, VOLUME_STEP)
+                    }
+                    SettingControl::CameraSensitivity => {
+                        (&mut settings.camera_sensitivity, SENSITIVITY_STEP)
+                    }
+                };
+                *value += step * steps as f32;
+                *settings = settings.clone().sanitized();
+                Ok(())
+            }
+            UiAction::ToggleFullscreen => {
+                settings.graphics.fullscreen = !settings.graphics.fullscreen;
+                Ok(())
+            }
+            UiAction::ToggleVsync => {
+                settings.graphics.vsync = !settings.graphics.vsync;
+                Ok(())
+            }
+            UiAction::CycleLanguage => {
+                let locales: Vec<&str> = LOCALES.iter().map(|(id, _)| *id).collect();
+                let language = next(&locales, settings.language.as_str()).to_string();
+                settings.language = language;
+                Ok(())
+            }
+            UiAction::Rebind(slot) => {
+                rebinding.slot = Some(slot);
+                Ok(())
+            }
+            // In game actions are handled by handle_ui_actions
+            UiAction::NextTurn
+            | UiAction::ConfirmEndTurn
+            | UiAction::KeepPlaying
+            | UiAction::Buy(_) => Ok(()),
+        };
+        if let Err(error) = result {
+            warn!("Ignoring {:?}: {:?}", event.action, error);
+        }
+    }
+}
+
+// Pause pauses and resumes the game, and like Cancel leaves the settings
+pub fn pause_system(
+    actions: Res<ActionState>,
+    rebinding: Res<Rebinding>,
+    settings: Res<Settings>,
+    mut screen: ResMut<State<AppScreen>>,
+) {
+    // While rebinding the next input goes to the binding
+    if rebinding.slot.is_some() {
+        return;
+    }
+    let pause = actions.just_pressed(Action::Pause);
+    let cancel = actions.just_pressed(Action::Cancel);
+    let result = match screen.current() {
+        AppScreen::InGame if pause => screen.push(AppScreen::Paused),
+        AppScreen::Paused if pause || cancel => screen.pop(),
+        AppScreen::Settings if pause || cancel => {
+            settings.save();
+            screen.pop()
+        }
+        _ => Ok(()),
+    };
+    if let Err(error) = result {
+        warn!("Ignoring pause: {:?}", error);
+    }
+}
+
+// Binds the next key, mouse button or gamepad button pressed to the binding that waits for one, escape cancels.
+// Runs before the buttons, so the click that started rebinding isn't taken as the new binding.
+pub fn rebind_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+) {
+    let slot = match rebinding.slot {
+        Some(slot) => slot,
+        None => return,
+    };
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        rebinding.slot = None;
+        return;
+    }
+    let binding = keyboard_input
+        .get_just_pressed()
+        .next()
+        .map(|key| InputBinding::Key(*key))
+        .or_else(|| {
+            mouse_button_input
+                .get_just_pressed()
+                .next()
+                .map(|button| InputBinding::Mouse(*button))
+        })
+        .or_else(|| {
+            gamepad_input
+                .get_just_pressed()
+                .next()
+                .map(|button| InputBinding::GamepadButton(button.1))
+        });
+    if let Some(binding) = binding {
+        if settings.rebind(slot, binding) {
+            rebinding.slot = None;
+        }
+    }
+}
@@ -1,179 +1,146 @@
 use bevy::prelude::*;
 
 use super::components::*;
-use super::components::*;
+use super::helpers::button_label;
 use super::types::*;
-pub fn setup(
-    mut commands: Commands,
-    mut button_materials: Res<ButtonMaterials>,
-    asset_server: Res<AssetServer>,
-    mut ui_materials: ResMut<Assets<ColorMaterial>>,
-) {
+use super::widgets;
+use crate::gameplay::ruleset::RuleSet;
+use crate::localization::Localization;
+
+pub fn setup(mut commands: Commands) {
     // ui camera
     commands.spawn_bundle(UiCameraBundle::default());
+}
+
+// The in game HUD, the minimap starts over for the planet of the new game
+pub fn hud(
+    commands: &mut Commands,
+    theme: &UiTheme,
+    loc: &Localization,
+    ruleset: &RuleSet,
+    ui_materials: &mut Assets<ColorMaterial>,
+    textures: &mut Assets<Texture>,
+    minimap: &mut MinimapState,
+) -> Entity {
+    minimap.texture = textures.add(super::helpers::new_minimap_texture(1, 1));
+    minimap.layout = None;
+    let minimap_material = ui_materials.add(minimap.texture.clone().into());
+
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
                 size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                justify_content: JustifyContent::SpaceBetween,
                 ..Default::default()
             },
-            material: ui_materials.add(Color::NONE.into()),
+            material: theme.transparent.clone(),
             ..Default::default()
         })
         .with_children(|parent| {
-            // left vertical fill (content)
-            parent
-                .spawn_bundle(NodeBundle {
-                    style: Style {
-                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                        align_items: AlignItems::FlexEnd,
+            // selection info, top left
+            widgets::panel(
+                parent,
+                theme,
+                Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Px(10.0),
+                        top: Val::Px(10.0),
                         ..Default::default()
                     },
-                    material: ui_materials.add(Color::NONE.into()),
+                    flex_direction: FlexDirection::ColumnReverse,
                     ..Default::default()
-                })
-                .with_children(|parent| {
-                    // text
-                    parent
-                        .spawn_bundle(TextBundle {
-                            style: Style {
-                                margin: Rect::all(Val::Px(5.0)),
-                                ..Default::default()
-                            },
-                            text: Text::with_section(
-                                "Turn",
-                                TextStyle {
-                                    font: asset_server.load("fonts/Satisfy-Regular.ttf"),
-                                    font_size: 30.0,
-                                    color: Color::WHITE,
-                                },
-                                Default::default(),
-                            ),
-                            ..Default::default()
-                        })
-                        .insert(Turn);
-                });
-            parent
-                .spawn_bundle(NodeBundle {
-                    style: Style {
-                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                        align_items: AlignItems::FlexEnd,
+                },
+            )
+            .with_children(|parent| {
+                widgets::label(parent, theme, "", theme.font_size).insert(Turn);
+                widgets::label(parent, theme, "", theme.small_font_size).insert(Resources);
+                widgets::list(parent, theme, Vec::new()).insert(Units);
+                widgets::label(parent, theme, "", theme.small_font_size).insert(Tile);
+            });
+
+            // purchases, bottom left, with the tooltips above them
+            widgets::group(
+                parent,
+                theme,
+                Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Px(10.0),
+                        bottom: Val::Px(10.0),
                         ..Default::default()
                     },
-                    material: ui_materials.add(Color::NONE.into()),
+                    flex_direction: FlexDirection::ColumnReverse,
                     ..Default::default()
-                })
-                .with_children(|parent| {
-                    parent
-                        .spawn_bundle(TextBundle {
-                            style: Style {
-                                margin: Rect::all(Val::Px(5.0)),
-                                ..Default::default()
-                            },
-                            text: Text::with_section(
-                                "Resources",
-                                TextStyle {
-                                    font: asset_server.load("fonts/Satisfy-Regular.ttf"),
-                                    font_size: 30.0,
-                                    color: Color::WHITE,
-                                },
-                                Default::default(),
-                            ),
-                            ..Default::default()
-                        })
-                        .insert(Resources);
+                },
+            )
+            .with_children(|parent| {
+                widgets::tooltip_panel(
+                    parent,
+                    theme,
+                    Style {
+                        max_size: Size::new(Val::Px(400.0), Val::Undefined),
+                        padding: Rect::all(Val::Px(5.0)),
+                        ..Default::default()
+                    },
+                );
+                widgets::group(parent, theme, Style::default()).with_children(|parent| {
+                    for item in PURCHASE_ITEMS.iter() {
+                        widgets::button(
+                            parent,
+                            theme,
+                            UiAction::Buy(*item),
+                            button_label(loc, ruleset, UiAction::Buy(*item)).unwrap_or_default(),
+                            Size::new(Val::Px(120.0), Val::Px(50.0)),
+                        )
+                        .insert(Tooltip {
+                            text: String::new(),
+                        });
+                    }
                 });
-            parent
-                .spawn_bundle(NodeBundle {
-                    style: Style {
-                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                        align_items: AlignItems::FlexEnd,
+            });
+
+            // next turn, bottom right
+            widgets::group(
+                parent,
+                theme,
+                Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        right: Val::Px(10.0),
+                        bottom: Val::Px(10.0),
                         ..Default::default()
                     },
-                    material: ui_materials.add(Color::NONE.into()),
                     ..Default::default()
-                })
-                .with_children(|parent| {
-                    parent
-                        .spawn_bundle(TextBundle {
-                            style: Style {
-                                margin: Rect::all(Val::Px(5.0)),
-                                ..Default::default()
-                            },
-                            text: Text::with_section(
-                                "Units",
-                                TextStyle {
-                                    font: asset_server.load("fonts/Satisfy-Regular.ttf"),
-                                    font_size: 30.0,
-                                    color: Color::WHITE,
-                                },
-                                Default::default(),
-                            ),
-                            ..Default::default()
-                        })
-                        .insert(Units);
-                });
-            // Selected
+                },
+            )
+            .with_children(|parent| {
+                widgets::button(
+                    parent,
+                    theme,
+                    UiAction::NextTurn,
+                    button_label(loc, ruleset, UiAction::NextTurn).unwrap_or_default(),
+                    Size::new(Val::Px(150.0), Val::Px(65.0)),
+                );
+            });
+
+            // minimap, top right, sized once its layout is known
             parent
-                .spawn_bundle(NodeBundle {
+                .spawn_bundle(ImageBundle {
                     style: Style {
-                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                        align_items: AlignItems::FlexEnd,
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            right: Val::Px(10.0),
+                            top: Val::Px(10.0),
+                            ..Default::default()
+                        },
+                        size: Size::new(Val::Px(MINIMAP_WIDTH), Val::Px(MINIMAP_WIDTH)),
                         ..Default::default()
                     },
-                    material: ui_materials.add(Color::NONE.into()),
+                    material: minimap_material,
                     ..Default::default()
                 })
-                .with_children(|parent| {
-                    parent
-                        .spawn_bundle(TextBundle {
-                            style: Style {
-                                margin: Rect::all(Val::Px(5.0)),
-                                ..Default::default()
-                            },
-                            text: Text::with_section(
-                                "Tile",
-                                TextStyle {
-                                    font: asset_server.load("fonts/Satisfy-Regular.ttf"),
-                                    font_size: 30.0,
-                                    color: Color::WHITE,
-                                },
-                                Default::default(),
-                            ),
-                            ..Default::default()
-                        })
-                        .insert(Tile);
-                });
-        });
-    // ui camera
-    commands
-        .spawn_bundle(ButtonBundle {
-            style: Style {
-                size: Size::new(Val::Px(150.0), Val::Px(65.0)),
-                // center button
-                margin: Rect::all(Val::Auto),
-                // horizontally center child text
-                justify_content: JustifyContent::Center,
-                // vertically center child text
-                align_items: AlignItems::Center,
-                ..Default::default()
-            },
-            material: button_materials.normal.clone(),
-            ..Default::default()
+                .insert(Interaction::default())
+                .insert(Minimap);
         })
-        .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text::with_section(
-                    "Next Turn",
-                    TextStyle {
-                        font: asset_server.load("fonts/Satisfy-Regular.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
-                    },
-                    Default::default(),
-                ),
-                ..Default::default()
-            });
-        });
+        .id()
 }
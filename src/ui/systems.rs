@@ -2,67 +2,438 @@ use super::components::*;
 use super::types::*;
 use bevy::prelude::*;
 
+use super::helpers;
 use crate::gameplay::components::*;
+use crate::gameplay::helpers::{build_board, hex_world_position};
+use crate::gameplay::ruleset::RuleSet;
+use crate::gameplay::topology::HexTopology;
+use crate::localization::Localization;
+use crate::orbit_camera::{CameraCommand, OrbitCamera};
+use bevy::render::camera::Camera;
+use bevy::utils::HashMap;
 
-pub fn update_units(mut units: Query<&mut Text, With<Units>>) {
-    for mut unit in units.iter_mut() {
-        for mut section in unit.sections.iter_mut() {}
+// Recomputes what the HUD shows when the selection, the board or the turn changes
+pub fn update_selection_summary(
+    selection: Res<Selection>,
+    grid: Res<HexGrid>,
+    ruleset: Res<RuleSet>,
+    state: Res<State<GameState>>,
+    tiles: Query<(
+        &GridPosition,
+        &TerrainType,
+        &Team,
+        Option<&Occupant>,
+        Option<&MovedTag>,
+        Option<&Resource>,
+    )>,
+    mut selection_events: EventReader<SelectionChanged>,
+    mut moved_events: EventReader<UnitMoved>,
+    mut purchased_events: EventReader<UnitPurchased>,
+    mut turn_events: EventReader<TurnStarted>,
+    changed_treasuries: Query<(), Changed<Resource>>,
+    turn_order: Res<TurnOrder>,
+    vision: Res<TeamVision>,
+    mut summary: ResMut<SelectionSummary>,
+) {
+    let dirty = selection_events.iter().count() > 0
+        || moved_events.iter().count() > 0
+        || purchased_events.iter().count() > 0
+        || turn_events.iter().count() > 0
+        || changed_treasuries.iter().next().is_some()
+        || ruleset.is_changed()
+        || vision.is_changed();
+    if !dirty {
+        return;
+    }
+
+    // Provinces that reach out of sight add up as the team on turn remembers them
+    let team = turn_order.team_for_turn(state.current().turn);
+    let board = build_board(&grid, &ruleset, &tiles);
+    let board = match vision.memories.get(&team) {
+        Some(memory) => memory.known_board(&board),
+        None => board,
+    };
+    let new_summary = match selection.primary() {
+        // Tiles out of sight tell nothing about what stands on them
+        Some(coord) if vision.sees(grid.coord_to_index(coord)) => SelectionSummary {
+            province: helpers::province_summary(&board, coord),
+            tile: helpers::tile_summary(&board, coord),
+        },
+        _ => SelectionSummary::default(),
+    };
+    // Only touch the resource when something differs, so the texts don't get rebuilt for nothing
+    if *summary != new_summary {
+        *summary = new_summary;
+    }
+}
+
+pub fn update_units(
+    summary: Res<SelectionSummary>,
+    loc: Res<Localization>,
+    mut units: Query<&mut List, With<Units>>,
+) {
+    if !summary.is_changed() && !loc.is_changed() {
+        return;
+    }
+    let items: Vec<String> = match summary.province.as_ref() {
+        Some(province) => UNIT_TIERS
+            .iter()
+            .zip(province.units.iter())
+            .map(|(tier, count)| helpers::unit_count(&loc, *tier, *count))
+            .collect(),
+        None => Vec::new(),
+    };
+    for mut list in units.iter_mut() {
+        list.items = items.clone();
     }
 }
 
-pub fn update_resources(mut units: Query<&mut Text, With<Resources>>) {
-    for mut unit in units.iter_mut() {
-        for mut section in unit.sections.iter_mut() {}
+pub fn update_resources(
+    summary: Res<SelectionSummary>,
+    loc: Res<Localization>,
+    mut resources: Query<&mut Text, With<Resources>>,
+) {
+    if !summary.is_changed() && !loc.is_changed() {
+        return;
+    }
+    let value = match summary.province.as_ref() {
+        Some(province) => {
+            let treasury = province
+                .treasury
+                .map_or("-".to_string(), |treasury| treasury.to_string());
+            loc.format(
+                "hud-economy",
+                &[
+                    ("treasury", treasury.into()),
+                    ("income", province.income.into()),
+                    ("upkeep", province.upkeep.into()),
+                    ("net", format!("{:+}", province.net()).into()),
+                ],
+            )
+        }
+        None => String::new(),
+    };
+    for mut resource in resources.iter_mut() {
+        for mut section in resource.sections.iter_mut() {
+            section.value = value.clone();
+        }
     }
 }
 
-pub fn update_turns(state: Res<State<GameState>>, mut turns: Query<&mut Text, With<Turn>>) {
+// The state reports a change every frame, so the text follows the turn number instead
+pub fn update_turns(
+    state: Res<State<GameState>>,
+    loc: Res<Localization>,
+    spawned: Query<(), Added<Turn>>,
+    mut shown_turn: Local<Option<i32>>,
+    mut turns: Query<&mut Text, With<Turn>>,
+) {
+    let turn = state.current().turn;
+    if *shown_turn == Some(turn) && !loc.is_changed() && spawned.iter().next().is_none() {
+        return;
+    }
+    *shown_turn = Some(turn);
+    let value = loc.format("hud-turn", &[("turn", turn.into())]);
     for mut turn in turns.iter_mut() {
         for mut section in turn.sections.iter_mut() {
-            section.value = format!("Turn {}", state.current().turn);
+            section.value = value.clone();
         }
     }
 }
 
 pub fn update_tile(
+    summary: Res<SelectionSummary>,
+    loc: Res<Localization>,
     mut tiles: Query<&mut Text, With<Tile>>,
-    terrain: Query<&TerrainType, With<SelectedTag>>,
 ) {
+    if !summary.is_changed() && !loc.is_changed() {
+        return;
+    }
+    let value = match summary.tile.as_ref() {
+        Some(tile) => loc.format(
+            "hud-tile",
+            &[
+                ("terrain", helpers::terrain_name(&loc, tile.terrain).into()),
+                ("team", tile.team.into()),
+                (
+                    "occupant",
+                    helpers::occupant_name(&loc, tile.occupant).into(),
+                ),
+                ("defense", tile.defense.into()),
+            ],
+        ),
+        None => String::new(),
+    };
     for mut tile in tiles.iter_mut() {
         for mut section in tile.sections.iter_mut() {
-            for t in terrain.iter() {
-                if *t == TerrainType::Land {
-                    section.value = format!("Lang");
-                } else {
-                    section.value = format!("Water");
+            section.value = value.clone();
+        }
+    }
+}
+
+// Works out which cell every minimap pixel shows, as soon as the planet can tell where its cells are
+pub fn build_minimap_layout(
+    grid: Res<HexGrid>,
+    planets: Query<(&Handle<Mesh>, &GlobalTransform, &Planet)>,
+    meshes: Res<Assets<Mesh>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut minimap: ResMut<MinimapState>,
+    mut minimap_nodes: Query<&mut Style, With<Minimap>>,
+) {
+    if minimap.layout.is_some() {
+        return;
+    }
+    let mut cell_positions = HashMap::default();
+    for coord in grid.coords() {
+        match hex_world_position(&grid, &planets, &meshes, coord) {
+            Some(position) => cell_positions.insert(coord, position),
+            // The planet mesh is still loading
+            None => return,
+        };
+    }
+    let surface = match (grid.sphere(), planets.iter().next()) {
+        (Some(_), Some((_, transform, _))) => MinimapSurface::Sphere {
+            center: transform.translation,
+            radius: cell_positions
+                .values()
+                .map(|position| position.distance(transform.translation))
+                .fold(0.0, f32::max),
+        },
+        _ => MinimapSurface::Plane {
+            height: cell_positions.values().map(|position| position.y).sum::<f32>()
+                / cell_positions.len().max(1) as f32,
+        },
+    };
+
+    let layout = helpers::build_minimap_layout(&grid, cell_positions, surface);
+    textures.set(
+        minimap.texture.clone(),
+        helpers::new_minimap_texture(layout.width, layout.height),
+    );
+    // Keep the hexes in proportion: columns are half a hex apart and rows 0.865
+    let aspect = match grid.sphere() {
+        Some(_) => 0.5,
+        None => (layout.height as f32 * 0.865) / (layout.width as f32 * 0.5),
+    };
+    for mut style in minimap_nodes.iter_mut() {
+        style.size = Size::new(Val::Px(MINIMAP_WIDTH), Val::Px(MINIMAP_WIDTH * aspect));
+    }
+    minimap.layout = Some(layout);
+}
+
+// Redraws the minimap when tiles change hands or the camera moves
+pub fn update_minimap(
+    minimap: Res<MinimapState>,
+    grid: Res<HexGrid>,
+    vision: Res<TeamVision>,
+    ruleset: Res<RuleSet>,
+    tiles: Query<(&GridPosition, &TerrainType, &Team)>,
+    changed_tiles: Query<(), Or<(Changed<Team>, Changed<TerrainType>)>>,
+    cameras: Query<(&Camera, &GlobalTransform), (With<OrbitCamera>, Changed<GlobalTransform>)>,
+    all_cameras: Query<(&Camera, &GlobalTransform), With<OrbitCamera>>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    let layout = match minimap.layout.as_ref() {
+        Some(layout) => layout,
+        None => return,
+    };
+    let dirty = minimap.is_changed()
+        || vision.is_changed()
+        || ruleset.is_changed()
+        || changed_tiles.iter().next().is_some()
+        || cameras.iter().next().is_some();
+    if !dirty {
+        return;
+    }
+    let texture = match textures.get_mut(&minimap.texture) {
+        Some(texture) => texture,
+        None => return,
+    };
+
+    let tile_color = |terrain: TerrainType, team: i32| match terrain {
+        TerrainType::Water => water_color(),
+        _ if !ruleset.passable(terrain) => impassable_color(),
+        _ => team_color(team),
+    };
+    // Unexplored tiles stay black, remembered ones show how they were last seen
+    let colors: HashMap<IVec2, Color> = tiles
+        .iter()
+        .filter_map(|(position, terrain, team)| {
+            let index = grid.coord_to_index(position.position);
+            let color = match vision.visibility(index) {
+                TileVisibility::Visible => tile_color(*terrain, team.number),
+                TileVisibility::Remembered => {
+                    let tile = vision.known_tile(index)?;
+                    remembered_color(tile_color(tile.terrain, tile.team))
                 }
+                TileVisibility::Unexplored => return None,
+            };
+            Some((position.position, color))
+        })
+        .collect();
+    for (index, cell) in layout.pixel_cells.iter().enumerate() {
+        let pixel = IVec2::new(
+            (index as u32 % layout.width) as i32,
+            (index as u32 / layout.width) as i32,
+        );
+        let color = cell
+            .and_then(|cell| colors.get(&cell).copied())
+            .unwrap_or(Color::BLACK);
+        helpers::put_pixel(texture, layout.width, pixel, color);
+    }
+
+    // Outline of what the camera sees, corners that miss the planet are left out
+    for (camera, transform) in all_cameras.iter() {
+        let inverse_view_projection =
+            (camera.projection_matrix * transform.compute_matrix().inverse()).inverse();
+        let corners: Vec<Option<IVec2>> = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .iter()
+            .map(|(x, y)| {
+                let point = inverse_view_projection * Vec4::new(*x, *y, 0.5, 1.0);
+                let direction = (point.truncate() / point.w - transform.translation).normalize();
+                helpers::intersect_surface(layout.surface, transform.translation, direction)
+                    .and_then(|hit| helpers::nearest_cell(layout, hit))
+                    .and_then(|cell| layout.cell_pixels.get(&cell).copied())
+            })
+            .collect();
+        for index in 0..corners.len() {
+            if let (Some(from), Some(to)) = (corners[index], corners[(index + 1) % corners.len()]) {
+                helpers::draw_line(texture, layout.width, from, to, Color::WHITE);
             }
         }
     }
 }
 
-pub fn button_system(
-    button_materials: Res<ButtonMaterials>,
-    mut state: ResMut<State<GameState>>,
-    mut interaction_query: Query<
-        (&Interaction, &mut Handle<ColorMaterial>, &Children),
-        (Changed<Interaction>, With<Button>),
-    >,
-    mut text_query: Query<&mut Text>,
+// Clicking or dragging on the minimap centers the camera on the cell under the cursor
+pub fn minimap_click_system(
+    minimap: Res<MinimapState>,
+    windows: Res<Windows>,
+    nodes: Query<(&Interaction, &Node, &GlobalTransform), With<Minimap>>,
+    mut camera_commands: EventWriter<CameraCommand>,
 ) {
-    for (interaction, mut material, children) in interaction_query.iter_mut() {
-        let mut text = text_query.get_mut(children[0]).unwrap();
-        match *interaction {
-            Interaction::Clicked => {
-                *material = button_materials.pressed.clone();
-                let turn = state.current().turn;
-                state.set(GameState { turn: turn + 1 }).unwrap();
-            }
-            Interaction::Hovered => {
-                *material = button_materials.hovered.clone();
+    let layout = match minimap.layout.as_ref() {
+        Some(layout) => layout,
+        None => return,
+    };
+    let cursor = match windows.get_primary().and_then(|window| window.cursor_position()) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    for (interaction, node, transform) in nodes.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        // Ui positions start at the bottom left, image rows at the top
+        let bottom_left = transform.translation.truncate() - node.size * 0.5;
+        let relative = (cursor - bottom_left) / node.size;
+        let pixel = IVec2::new(
+            (relative.x * layout.width as f32) as i32,
+            ((1.0 - relative.y) * layout.height as f32) as i32,
+        );
+        if pixel.x < 0
+            || pixel.y < 0
+            || pixel.x >= layout.width as i32
+            || pixel.y >= layout.height as i32
+        {
+            continue;
+        }
+        let cell = layout.pixel_cells[(pixel.y as u32 * layout.width + pixel.x as u32) as usize];
+        if let Some(position) = cell.and_then(|cell| layout.cell_positions.get(&cell)) {
+            camera_commands.send(CameraCommand::CenterOn(*position));
+        }
+    }
+}
+
+pub fn handle_ui_actions(
+    mut actions: EventReader<UiActionEvent>,
+    mut end_turn_requests: EventWriter<EndTurnRequested>,
+    mut pending_purchase: ResMut<PendingPurchase>,
+    mut prompt: ResMut<EndTurnPrompt>,
+) {
+    for event in actions.iter() {
+        match event.action {
+            UiAction::NextTurn => end_turn_requests.send(EndTurnRequested { confirmed: false }),
+            UiAction::ConfirmEndTurn => {
+                end_turn_requests.send(EndTurnRequested { confirmed: true })
             }
-            Interaction::None => {
-                *material = button_materials.normal.clone();
+            UiAction::KeepPlaying => prompt.unmoved_units = None,
+            UiAction::Buy(item) => pending_purchase.item = Some(item),
+            // Menu actions are handled by screens::handle_menu_actions
+            _ => {}
+        }
+    }
+}
+
+// Disables what the selected province can't afford, or everything when it isn't the province's turn,
+// and explains the purchases in their tooltips
+pub fn update_purchase_buttons(
+    summary: Res<SelectionSummary>,
+    pending_purchase: Res<PendingPurchase>,
+    loc: Res<Localization>,
+    ruleset: Res<RuleSet>,
+    state: Res<State<GameState>>,
+    turn_order: Res<TurnOrder>,
+    mut shown_team: Local<Option<i32>>,
+    mut buttons: Query<(&mut ActionButton, &mut Tooltip)>,
+) {
+    let team = turn_order.team_for_turn(state.current().turn);
+    if *shown_team == Some(team)
+        && !summary.is_changed()
+        && !pending_purchase.is_changed()
+        && !loc.is_changed()
+        && !ruleset.is_changed()
+    {
+        return;
+    }
+    *shown_team = Some(team);
+    let treasury = summary.province.as_ref().and_then(|province| province.treasury);
+    let on_turn = summary
+        .tile
+        .as_ref()
+        .map_or(false, |tile| tile.team == team);
+    for (mut button, mut tooltip) in buttons.iter_mut() {
+        let item = match button.action {
+            UiAction::Buy(item) => item,
+            _ => continue,
+        };
+        let enabled =
+            on_turn && treasury.map_or(false, |treasury| treasury >= ruleset.purchase_cost(item));
+        if button.enabled != enabled {
+            button.enabled = enabled;
+        }
+        let text = match pending_purchase.item {
+            Some(pending) if pending == item => loc.format(
+                "purchase-drop",
+                &[("name", helpers::purchase_name(&loc, item).into())],
+            ),
+            _ => helpers::purchase_tooltip(&loc, &ruleset, item, treasury),
+        };
+        if tooltip.text != text {
+            tooltip.text = text;
+        }
+    }
+}
+
+// The menus are rebuilt instead when the language changes, prices follow the ruleset
+pub fn update_button_labels(
+    loc: Res<Localization>,
+    ruleset: Res<RuleSet>,
+    buttons: Query<(&ActionButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !loc.is_changed() && !ruleset.is_changed() {
+        return;
+    }
+    for (button, children) in buttons.iter() {
+        let value = match helpers::button_label(&loc, &ruleset, button.action) {
+            Some(value) => value,
+            None => continue,
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                for section in text.sections.iter_mut() {
+                    section.value = value.clone();
+                }
             }
         }
     }
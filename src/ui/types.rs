@@ -1,18 +1,229 @@
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 
+use crate::gameplay::components::{Occupant, Purchase, TerrainType, UnitTier};
+use crate::settings::BindingSlot;
+
+// Look of one kind of button, per interaction state
 pub struct ButtonMaterials {
     pub normal: Handle<ColorMaterial>,
     pub hovered: Handle<ColorMaterial>,
     pub pressed: Handle<ColorMaterial>,
+    pub disabled: Handle<ColorMaterial>,
+}
+
+impl ButtonMaterials {
+    pub fn new(
+        materials: &mut Assets<ColorMaterial>,
+        normal: Color,
+        hovered: Color,
+        pressed: Color,
+        disabled: Color,
+    ) -> Self {
+        ButtonMaterials {
+            normal: materials.add(normal.into()),
+            hovered: materials.add(hovered.into()),
+            pressed: materials.add(pressed.into()),
+            disabled: materials.add(disabled.into()),
+        }
+    }
+
+    pub fn for_state(&self, interaction: Interaction, enabled: bool) -> &Handle<ColorMaterial> {
+        match (enabled, interaction) {
+            (false, _) => &self.disabled,
+            (true, Interaction::Clicked) => &self.pressed,
+            (true, Interaction::Hovered) => &self.hovered,
+            (true, Interaction::None) => &self.normal,
+        }
+    }
 }
 
-impl FromWorld for ButtonMaterials {
+// Everything the widgets need to look alike
+pub struct UiTheme {
+    pub font: Handle<Font>,
+    pub text_color: Color,
+    pub font_size: f32,
+    pub small_font_size: f32,
+    pub button_font_size: f32,
+    pub button: ButtonMaterials,
+    pub panel: Handle<ColorMaterial>,
+    pub tooltip: Handle<ColorMaterial>,
+    pub transparent: Handle<ColorMaterial>,
+}
+
+impl FromWorld for UiTheme {
     fn from_world(world: &mut World) -> Self {
+        let font = world
+            .get_resource::<AssetServer>()
+            .unwrap()
+            .load("fonts/Satisfy-Regular.ttf");
         let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
-        ButtonMaterials {
-            normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
-            hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
-            pressed: materials.add(Color::rgb(0.35, 0.75, 0.35).into()),
+        UiTheme {
+            font,
+            text_color: Color::rgb(0.9, 0.9, 0.9),
+            font_size: 30.0,
+            small_font_size: 20.0,
+            button_font_size: 24.0,
+            button: ButtonMaterials::new(
+                &mut materials,
+                Color::rgb(0.15, 0.15, 0.15),
+                Color::rgb(0.25, 0.25, 0.25),
+                Color::rgb(0.35, 0.75, 0.35),
+                Color::rgba(0.15, 0.15, 0.15, 0.5),
+            ),
+            panel: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.4).into()),
+            tooltip: materials.add(Color::rgba(0.05, 0.05, 0.05, 0.85).into()),
+            transparent: materials.add(Color::NONE.into()),
         }
     }
 }
+
+// What a button does when it is clicked
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UiAction {
+    NextTurn,
+    // Answers to the end turn prompt
+    ConfirmEndTurn,
+    KeepPlaying,
+    // Starts dragging the purchase onto the map
+    Buy(Purchase),
+    // Moving between screens
+    NewGame,
+    StartGame,
+    Resume,
+    OpenSettings,
+    // Saves the settings and goes back to the screen that opened them
+    CloseSettings,
+    ToMainMenu,
+    Quit,
+    // New game setup
+    CyclePlanetShape,
+    CycleMapSize,
+    CycleWrap,
+    CycleVariant(VariantControl),
+    // Settings, steps a value up or down
+    Adjust(SettingControl, i32),
+    ToggleFullscreen,
+    ToggleVsync,
+    CycleLanguage,
+    // Binds the next key that is pressed
+    Rebind(BindingSlot),
+}
+
+// Rule variants of the new game setup
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VariantControl {
+    FogOfWar,
+    Trees,
+    TreasuryCap,
+    StartingPositions,
+    TerrainIncome,
+    SuddenDeath,
+}
+
+// Settings that are changed in steps
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SettingControl {
+    MasterVolume,
+    MusicVolume,
+    EffectsVolume,
+    CameraSensitivity,
+}
+
+// The binding on the settings screen that waits for a key
+#[derive(Default)]
+pub struct Rebinding {
+    pub slot: Option<BindingSlot>,
+}
+
+// Sent by every ActionButton that gets clicked
+pub struct UiActionEvent {
+    pub action: UiAction,
+}
+
+pub const MINIMAP_WIDTH: f32 = 200.0;
+// Spheres are drawn as an equirectangular map of this many pixels wide, and half as high
+pub const MINIMAP_SPHERE_RESOLUTION: u32 = 96;
+
+// The surface the minimap frustum rays hit
+#[derive(Clone, Copy, Debug)]
+pub enum MinimapSurface {
+    Plane { height: f32 },
+    Sphere { center: Vec3, radius: f32 },
+}
+
+// Which cell every minimap pixel shows, and where every cell is in the world and on the minimap
+pub struct MinimapLayout {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_cells: Vec<Option<IVec2>>,
+    pub cell_pixels: HashMap<IVec2, IVec2>,
+    pub cell_positions: HashMap<IVec2, Vec3>,
+    pub surface: MinimapSurface,
+}
+
+#[derive(Default)]
+pub struct MinimapState {
+    pub texture: Handle<Texture>,
+    // Built once the planet mesh is loaded
+    pub layout: Option<MinimapLayout>,
+}
+
+pub fn team_color(team: i32) -> Color {
+    match team {
+        0 => Color::rgb(0.6, 0.6, 0.5),
+        1 => Color::rgb(0.8, 0.2, 0.2),
+        2 => Color::rgb(0.2, 0.4, 0.9),
+        3 => Color::rgb(0.9, 0.8, 0.2),
+        4 => Color::rgb(0.3, 0.7, 0.3),
+        _ => Color::rgb(0.7, 0.3, 0.8),
+    }
+}
+
+pub fn water_color() -> Color {
+    Color::rgb(0.05, 0.15, 0.35)
+}
+
+// Land nobody can stand on, like mountains
+pub fn impassable_color() -> Color {
+    Color::rgb(0.45, 0.42, 0.4)
+}
+
+// Tiles that are out of sight show faded, as they were last seen
+pub fn remembered_color(color: Color) -> Color {
+    Color::rgb(color.r() * 0.5, color.g() * 0.5, color.b() * 0.5)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProvinceSummary {
+    // None when the province has no capital to keep money in
+    pub treasury: Option<i32>,
+    pub income: i32,
+    pub upkeep: i32,
+    // Indexed like UNIT_TIERS
+    pub units: [i32; 4],
+}
+
+impl ProvinceSummary {
+    pub fn net(&self) -> i32 {
+        self.income - self.upkeep
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileSummary {
+    pub terrain: TerrainType,
+    pub team: i32,
+    pub occupant: Option<Occupant>,
+    pub defense: i32,
+}
+
+// What the HUD shows about the selection, only recomputed when something happened to it
+#[derive(Default, Debug, PartialEq)]
+pub struct SelectionSummary {
+    pub province: Option<ProvinceSummary>,
+    pub tile: Option<TileSummary>,
+}
+
+// What the purchase panel offers, higher tiers are made by merging
+pub const PURCHASE_ITEMS: [Purchase; 2] = [Purchase::Unit(UnitTier::Peasant), Purchase::Castle];
@@ -0,0 +1,220 @@
+// Small widget toolkit on top of the bevy ui bundles, so every screen looks and behaves alike.
+// Every widget is spawned as a child and returns its EntityCommands, to insert markers or add children.
+use super::components::*;
+use super::types::*;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+
+pub fn text_style(theme: &UiTheme, font_size: f32) -> TextStyle {
+    TextStyle {
+        font: theme.font.clone(),
+        font_size,
+        color: theme.text_color,
+    }
+}
+
+pub fn label<'a, 'b>(
+    parent: &'b mut ChildBuilder<'a, '_>,
+    theme: &UiTheme,
+    text: impl Into<String>,
+    font_size: f32,
+) -> EntityCommands<'a, 'b> {
+    parent.spawn_bundle(TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(5.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(text, text_style(theme, font_size), Default::default()),
+        ..Default::default()
+    })
+}
+
+// Container for other widgets, `style` decides the layout
+pub fn panel<'a, 'b>(
+    parent: &'b mut ChildBuilder<'a, '_>,
+    theme: &UiTheme,
+    style: Style,
+) -> EntityCommands<'a, 'b> {
+    parent.spawn_bundle(NodeBundle {
+        style,
+        material: theme.panel.clone(),
+        ..Default::default()
+    })
+}
+
+// Panel without a background, just for layout
+pub fn group<'a, 'b>(
+    parent: &'b mut ChildBuilder<'a, '_>,
+    theme: &UiTheme,
+    style: Style,
+) -> EntityCommands<'a, 'b> {
+    parent.spawn_bundle(NodeBundle {
+        style,
+        material: theme.transparent.clone(),
+        ..Default::default()
+    })
+}
+
+pub fn button<'a, 'b>(
+    parent: &'b mut ChildBuilder<'a, '_>,
+    theme: &UiTheme,
+    action: UiAction,
+    text: impl Into<String>,
+    size: Size<Val>,
+) -> EntityCommands<'a, 'b> {
+    let mut button = parent.spawn_bundle(ButtonBundle {
+        style: Style {
+            size,
+            margin: Rect::all(Val::Px(5.0)),
+            // center the text
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: theme.button.normal.clone(),
+        ..Default::default()
+    });
+    button
+        .insert(ActionButton {
+            action,
+            enabled: true,
+        })
+        .with_children(|parent| {
+            label(parent, theme, text, theme.button_font_size);
+        });
+    button
+}
+
+// Column of labels, change the List component to change them
+pub fn list<'a, 'b>(
+    parent: &'b mut ChildBuilder<'a, '_>,
+    theme: &UiTheme,
+    items: Vec<String>,
+) -> EntityCommands<'a, 'b> {
+    let mut list = group(
+        parent,
+        theme,
+        Style {
+            flex_direction: FlexDirection::ColumnReverse,
+            ..Default::default()
+        },
+    );
+    list.with_children(|parent| {
+        for item in items.iter() {
+            label(parent, theme, item.clone(), theme.small_font_size);
+        }
+    })
+    .insert(List { items });
+    list
+}
+
+// The panel every Tooltip is shown in, hidden while nothing with a tooltip is hovered
+pub fn tooltip_panel<'a, 'b>(
+    parent: &'b mut ChildBuilder<'a, '_>,
+    theme: &UiTheme,
+    style: Style,
+) -> EntityCommands<'a, 'b> {
+    let mut tooltip = parent.spawn_bundle(NodeBundle {
+        style,
+        material: theme.tooltip.clone(),
+        visible: Visible {
+            is_visible: false,
+            is_transparent: true,
+        },
+        ..Default::default()
+    });
+    tooltip.insert(TooltipPanel).with_children(|parent| {
+        label(parent, theme, "", theme.small_font_size).insert(Visible {
+            is_visible: false,
+            is_transparent: true,
+        });
+    });
+    tooltip
+}
+
+// Keeps the button colors in line with their interaction and sends the action of clicked buttons
+pub fn button_system(
+    theme: Res<UiTheme>,
+    mut buttons: Query<
+        (
+            &Interaction,
+            ChangeTrackers<Interaction>,
+            &ActionButton,
+            &mut Handle<ColorMaterial>,
+        ),
+        Or<(Changed<Interaction>, Changed<ActionButton>)>,
+    >,
+    mut actions: EventWriter<UiActionEvent>,
+) {
+    for (interaction, interaction_tracker, button, mut material) in buttons.iter_mut() {
+        let new_material = theme.button.for_state(*interaction, button.enabled);
+        if *material != *new_material {
+            *material = new_material.clone();
+        }
+        if interaction_tracker.is_changed()
+            && *interaction == Interaction::Clicked
+            && button.enabled
+        {
+            actions.send(UiActionEvent {
+                action: button.action,
+            });
+        }
+    }
+}
+
+// Shows the tooltip of whatever is hovered
+pub fn tooltip_system(
+    hovered: Query<(&Interaction, &Tooltip)>,
+    mut panels: Query<(&mut Visible, &Children), With<TooltipPanel>>,
+    mut labels: Query<(&mut Text, &mut Visible), Without<TooltipPanel>>,
+) {
+    let text = hovered
+        .iter()
+        .find(|(interaction, _)| **interaction != Interaction::None)
+        .map(|(_, tooltip)| tooltip.text.clone())
+        .filter(|text| !text.is_empty());
+    for (mut visible, children) in panels.iter_mut() {
+        visible.is_visible = text.is_some();
+        for child in children.iter() {
+            if let Ok((mut label, mut label_visible)) = labels.get_mut(*child) {
+                label_visible.is_visible = text.is_some();
+                if let Some(text) = text.as_ref() {
+                    for section in label.sections.iter_mut() {
+                        if section.value != *text {
+                            section.value = text.clone();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Updates the labels of lists whose items changed, spawning and despawning labels as needed
+pub fn list_system(
+    mut commands: Commands,
+    theme: Res<UiTheme>,
+    lists: Query<(Entity, &List, Option<&Children>), Changed<List>>,
+    mut labels: Query<&mut Text>,
+) {
+    for (entity, list, children) in lists.iter() {
+        let children: Vec<Entity> = children.map_or(Vec::new(), |children| children.to_vec());
+        for (index, item) in list.items.iter().enumerate() {
+            match children.get(index).and_then(|child| labels.get_mut(*child).ok()) {
+                Some(mut text) => {
+                    for section in text.sections.iter_mut() {
+                        section.value = item.clone();
+                    }
+                }
+                None => {
+                    commands.entity(entity).with_children(|parent| {
+                        label(parent, &theme, item.clone(), theme.small_font_size);
+                    });
+                }
+            }
+        }
+        for child in children.iter().skip(list.items.len()) {
+            commands.entity(*child).despawn_recursive();
+        }
+    }
+}